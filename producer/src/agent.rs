@@ -0,0 +1,858 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Agent
+//
+// Every character of the series is an agent writing its own lines. An agent knows its
+// personality from the bible, remembers what was said in the current scene and keeps a memory
+// of the past episodes between the runs. The director picks who speaks next and gives the
+// turn to the agents until the beat is over.
+//
+// The prompts have a system and a user role so they can be sent to a chat model, a completion
+// client receives them as a single text.
+//
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use error_stack::{IntoReport, Result, ResultExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::episode::{default_templates, Beat, Episode, Scene};
+use crate::logger::InContextExt;
+use crate::script::{ScriptLine, ScriptParser};
+use crate::series::{Character, Series};
+use crate::template::TemplateSet;
+use crate::{cdebug, cinfo, CompletionClient, EGeneral, ModelType, PromptRequestInfo};
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use std::sync::Mutex;
+
+    use error_stack::Result;
+    use futures::executor::block_on;
+
+    use crate::episode::{Act, Beat, Episode, Scene};
+    use crate::script::ScriptLine;
+    use crate::series::{Character, Series};
+    use crate::{CompletionClient, EGeneral, ModelType, PromptRequestInfo};
+    use super::{Agent, Director, Memory};
+
+    /// answer the director with a list of names and the agents with a numbered line
+    struct ScriptedClient { next: Mutex<Vec<&'static str>>, prompts: Mutex<Vec<String>> }
+
+    impl ScriptedClient {
+
+        fn new(next: &[&'static str]) -> Self {
+
+            Self { next: Mutex::new(next.iter().rev().copied().collect()), prompts: Mutex::new(Vec::new()) }
+
+        }
+
+        fn prompts(&self, stage: &str) -> Vec<String> {
+
+            self.prompts.lock().unwrap().iter().filter(|p| p.trim_end().ends_with(stage)).cloned().collect()
+
+        }
+
+    }
+
+    impl CompletionClient for ScriptedClient {
+
+        async fn complete(&self, request: PromptRequestInfo) -> Result<String, EGeneral> {
+
+            let mut prompts = self.prompts.lock().unwrap();
+            prompts.push(request.prompt.clone());
+            let n = prompts.len();
+
+            let prompt = request.prompt.trim_end();
+
+            Ok(match prompt.rsplit('\n').next().unwrap() {
+
+                "Next:" => self.next.lock().unwrap().pop().unwrap_or("someone").to_string(),
+                "Line:" => format!("[waves]\nBOLT: Line {}.\nNOVA: Not mine.", n),
+                _ => "- FACT: The coffee comes from the depot\nRELATIONSHIP: Nova - partner in crime\nsomething else".to_string()
+
+            })
+
+        }
+
+    }
+
+    fn series() -> Series {
+
+        let mut series = Series::new("The Night Shift", "Two robots run a diner on the moon");
+        series.characters = vec![Character::new("Bolt"), Character::new("Nova"), Character::new("Gus")];
+        series
+
+    }
+
+    fn episode() -> Episode {
+
+        Episode {
+            number: 3,
+            logline: "The diner runs out of coffee".to_string(),
+            acts: vec![Act {
+                scenes: vec![Scene {
+                    heading: "Diner".to_string(),
+                    summary: "Bolt and Nova find no coffee".to_string(),
+                    beats: vec![
+                        Beat { description: "They argue".to_string(), ..Beat::default() },
+                        Beat { description: "They leave".to_string(), ..Beat::default() },
+                    ]
+                }],
+                ..Act::default()
+            }]
+        }
+
+    }
+
+    #[test]
+    fn director_gives_the_turns() {
+
+        let client = ScriptedClient::new(&["Bolt", "nova.", "END", "Nova", "Gus", "END"]);
+        let series = series();
+        let mut director = Director::new(&client, &series, ModelType::Fastest).with_max_lines(3);
+        let mut episode = episode();
+
+        block_on(director.play_scene(&mut episode, 0, 0)).unwrap();
+
+        let speakers = |beat: &Beat| beat.lines.iter()
+            .filter_map(|l| match l { ScriptLine::Dialogue { speaker, .. } => Some(speaker.clone()), _ => None })
+            .collect::<Vec<_>>();
+
+        let beats = &episode.acts[0].scenes[0].beats;
+
+        // the agent keeps only its own line, Gus is not in the scene so the turn goes to Bolt
+        assert_eq!(speakers(&beats[0]), ["Bolt", "Nova"]);
+        assert_eq!(speakers(&beats[1]), ["Nova", "Bolt"]);
+        assert!(matches!(&beats[0].lines[0], ScriptLine::Direction { text } if text == "waves"));
+        assert!(beats[0].dialogue.starts_with("[waves]\nBOLT: Line 2."));
+
+        // every agent in the scene heard the whole scene, the others nothing
+        assert_eq!(director.agent("Nova").unwrap().scene().len(), 8);
+        assert!(director.agent("Gus").unwrap().scene().is_empty());
+
+        let lines = client.prompts("Line:");
+
+        assert!(lines[1].starts_with("You are Nova"));
+        assert!(lines[1].contains("What was said in the scene so far:\n[waves]\nBOLT: Line 2."));
+        assert!(client.prompts("Next:")[0].contains("Who speaks next among Bolt, Nova?"));
+
+    }
+
+    #[test]
+    fn agents_remember_the_episode() {
+
+        let client = ScriptedClient::new(&["Bolt", "END", "END"]);
+        let series = series();
+        let mut director = Director::new(&client, &series, ModelType::Fastest);
+        let mut episode = episode();
+
+        block_on(director.play_episode(&mut episode)).unwrap();
+        block_on(director.remember(&episode)).unwrap();
+
+        let bolt = &director.agent("Bolt").unwrap().memory;
+
+        assert_eq!(bolt.episodes[0].number, 3);
+        assert_eq!(bolt.episodes[0].summary, "The diner runs out of coffee. Bolt and Nova find no coffee");
+        assert_eq!(bolt.facts, ["The coffee comes from the depot"]);
+        assert_eq!(bolt.relationships["Nova"], "partner in crime");
+
+        // the beat can't end after one reply so Nova spoke too, Gus was not in the scene
+        assert_eq!(director.agent("Nova").unwrap().memory.episodes.len(), 1);
+        assert!(director.agent("Gus").unwrap().memory.episodes.is_empty());
+
+    }
+
+    #[test]
+    fn memory_is_saved_and_given_to_the_agent() {
+
+        let dir = std::env::temp_dir().join(format!("producer-memory-{}", std::process::id()));
+
+        let mut memory = Memory::default();
+        memory.remember_episode(1, "Bolt broke the toaster");
+        memory.remember_episode(1, "Bolt fixed the toaster");
+        memory.learn("Nova hates toast");
+        memory.learn("Nova hates toast");
+        memory.save(&dir, "Bolt").unwrap();
+
+        assert_eq!(Memory::load(&dir, "Gus").unwrap(), Memory::default());
+
+        let memory = Memory::load(&dir, "Bolt").unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(memory.episodes.len(), 1);
+        assert_eq!(memory.facts.len(), 1);
+
+        let series = series();
+        let mut agent = Agent::new(series.characters[0].clone(), memory);
+        agent.hear(&ScriptLine::Note { text: "not heard".to_string() });
+
+        let prompt = crate::episode::default_templates()
+            .render("agent", &agent.context(&series, &Scene::default(), &Beat::default()))
+            .unwrap();
+
+        assert!(prompt.contains("- Episode 1: Bolt fixed the toaster\n"));
+        assert!(prompt.contains("- Nova hates toast\n"));
+        assert!(prompt.contains("as 'BOLT: what you say'"));
+        assert!(agent.scene().is_empty());
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Memory
+//
+/// directory of the memories in the directory of a series
+pub const MEMORY_DIR: &str = "memory";
+//
+// the oldest memories are forgotten past these numbers
+const MAX_EPISODES: usize = 20;
+const MAX_FACTS: usize = 50;
+//
+//
+/// What a character remembers of an episode
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct EpisodeMemory {
+
+    pub number:     u32,
+    pub summary:    String,
+
+}
+//
+//
+/// Long term memory of a character, kept between the episodes
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Memory {
+
+    pub episodes:       Vec<EpisodeMemory>,
+    pub facts:          Vec<String>,
+    /// name of another character and what they are now, added to the ones of the bible
+    pub relationships:  BTreeMap<String, String>,
+
+}
+//
+impl Memory {
+    //
+    /// File of the memory of a character
+    pub fn path(dir: &Path, name: &str) -> PathBuf {
+
+        let file: String = name.to_lowercase().chars().map(|c| if c.is_alphanumeric() { c } else { '-' }).collect();
+
+        dir.join(format!("{}.json", file))
+
+    }
+    //
+    //
+    /// Load the memory of a character, empty if it has none yet
+    ///
+    /// # Arguments
+    ///
+    /// * 'dir'  - directory of the memories
+    /// * 'name' - name of the character
+    ///
+    pub fn load(dir: &Path, name: &str) -> Result<Self, EGeneral> {
+        //
+        let path = Self::path(dir, name);
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        //
+        let content = std::fs::read_to_string(&path)
+            .into_report()
+            .change_context(EGeneral::Agent)
+            .attach_printable_lazy(|| format!("unable to read '{}'", path.display()))?;
+
+        serde_json::from_str(&content)
+            .into_report()
+            .change_context(EGeneral::Agent)
+            .attach_printable_lazy(|| format!("in '{}'", path.display()))
+        //
+    }
+    //
+    //
+    /// Save the memory of a character, the directory is created if needed
+    ///
+    /// # Arguments
+    ///
+    /// * 'dir'  - directory of the memories
+    /// * 'name' - name of the character
+    ///
+    pub fn save(&self, dir: &Path, name: &str) -> Result<(), EGeneral> {
+        //
+        let path = Self::path(dir, name);
+
+        std::fs::create_dir_all(dir)
+            .into_report()
+            .change_context(EGeneral::Agent)
+            .attach_printable_lazy(|| format!("unable to create '{}'", dir.display()))?;
+
+        let content = serde_json::to_string_pretty(self).into_report().change_context(EGeneral::Agent)?;
+
+        std::fs::write(&path, content)
+            .into_report()
+            .change_context(EGeneral::Agent)
+            .attach_printable_lazy(|| format!("unable to write '{}'", path.display()))
+        //
+    }
+    //
+    //
+    /// Remember the summary of an episode, replacing the one of the same episode
+    pub fn remember_episode(&mut self, number: u32, summary: &str) {
+
+        self.episodes.retain(|e| e.number != number);
+        self.episodes.push(EpisodeMemory { number, summary: summary.to_string() });
+        self.episodes.sort_by_key(|e| e.number);
+
+        let excess = self.episodes.len().saturating_sub(MAX_EPISODES);
+        self.episodes.drain(..excess);
+
+    }
+    //
+    //
+    /// Remember a fact if it is not already known
+    pub fn learn(&mut self, fact: &str) {
+
+        if self.facts.iter().any(|f| f.eq_ignore_ascii_case(fact)) {
+            return;
+        }
+
+        self.facts.push(fact.to_string());
+
+        let excess = self.facts.len().saturating_sub(MAX_FACTS);
+        self.facts.drain(..excess);
+
+    }
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Agent
+//
+/// lines of the scene given to an agent, the oldest ones are left out
+const SCENE_LINES: usize = 20;
+//
+//
+/// A character writing its own lines
+pub struct Agent {
+
+    pub character:  Character,
+    pub memory:     Memory,
+    /// short term memory, what was said in the current scene
+    scene:          Vec<ScriptLine>,
+
+}
+//
+impl Agent {
+    //
+    /// Create an agent for a character of the bible
+    pub fn new(character: Character, memory: Memory) -> Self { Self { character, memory, scene: Vec::new() } }
+    //
+    //
+    /// What was said in the current scene
+    pub fn scene(&self) -> &[ScriptLine] { &self.scene }
+    //
+    //
+    /// Forget the scene that ended
+    pub fn start_scene(&mut self) { self.scene.clear(); }
+    //
+    //
+    /// Remember a line of the current scene, the notes of the writers are not heard
+    pub fn hear(&mut self, line: &ScriptLine) {
+
+        if !matches!(line, ScriptLine::Note { .. }) {
+            self.scene.push(line.clone());
+        }
+
+    }
+    //
+    //
+    /// Variables of the 'agent' template
+    ///
+    /// # Arguments
+    ///
+    /// * 'series' - the bible of the series
+    /// * 'scene'  - the scene being played
+    /// * 'beat'   - the beat being played
+    ///
+    pub fn context(&self, series: &Series, scene: &Scene, beat: &Beat) -> Value {
+        //
+        // the relationships learned replace the ones of the bible
+        let mut relationships = self.character.relationships.clone();
+        relationships.extend(self.memory.relationships.clone());
+
+        let relationships: Vec<String> = relationships.iter().map(|(name, what)| format!("{}: {}", name, what)).collect();
+        //
+        json!({
+            "series": series,
+            "character": self.character,
+            "cue": self.character.name.to_uppercase(),
+            "memory": self.memory,
+            "relationships": relationships,
+            "scene": { "heading": scene.heading, "summary": scene.summary },
+            "beat": { "description": beat.description },
+            "heard": heard(&self.scene)
+        })
+        //
+    }
+    //
+    //
+    /// Read the answer of the 'reflect' template, one 'FACT:' or 'RELATIONSHIP:' per line
+    fn learn_from(&mut self, text: &str) {
+        //
+        for line in text.lines() {
+
+            let line = line.trim().trim_start_matches(['-', '*', '•']).trim();
+
+            let Some((kind, value)) = line.split_once(':') else { continue };
+            let value = value.trim();
+
+            match kind.trim().to_lowercase().as_str() {
+
+                "fact" if !value.is_empty() => self.memory.learn(value),
+
+                "relationship" => {
+
+                    let parts = value.split_once(" - ").or_else(|| value.split_once(':'));
+
+                    if let Some((name, what)) = parts {
+                        self.memory.relationships.insert(name.trim().to_string(), what.trim().to_string());
+                    }
+
+                },
+
+                _ => {}
+
+            }
+
+        }
+        //
+    }
+    //
+}
+//
+//
+/// The lines of a scene as text, the most recent ones only
+fn heard(lines: &[ScriptLine]) -> String {
+
+    lines[lines.len().saturating_sub(SCENE_LINES)..].iter()
+        .map(|l| l.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Director
+//
+/// default number of replies in a beat
+pub const DEFAULT_MAX_LINES: usize = 8;
+//
+// replies before the director can end a beat
+const MIN_LINES: usize = 2;
+//
+// maximum tokens of the completions
+const AGENT_TOKENS:     u16 = 120;
+const DIRECTOR_TOKENS:  u16 = 10;
+const REFLECT_TOKENS:   u16 = 250;
+//
+//
+/// Give the turns to the agents of a series
+pub struct Director<'a, C: CompletionClient> {
+
+    client:     &'a C,
+    series:     &'a Series,
+    templates:  TemplateSet,
+    parser:     ScriptParser,
+    model:      ModelType,
+    agents:     Vec<Agent>,
+    max_lines:  usize,
+
+}
+//
+impl<'a, C: CompletionClient> Director<'a, C> {
+    //
+    /// Create a director with an agent without memory for each character
+    ///
+    /// # Arguments
+    ///
+    /// * 'client' - what completes the prompts
+    /// * 'series' - the bible of the series
+    /// * 'model'  - the model of every completion
+    ///
+    pub fn new(client: &'a C, series: &'a Series, model: ModelType) -> Self {
+
+        Self {
+            client,
+            series,
+            templates:  default_templates(),
+            parser:     ScriptParser::for_series(series),
+            model,
+            agents:     series.characters.iter().map(|c| Agent::new(c.clone(), Memory::default())).collect(),
+            max_lines:  DEFAULT_MAX_LINES
+        }
+
+    }
+    //
+    /// Load the memory of every agent
+    pub fn with_memories(mut self, dir: &Path) -> Result<Self, EGeneral> {
+
+        for agent in &mut self.agents {
+            agent.memory = Memory::load(dir, &agent.character.name)?;
+        }
+
+        Ok(self)
+
+    }
+    //
+    /// Replace the templates, the missing ones are taken from the defaults
+    pub fn with_templates(mut self, templates: TemplateSet) -> Self {
+
+        self.templates.merge(templates);
+        self
+
+    }
+    //
+    /// Change the maximum number of replies in a beat
+    pub fn with_max_lines(mut self, max_lines: usize) -> Self {
+
+        self.max_lines = max_lines;
+        self
+
+    }
+    //
+    //
+    /// Every agent
+    pub fn agents(&self) -> &[Agent] { &self.agents }
+    //
+    //
+    /// The agent of a character, whatever the case of its name
+    pub fn agent(&self, name: &str) -> Option<&Agent> {
+
+        self.agents.iter().find(|a| a.character.name.eq_ignore_ascii_case(name))
+
+    }
+    //
+    //
+    /// Save the memory of every agent
+    pub fn save_memories(&self, dir: &Path) -> Result<(), EGeneral> {
+
+        self.agents.iter().try_for_each(|a| a.memory.save(dir, &a.character.name))
+
+    }
+    //
+    //
+    /// Play every scene of an episode, the beats must be written
+    pub async fn play_episode(&mut self, episode: &mut Episode) -> Result<(), EGeneral> {
+
+        for a in 0..episode.acts.len() {
+
+            for s in 0..episode.acts[a].scenes.len() {
+
+                self.play_scene(episode, a, s)
+                    .in_context(&[("act", a + 1), ("scene", s + 1)])
+                    .await?;
+
+            }
+
+        }
+
+        Ok(())
+
+    }
+    //
+    //
+    /// Play the beats of a scene, their lines are replaced
+    ///
+    /// # Arguments
+    ///
+    /// * 'episode' - the episode of the scene
+    /// * 'a'       - index of the act
+    /// * 's'       - index of the scene in the act
+    ///
+    pub async fn play_scene(&mut self, episode: &mut Episode, a: usize, s: usize) -> Result<(), EGeneral> {
+        //
+        let scene = episode.acts.get(a).and_then(|act| act.scenes.get(s)).cloned().ok_or_else(|| {
+
+            EGeneral::Agent.as_report().attach_printable(format!("there is no scene {} in act {}", s + 1, a + 1))
+
+        })?;
+        //
+        let cast = self.cast(&scene);
+
+        for agent in &mut self.agents {
+            agent.start_scene();
+        }
+
+        cinfo!(category: "script", "playing '{}' with {}", scene.heading, self.names(&cast));
+        //
+        // turn of the last reply of each agent, to give the turn to the one silent for longest
+        let mut turns: Vec<Option<usize>> = vec![None; self.agents.len()];
+        let mut turn = 0;
+        //
+        for (b, beat) in scene.beats.iter().enumerate() {
+
+            let mut lines = Vec::new();
+
+            for count in 0..self.max_lines {
+
+                let last = turns.iter().enumerate().filter_map(|(i, t)| t.map(|t| (t, i))).max().map(|(_, i)| i);
+
+                let Some(speaker) = self.next_speaker(&scene, beat, &cast, &turns, last, count).await? else { break };
+
+                let spoken = self.speak(speaker, &scene, beat).await?;
+
+                for i in &cast {
+                    spoken.iter().for_each(|l| self.agents[*i].hear(l));
+                }
+
+                lines.extend(spoken);
+                turns[speaker] = Some(turn);
+                turn += 1;
+
+            }
+
+            let beat = &mut episode.acts[a].scenes[s].beats[b];
+
+            beat.dialogue = lines.iter().map(|l| l.to_string()).collect::<Vec<_>>().join("\n");
+            beat.lines = lines;
+
+        }
+        //
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Make every agent who spoke in the episode remember it and what it learned
+    pub async fn remember(&mut self, episode: &Episode) -> Result<(), EGeneral> {
+        //
+        for i in 0..self.agents.len() {
+
+            let name = self.agents[i].character.name.clone();
+
+            // the scenes where the agent spoke
+            let scenes: Vec<&Scene> = episode.scenes()
+                .filter(|s| s.beats.iter().flat_map(|b| &b.lines).any(|l| is_speaker(l, &name)))
+                .collect();
+
+            if scenes.is_empty() {
+                continue;
+            }
+
+            let summary = std::iter::once(episode.logline.as_str())
+                .chain(scenes.iter().map(|s| s.summary.as_str()))
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>()
+                .join(". ");
+
+            let lines: Vec<ScriptLine> = scenes.iter().flat_map(|s| &s.beats).flat_map(|b| b.lines.clone()).collect();
+
+            let context = json!({
+                "series": self.series,
+                "episode": { "number": episode.number, "logline": episode.logline },
+                "character": self.agents[i].character,
+                "heard": heard(&lines)
+            });
+
+            let text = self.complete("reflect", &context, REFLECT_TOKENS).await?;
+
+            let agent = &mut self.agents[i];
+
+            agent.memory.remember_episode(episode.number, &summary);
+            agent.learn_from(&text);
+
+            cdebug!(category: "script", "{} remembers {} facts", name, agent.memory.facts.len());
+
+        }
+        //
+        Ok(())
+        //
+    }
+    //
+    //
+    /// The agents in a scene, the characters named in the scene or everyone if none is named
+    fn cast(&self, scene: &Scene) -> Vec<usize> {
+        //
+        let text = std::iter::once(&scene.summary)
+            .chain(scene.beats.iter().map(|b| &b.description))
+            .map(|t| t.to_lowercase())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let named: Vec<usize> = (0..self.agents.len())
+            .filter(|i| {
+
+                let name = self.agents[*i].character.name.to_lowercase();
+
+                text.split(|c: char| !c.is_alphanumeric()).any(|w| w == name) || text.contains(&name) && name.contains(' ')
+
+            })
+            .collect();
+        //
+        match named.is_empty() {
+
+            true => (0..self.agents.len()).collect(),
+            false => named
+
+        }
+        //
+    }
+    //
+    //
+    /// Names of some agents like 'Bolt, Nova'
+    fn names(&self, agents: &[usize]) -> String {
+
+        agents.iter().map(|i| self.agents[*i].character.name.as_str()).collect::<Vec<_>>().join(", ")
+
+    }
+    //
+    //
+    /// Ask who speaks next, none when the beat is over
+    ///
+    /// When the answer is not an agent of the scene the turn goes to the one silent for longest,
+    /// the beat can't end before a few replies
+    ///
+    /// # Arguments
+    ///
+    /// * 'scene' - the scene being played
+    /// * 'beat'  - the beat being played
+    /// * 'cast'  - the agents in the scene
+    /// * 'turns' - the turn of the last reply of each agent
+    /// * 'last'  - the agent who spoke last
+    /// * 'count' - the number of replies in the beat
+    ///
+    async fn next_speaker(
+        &self,
+        scene: &Scene,
+        beat: &Beat,
+        cast: &[usize],
+        turns: &[Option<usize>],
+        last: Option<usize>,
+        count: usize
+
+    ) -> Result<Option<usize>, EGeneral> {
+        //
+        let heard = cast.first().map(|i| heard(self.agents[*i].scene())).unwrap_or_default();
+
+        let context = json!({
+            "series": self.series,
+            "scene": { "heading": scene.heading, "summary": scene.summary },
+            "beat": { "description": beat.description },
+            "heard": heard,
+            "cast": self.names(cast)
+        });
+
+        let answer = self.complete("director", &context, DIRECTOR_TOKENS).await?;
+        let answer = answer.trim().trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+        //
+        if answer.starts_with("end") && count >= MIN_LINES {
+            return Ok(None);
+        }
+        //
+        let chosen = cast.iter()
+            .filter(|i| answer.starts_with(&self.agents[**i].character.name.to_lowercase()))
+            .max_by_key(|i| self.agents[**i].character.name.len());
+
+        if let Some(chosen) = chosen {
+            return Ok(Some(*chosen));
+        }
+        //
+        let silent = cast.iter()
+            .filter(|i| cast.len() == 1 || Some(**i) != last)
+            .min_by_key(|i| turns[**i].map_or(0, |t| t + 1))
+            .copied();
+
+        cdebug!(category: "script", "the director answered '{}', the turn goes to the agent silent for longest", answer);
+
+        Ok(silent)
+        //
+    }
+    //
+    //
+    /// Ask an agent for its reply, the actions before it are kept
+    async fn speak(&self, index: usize, scene: &Scene, beat: &Beat) -> Result<Vec<ScriptLine>, EGeneral> {
+        //
+        let agent = &self.agents[index];
+        let name = &agent.character.name;
+
+        let mut request = self.request("agent", &agent.context(self.series, scene, beat), AGENT_TOKENS)?;
+        request.stop_token = Some(vec!["\n\n".to_string()]);
+
+        let (_, script) = self.parser.complete(self.client, &self.templates, request).await
+            .change_context(EGeneral::Agent)
+            .attach_printable_lazy(|| format!("while {} was speaking", name))?;
+        //
+        let mut lines = Vec::new();
+
+        for mut line in script.lines {
+
+            let reply = matches!(line, ScriptLine::Dialogue { .. });
+
+            // the agent can only speak for itself
+            if let ScriptLine::Dialogue { speaker, .. } = &mut line {
+                *speaker = name.clone();
+            }
+
+            if !matches!(line, ScriptLine::Heading { .. }) {
+                lines.push(line);
+            }
+
+            if reply {
+                break;
+            }
+
+        }
+        //
+        Ok(lines)
+        //
+    }
+    //
+    //
+    /// Render a template as a request
+    fn request(&self, template: &str, context: &Value, max_tokens: u16) -> Result<PromptRequestInfo, EGeneral> {
+
+        let mut request = PromptRequestInfo::from_template(&self.templates, template, context, self.model)
+            .change_context(EGeneral::Agent)?;
+
+        request.max_word = Some(max_tokens);
+
+        Ok(request)
+
+    }
+    //
+    //
+    /// Render a template and send it to the client
+    async fn complete(&self, template: &str, context: &Value, max_tokens: u16) -> Result<String, EGeneral> {
+
+        let request = self.request(template, context, max_tokens)?;
+
+        self.client.complete(request).await
+            .change_context(EGeneral::Agent)
+            .attach_printable_lazy(|| format!("while asking the {}", template))
+
+    }
+    //
+}
+//
+//
+/// Check if a line is a reply of a character
+fn is_speaker(line: &ScriptLine, name: &str) -> bool {
+
+    matches!(line, ScriptLine::Dialogue { speaker, .. } if speaker.eq_ignore_ascii_case(name))
+
+}
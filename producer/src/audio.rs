@@ -0,0 +1,360 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Audio
+//
+// Mono sample buffers and their WAV files. The samples are floats between -1 and 1, the files
+// are written and read as 16-bit PCM or 32-bit float, the channels of a stereo file are mixed
+// down.
+//
+pub mod mix;
+pub mod tts;
+
+use std::path::Path;
+use std::time::Duration;
+
+use error_stack::{IntoReport, Result, ResultExt};
+
+use crate::EGeneral;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use std::time::Duration;
+
+    use super::{Audio, SampleFormat};
+
+    #[test]
+    fn wav_round_trip() {
+
+        let audio = Audio { sample_rate: 8000, samples: vec![0.0, 0.5, -0.5, 1.0, -1.0, 0.25] };
+
+        let bytes = audio.to_wav();
+
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(bytes.len(), 44 + 12);
+
+        let read = Audio::from_wav(&bytes).unwrap();
+
+        assert_eq!(read.sample_rate, 8000);
+        assert!(read.samples.iter().zip(&audio.samples).all(|(a, b)| (a - b).abs() < 1e-4));
+        assert_eq!(read.duration(), Duration::from_micros(750));
+
+        assert!(Audio::from_wav(b"RIFF1234WAVEjunk").is_err());
+
+        let float = audio.encode(SampleFormat::Float32);
+
+        assert_eq!(float.len(), 44 + 24);
+        assert_eq!(Audio::from_wav(&float).unwrap(), audio);
+
+    }
+
+    #[test]
+    fn empty_format_is_rejected() {
+
+        let bytes = Audio { sample_rate: 8000, samples: vec![0.5; 4] }.to_wav();
+
+        // the channels then the sample rate of the format chunk
+        let mut silent = bytes.clone();
+        silent[22..24].copy_from_slice(&0u16.to_le_bytes());
+
+        let mut frozen = bytes.clone();
+        frozen[24..28].copy_from_slice(&0u32.to_le_bytes());
+
+        assert!(Audio::from_wav(&bytes).is_ok());
+        assert!(format!("{:?}", Audio::from_wav(&silent).unwrap_err()).contains("0 channels"));
+        assert!(format!("{:?}", Audio::from_wav(&frozen).unwrap_err()).contains("at 0 Hz"));
+
+    }
+
+    #[test]
+    fn float_stereo_files_are_mixed_down() {
+
+        // a stereo float file with an unknown chunk before the data
+        let mut bytes = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        bytes.extend(16u32.to_le_bytes());
+        bytes.extend(3u16.to_le_bytes());
+        bytes.extend(2u16.to_le_bytes());
+        bytes.extend(100u32.to_le_bytes());
+        bytes.extend(800u32.to_le_bytes());
+        bytes.extend(8u16.to_le_bytes());
+        bytes.extend(32u16.to_le_bytes());
+        bytes.extend(b"LIST\x02\0\0\0ab");
+        // a streamed file does not know the size of its data
+        bytes.extend(b"data\xff\xff\xff\xff");
+
+        for sample in [1.0f32, 0.0, 0.5, 0.5] {
+            bytes.extend(sample.to_le_bytes());
+        }
+
+        let audio = Audio::from_wav(&bytes).unwrap();
+
+        assert_eq!(audio.sample_rate, 100);
+        assert_eq!(audio.samples, [0.5, 0.5]);
+
+    }
+
+    #[test]
+    fn resampled_to_another_rate() {
+
+        let audio = Audio { sample_rate: 4, samples: vec![0.0, 1.0, 0.0, -1.0] };
+
+        let up = audio.resample(8);
+
+        assert_eq!(up.samples.len(), 8);
+        assert_eq!(up.samples[1], 0.5);
+        assert_eq!(up.duration(), audio.duration());
+        assert_eq!(audio.resample(4), audio);
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Audio
+//
+/// sample rate of the audio made by the producer
+pub const SAMPLE_RATE: u32 = 22050;
+//
+/// extension of the audio files
+pub const WAV_EXTENSION: &str = "wav";
+//
+//
+/// How the samples are written in a WAV file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SampleFormat {
+
+    #[default]
+    Pcm16,
+    Float32,
+
+}
+//
+impl SampleFormat {
+
+    // format tag and bits per sample in the header
+    fn header(&self) -> (u16, u16) {
+
+        match self {
+
+            Self::Pcm16 => (1, 16),
+            Self::Float32 => (3, 32)
+
+        }
+
+    }
+
+}
+//
+//
+/// A mono sound
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Audio {
+
+    pub sample_rate:    u32,
+    /// between -1 and 1
+    pub samples:        Vec<f32>,
+
+}
+//
+impl Audio {
+    //
+    /// Silence of a length
+    pub fn silence(sample_rate: u32, length: Duration) -> Self {
+
+        Self { sample_rate, samples: vec![0.0; (length.as_secs_f64() * sample_rate as f64).round() as usize] }
+
+    }
+    //
+    //
+    /// Length of the sound
+    pub fn duration(&self) -> Duration {
+
+        match self.sample_rate {
+
+            0 => Duration::ZERO,
+            rate => Duration::from_secs_f64(self.samples.len() as f64 / rate as f64)
+
+        }
+
+    }
+    //
+    //
+    /// The sound at another sample rate, the samples are interpolated
+    pub fn resample(&self, sample_rate: u32) -> Self {
+        //
+        if sample_rate == self.sample_rate || self.samples.is_empty() {
+            return Self { sample_rate, samples: self.samples.clone() };
+        }
+        //
+        let ratio = self.sample_rate as f64 / sample_rate as f64;
+        let length = (self.samples.len() as f64 / ratio).round() as usize;
+        let last = self.samples.len() - 1;
+
+        let samples = (0..length)
+            .map(|i| {
+
+                let position = i as f64 * ratio;
+                let index = (position.floor() as usize).min(last);
+                let fraction = (position - index as f64) as f32;
+
+                self.samples[index] * (1.0 - fraction) + self.samples[(index + 1).min(last)] * fraction
+
+            })
+            .collect();
+        //
+        Self { sample_rate, samples }
+        //
+    }
+    //
+    //
+    /// The sound as a 16-bit PCM WAV file
+    pub fn to_wav(&self) -> Vec<u8> { self.encode(SampleFormat::Pcm16) }
+    //
+    //
+    /// The sound as a WAV file
+    ///
+    /// # Arguments
+    ///
+    /// * 'format' - how the samples are written, they are clipped between -1 and 1
+    ///
+    pub fn encode(&self, format: SampleFormat) -> Vec<u8> {
+        //
+        let (tag, bits) = format.header();
+        let width = bits as u32 / 8;
+        let data = self.samples.len() as u32 * width;
+        let mut bytes = Vec::with_capacity(44 + data as usize);
+
+        bytes.extend(b"RIFF");
+        bytes.extend((36 + data).to_le_bytes());
+        bytes.extend(b"WAVEfmt ");
+        bytes.extend(16u32.to_le_bytes());
+        bytes.extend(tag.to_le_bytes());
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend(self.sample_rate.to_le_bytes());
+        bytes.extend((self.sample_rate * width).to_le_bytes());
+        bytes.extend((width as u16).to_le_bytes());
+        bytes.extend(bits.to_le_bytes());
+        bytes.extend(b"data");
+        bytes.extend(data.to_le_bytes());
+        //
+        for sample in self.samples.iter().map(|s| s.clamp(-1.0, 1.0)) {
+
+            match format {
+
+                SampleFormat::Pcm16 => bytes.extend(((sample * i16::MAX as f32).round() as i16).to_le_bytes()),
+                SampleFormat::Float32 => bytes.extend(sample.to_le_bytes())
+
+            }
+
+        }
+        //
+        bytes
+        //
+    }
+    //
+    //
+    /// Read a WAV file in 16-bit PCM or 32-bit float
+    pub fn from_wav(bytes: &[u8]) -> Result<Self, EGeneral> {
+        //
+        let invalid = |why: &str| EGeneral::Audio.as_report().attach_printable(format!("invalid wav file: {}", why));
+
+        if bytes.len() < 12 || &bytes[..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err(invalid("no RIFF WAVE header"));
+        }
+        //
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+
+        let mut format = None;
+        let mut position = 12;
+        //
+        while position + 8 <= bytes.len() {
+
+            let id = &bytes[position..position + 4];
+            let size = u32_at(position + 4) as usize;
+            let start = position + 8;
+
+            match id {
+
+                b"fmt " if start + 16 <= bytes.len() => {
+
+                    // format, channels, sample rate and bits per sample
+                    format = Some((u16_at(start), u16_at(start + 2) as usize, u32_at(start + 4), u16_at(start + 14)));
+
+                },
+
+                b"data" => {
+
+                    let (kind, channels, sample_rate, bits) = format.ok_or_else(|| invalid("the data comes before the format"))?;
+
+                    if channels == 0 || sample_rate == 0 {
+                        return Err(invalid(&format!("{} channels at {} Hz", channels, sample_rate)));
+                    }
+
+                    // the size is unknown when the file was streamed
+                    let end = start.saturating_add(size).min(bytes.len());
+                    let data = &bytes[start..end];
+
+                    let values: Vec<f32> = match (kind, bits) {
+
+                        (1, 16) => data.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / i16::MAX as f32).collect(),
+                        (3, 32) => data.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect(),
+                        _ => return Err(invalid(&format!("format {} with {} bits is not supported", kind, bits)))
+
+                    };
+
+                    let samples = values.chunks_exact(channels).map(|c| c.iter().sum::<f32>() / channels as f32).collect();
+
+                    return Ok(Self { sample_rate, samples });
+
+                },
+
+                _ => {}
+
+            }
+
+            // the chunks are padded to an even size
+            position = start.saturating_add(size + size % 2);
+
+        }
+        //
+        Err(invalid("no data"))
+        //
+    }
+    //
+    //
+    /// Write the sound as a 16-bit PCM WAV file
+    pub fn save(&self, path: &Path) -> Result<(), EGeneral> { self.save_as(path, SampleFormat::Pcm16) }
+    //
+    //
+    /// Write the sound as a WAV file in a format
+    pub fn save_as(&self, path: &Path, format: SampleFormat) -> Result<(), EGeneral> {
+
+        std::fs::write(path, self.encode(format))
+            .into_report()
+            .change_context(EGeneral::Audio)
+            .attach_printable_lazy(|| format!("unable to write '{}'", path.display()))
+
+    }
+    //
+    //
+    /// Read a WAV file
+    pub fn load(path: &Path) -> Result<Self, EGeneral> {
+
+        let bytes = std::fs::read(path)
+            .into_report()
+            .change_context(EGeneral::Audio)
+            .attach_printable_lazy(|| format!("unable to read '{}'", path.display()))?;
+
+        Self::from_wav(&bytes).attach_printable_lazy(|| format!("in '{}'", path.display()))
+
+    }
+    //
+}
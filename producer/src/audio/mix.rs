@@ -0,0 +1,677 @@
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Mixing
+//
+// The track of an episode is made of three layers: the dialogue, the sound effects named in the
+// script like '[laugh track]' or '[door slams]', and the ambient bed of each scene. The beds are
+// looped under their scene, crossfaded at the scene changes and ducked under the dialogue. The
+// mix only depends on its clips, the same clips always give the same samples.
+//
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::Duration;
+
+use error_stack::{IntoReport, Result, ResultExt};
+
+use crate::episode::Episode;
+use crate::script::ScriptLine;
+use crate::timeline::{LineKey, Pacing, Timeline};
+use crate::{cdebug, EGeneral};
+use super::{Audio, SampleFormat, SAMPLE_RATE, WAV_EXTENSION};
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use std::collections::{BTreeMap, HashMap};
+    use std::time::Duration;
+
+    use crate::episode::{Act, Beat, Episode, Scene};
+    use crate::script::ScriptLine;
+    use crate::timeline::{LineKey, Pacing};
+    use super::super::Audio;
+    use super::{arrange, cues, Clip, Cue, Layer, Mix, MixOptions, SoundBank};
+
+    fn ms(ms: u64) -> Duration { Duration::from_millis(ms) }
+
+    fn tone(sample_rate: u32, value: f32, length: usize) -> Audio { Audio { sample_rate, samples: vec![value; length] } }
+
+    #[test]
+    fn clips_are_placed_with_their_fades() {
+
+        let options = MixOptions { sample_rate: 10, ..MixOptions::default() };
+
+        let mut mix = Mix::default();
+        mix.add(Clip { fade_in: ms(200), ..Clip::new(Layer::Effect, ms(500), tone(10, 0.5, 4)) });
+        mix.add(Clip::new(Layer::Effect, ms(700), tone(10, 0.25, 2)));
+
+        assert_eq!(mix.duration(), ms(900));
+        assert_eq!(mix.render(&options).samples, [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.25, 0.75, 0.75]);
+
+        // a bed is looped to its length, a clip at another rate is resampled
+        let mut mix = Mix::default();
+        mix.add(Clip { length: Some(ms(600)), fade_out: ms(200), ..Clip::new(Layer::Bed, ms(0), Audio { sample_rate: 10, samples: vec![0.5, -0.5] }) });
+
+        assert_eq!(mix.render(&options).samples, [0.5, -0.5, 0.5, -0.5, 0.5, -0.25]);
+
+        // too loud, the mix is brought back under 1
+        let mut mix = Mix::default();
+        mix.add(Clip::new(Layer::Dialogue, ms(0), tone(20, 0.8, 4)));
+        mix.add(Clip::new(Layer::Effect, ms(100), tone(10, 0.8, 1)));
+
+        assert_eq!(mix.render(&options).samples, [0.5, 1.0]);
+
+    }
+
+    #[test]
+    fn beds_are_ducked_under_the_dialogue() {
+
+        let options = MixOptions { sample_rate: 100, duck: 0.25, attack: ms(100), release: ms(200), ..MixOptions::default() };
+
+        let mut mix = Mix::default();
+        mix.add(Clip::new(Layer::Bed, ms(0), tone(100, 1.0, 100)));
+        mix.add(Clip::new(Layer::Dialogue, ms(300), tone(100, 0.0, 30)));
+
+        let samples = mix.render(&options).samples;
+
+        assert_eq!(samples[29], 1.0);
+        assert!(samples[30] < 1.0 && samples[35] > 0.25);
+        assert_eq!(samples[40], 0.25);
+        assert_eq!(samples[59], 0.25);
+        assert!(samples[60] > 0.25 && samples[70] < 1.0);
+        assert_eq!(samples[80], 1.0);
+
+    }
+
+    #[test]
+    fn episode_is_arranged_with_its_cues() {
+
+        let dialogue = |text: &str| ScriptLine::Dialogue { speaker: "Bolt".to_string(), parenthetical: None, text: text.to_string() };
+
+        let scene = |heading: &str, lines: Vec<ScriptLine>| Scene {
+            heading:    heading.to_string(),
+            beats:      vec![Beat { lines, ..Beat::default() }],
+            ..Scene::default()
+        };
+
+        let episode = Episode {
+            acts: vec![Act {
+                scenes: vec![
+                    scene("INT. DINER - NIGHT", vec![dialogue("Hi. [laugh track]"), ScriptLine::Direction { text: "The door slams.".to_string() }]),
+                    scene("EXT. CRATER - DAY", vec![dialogue("[thunder] Run!")]),
+                ],
+                ..Act::default()
+            }],
+            ..Episode::default()
+        };
+
+        let sounds = SoundBank {
+            effects:    BTreeMap::from([("laugh track".to_string(), tone(100, 0.1, 50)), ("door slams".to_string(), tone(100, 0.1, 200))]),
+            beds:       BTreeMap::from([("diner".to_string(), tone(100, 0.1, 100)), ("crater".to_string(), tone(100, 0.1, 100))]),
+        };
+
+        let first = LineKey { act: 0, scene: 0, beat: 0, line: 0 };
+        let lines = HashMap::from([(first, tone(100, 0.5, 100))]);
+
+        let arrangement = arrange(&episode, &Pacing::default(), &lines, &sounds, &MixOptions::default());
+
+        assert_eq!(cues(&dialogue("[sighs] Hi. [laugh track]")), [
+            Cue { name: "sighs".to_string(), at_end: false },
+            Cue { name: "laugh track".to_string(), at_end: true }
+        ]);
+        assert_eq!(arrangement.missing, ["thunder"]);
+
+        // the direction lasts as long as its sound
+        let times: Vec<(Duration, Duration)> = arrangement.timeline.lines.iter().map(|l| (l.start, l.end)).collect();
+
+        assert_eq!(times, [(ms(1500), ms(2500)), (ms(2800), ms(4800)), (ms(6600), ms(7600))]);
+
+        let placed = |layer: Layer| -> Vec<(Duration, Duration)> {
+            arrangement.mix.clips.iter().filter(|c| c.layer == layer).map(|c| (c.start, c.end())).collect()
+        };
+
+        assert_eq!(placed(Layer::Dialogue), [(ms(1500), ms(2500))]);
+        assert_eq!(placed(Layer::Effect), [(ms(2500), ms(3000)), (ms(2800), ms(4800))]);
+
+        // the beds cross at the scene change at 5.1s
+        assert_eq!(placed(Layer::Bed), [(ms(0), ms(5600)), (ms(4600), ms(8400))]);
+        assert!(arrangement.mix.clips.iter().filter(|c| c.layer == Layer::Bed).all(|c| c.fade_in == ms(1000) && c.fade_out == ms(1000)));
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Clips
+//
+/// What a clip is part of, the beds are ducked under the dialogue
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layer {
+
+    Dialogue,
+    Effect,
+    Bed,
+
+}
+//
+//
+/// A sound placed in the mix
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clip {
+
+    pub layer:      Layer,
+    /// when the sound starts in the episode
+    pub start:      Duration,
+    pub audio:      Audio,
+    pub gain:       f32,
+    pub fade_in:    Duration,
+    pub fade_out:   Duration,
+    /// the sound is looped or cut to this length, its own length if none
+    pub length:     Option<Duration>,
+
+}
+//
+impl Clip {
+    //
+    /// A clip played once at full volume
+    pub fn new(layer: Layer, start: Duration, audio: Audio) -> Self {
+
+        Self { layer, start, audio, gain: 1.0, fade_in: Duration::ZERO, fade_out: Duration::ZERO, length: None }
+
+    }
+    //
+    //
+    /// When the clip stops
+    pub fn end(&self) -> Duration { self.start + self.length.unwrap_or_else(|| self.audio.duration()) }
+    //
+}
+//
+//
+/// How the episode is mixed and written
+#[derive(Clone, Debug)]
+pub struct MixOptions {
+
+    pub sample_rate:    u32,
+    pub format:         SampleFormat,
+    /// volume of the sound effects
+    pub effect_gain:    f32,
+    /// volume of the beds when nobody speaks
+    pub bed_gain:       f32,
+    /// volume of the beds under the dialogue, relative to their own
+    pub duck:           f32,
+    /// time for a bed to go down when a line starts
+    pub attack:         Duration,
+    /// time for a bed to come back up after a line
+    pub release:        Duration,
+    /// length of the transition between the beds of two scenes
+    pub crossfade:      Duration,
+
+}
+//
+impl Default for MixOptions {
+
+    fn default() -> Self {
+
+        Self {
+            sample_rate:    SAMPLE_RATE,
+            format:         SampleFormat::Pcm16,
+            effect_gain:    0.8,
+            bed_gain:       0.35,
+            duck:           0.3,
+            attack:         Duration::from_millis(80),
+            release:        Duration::from_millis(400),
+            crossfade:      Duration::from_millis(1000),
+        }
+
+    }
+
+}
+//
+//
+/// Number of samples of a length
+fn samples(length: Duration, sample_rate: u32) -> usize { (length.as_secs_f64() * sample_rate as f64).round() as usize }
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Mix
+//
+/// Every clip of a track
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mix { pub clips: Vec<Clip> }
+//
+impl Mix {
+    //
+    /// Place a clip
+    pub fn add(&mut self, clip: Clip) { self.clips.push(clip); }
+    //
+    //
+    /// Time when the last clip stops
+    pub fn duration(&self) -> Duration { self.clips.iter().map(Clip::end).max().unwrap_or(Duration::ZERO) }
+    //
+    //
+    /// Mix the clips in a single sound
+    ///
+    /// The beds are ducked while a dialogue clip plays and the whole mix is scaled down if it
+    /// goes over 1
+    ///
+    /// # Arguments
+    ///
+    /// * 'options' - the sample rate and the ducking
+    ///
+    pub fn render(&self, options: &MixOptions) -> Audio {
+        //
+        let rate = options.sample_rate;
+        let length = samples(self.duration(), rate);
+
+        let mut front = vec![0.0f32; length];
+        let mut beds = vec![0.0f32; length];
+        let mut speech = vec![false; length];
+        //
+        for clip in &self.clips {
+
+            let audio = clip.audio.resample(rate);
+
+            if audio.samples.is_empty() && clip.layer != Layer::Dialogue {
+                continue;
+            }
+
+            let start = samples(clip.start, rate);
+            let count = clip.length.map_or(audio.samples.len(), |l| samples(l, rate));
+            let (fade_in, fade_out) = (samples(clip.fade_in, rate), samples(clip.fade_out, rate));
+
+            let target = match clip.layer {
+
+                Layer::Bed => &mut beds,
+                Layer::Dialogue | Layer::Effect => &mut front
+
+            };
+
+            for i in 0..count.min(length.saturating_sub(start)) {
+
+                if clip.layer == Layer::Dialogue {
+                    speech[start + i] = true;
+                }
+
+                let Some(sample) = audio.samples.get(i % audio.samples.len().max(1)) else { continue };
+
+                let mut gain = clip.gain;
+
+                if i < fade_in {
+                    gain *= i as f32 / fade_in as f32;
+                }
+
+                if count - i < fade_out {
+                    gain *= (count - i) as f32 / fade_out as f32;
+                }
+
+                target[start + i] += sample * gain;
+
+            }
+
+        }
+        //
+        let ducking = ducking(&speech, options);
+
+        let mut mixed: Vec<f32> = front.iter().zip(&beds).zip(&ducking).map(|((f, b), d)| f + b * d).collect();
+        //
+        let peak = mixed.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+
+        if peak > 1.0 {
+            mixed.iter_mut().for_each(|s| *s /= peak);
+        }
+        //
+        Audio { sample_rate: rate, samples: mixed }
+        //
+    }
+    //
+}
+//
+//
+/// Volume of the beds at each sample, it goes down to the duck level while someone speaks
+fn ducking(speech: &[bool], options: &MixOptions) -> Vec<f32> {
+    //
+    let range = 1.0 - options.duck;
+    let step = |length: Duration| match samples(length, options.sample_rate) {
+
+        0 => range,
+        count => range / count as f32
+
+    };
+
+    let (down, up) = (step(options.attack), step(options.release));
+    let mut gain = 1.0f32;
+    //
+    speech.iter()
+        .map(|speaking| {
+
+            gain = match speaking {
+
+                true => (gain - down).max(options.duck),
+                false => (gain + up).min(1.0)
+
+            };
+
+            gain
+
+        })
+        .collect()
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Sounds
+//
+/// directory of the beds in the directory of the sounds
+pub const BEDS_DIR: &str = "beds";
+//
+//
+/// The name of a sound, lowercase words separated by a space
+///
+/// 'Laugh-Track' and '[laugh track]' both give 'laugh track'
+pub fn sound_name(text: &str) -> String {
+
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+
+}
+//
+//
+/// The sound effects and the beds that can be used in a mix
+#[derive(Clone, Debug, Default)]
+pub struct SoundBank {
+
+    /// effects by name, played by the cues of the script
+    pub effects:    BTreeMap<String, Audio>,
+    /// ambient sounds by name, played under the scenes whose heading has their name
+    pub beds:       BTreeMap<String, Audio>,
+
+}
+//
+impl SoundBank {
+    //
+    /// Load the WAV files of a directory, the name of a file is the name of its sound
+    ///
+    /// The beds are in the 'beds' directory inside it
+    ///
+    /// # Arguments
+    ///
+    /// * 'dir' - the directory of the sounds
+    ///
+    pub fn load(dir: &Path) -> Result<Self, EGeneral> {
+        //
+        let effects = Self::load_dir(dir)?;
+
+        let beds = match dir.join(BEDS_DIR).is_dir() {
+
+            true => Self::load_dir(&dir.join(BEDS_DIR))?,
+            false => BTreeMap::new()
+
+        };
+        //
+        cdebug!(category: "audio", "{} effects and {} beds in '{}'", effects.len(), beds.len(), dir.display());
+
+        Ok(Self { effects, beds })
+        //
+    }
+    //
+    //
+    fn load_dir(dir: &Path) -> Result<BTreeMap<String, Audio>, EGeneral> {
+        //
+        let entries = std::fs::read_dir(dir)
+            .into_report()
+            .change_context(EGeneral::Audio)
+            .attach_printable_lazy(|| format!("unable to read the sounds in '{}'", dir.display()))?;
+
+        let mut sounds = BTreeMap::new();
+        //
+        for entry in entries.flatten() {
+
+            let path = entry.path();
+
+            if path.extension().and_then(|e| e.to_str()) != Some(WAV_EXTENSION) {
+                continue;
+            }
+
+            let name = sound_name(&path.file_stem().unwrap_or_default().to_string_lossy());
+
+            sounds.insert(name, Audio::load(&path)?);
+
+        }
+        //
+        Ok(sounds)
+        //
+    }
+    //
+    //
+    /// The effects whose name is in a text, like 'door slams' in 'The door slams behind her'
+    pub fn heard(&self, text: &str) -> Vec<String> {
+
+        let text = format!(" {} ", sound_name(text));
+
+        self.effects.keys().filter(|name| text.contains(&format!(" {} ", name))).cloned().collect()
+
+    }
+    //
+    //
+    /// The bed of a scene, the longest name found in its heading
+    pub fn bed(&self, heading: &str) -> Option<&Audio> {
+
+        let heading = format!(" {} ", sound_name(heading));
+
+        self.beds.iter()
+            .filter(|(name, _)| heading.contains(&format!(" {} ", name)))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, audio)| audio)
+
+    }
+    //
+}
+//
+//
+/// A sound effect asked by a line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cue {
+
+    pub name:   String,
+    /// played when the line ends, the cue is after the words
+    pub at_end: bool,
+
+}
+//
+//
+/// The cues between brackets of a reply or of a stage direction
+pub fn cues(line: &ScriptLine) -> Vec<Cue> {
+    //
+    let text = match line {
+
+        ScriptLine::Dialogue { text, .. } | ScriptLine::Direction { text } => text,
+        _ => return Vec::new()
+
+    };
+    //
+    let mut cues = Vec::new();
+    let mut rest = text.as_str();
+    let mut said = false;
+
+    while let Some(open) = rest.find('[') {
+
+        said |= rest[..open].chars().any(char::is_alphanumeric);
+
+        let Some(close) = rest[open..].find(']') else { break };
+        let name = sound_name(&rest[open + 1..open + close]);
+
+        if !name.is_empty() {
+            cues.push(Cue { name, at_end: said });
+        }
+
+        rest = &rest[open + close + 1..];
+
+    }
+    //
+    cues
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Arrangement
+//
+/// The mix of an episode and what could not be placed in it
+#[derive(Clone, Debug)]
+pub struct Arrangement {
+
+    pub timeline:   Timeline,
+    pub mix:        Mix,
+    /// the cues without a sound in the bank
+    pub missing:    Vec<String>,
+
+}
+//
+//
+/// Place the lines, the sound effects and the beds of an episode
+///
+/// A stage direction that plays a sound effect lasts as long as the effect, the other ones
+/// keep the pause of the pacing
+///
+/// # Arguments
+///
+/// * 'episode' - the episode to mix
+/// * 'pacing'  - the pauses between the lines
+/// * 'lines'   - the recorded replies
+/// * 'sounds'  - the sound effects and the beds
+/// * 'options' - the volumes and the crossfade of the beds
+///
+pub fn arrange(
+    episode: &Episode,
+    pacing: &Pacing,
+    lines: &HashMap<LineKey, Audio>,
+    sounds: &SoundBank,
+    options: &MixOptions
+
+) -> Arrangement {
+    //
+    let mut durations: HashMap<LineKey, Duration> = lines.iter().map(|(key, audio)| (*key, audio.duration())).collect();
+    let mut effects: HashMap<LineKey, Vec<Cue>> = HashMap::new();
+    let mut missing = Vec::new();
+    //
+    for (a, act) in episode.acts.iter().enumerate() {
+
+        for (s, scene) in act.scenes.iter().enumerate() {
+
+            for (b, beat) in scene.beats.iter().enumerate() {
+
+                for (l, line) in beat.lines.iter().enumerate() {
+
+                    let key = LineKey { act: a, scene: s, beat: b, line: l };
+                    let mut found = cues(line);
+
+                    if let ScriptLine::Direction { text } = line {
+
+                        for name in sounds.heard(text) {
+
+                            if !found.iter().any(|c| c.name == name) {
+                                found.push(Cue { name, at_end: false });
+                            }
+
+                        }
+
+                    }
+
+                    found.retain(|cue| match sounds.effects.contains_key(&cue.name) {
+
+                        true => true,
+
+                        false => {
+
+                            if !missing.contains(&cue.name) {
+                                missing.push(cue.name.clone());
+                            }
+
+                            false
+
+                        }
+
+                    });
+
+                    if found.is_empty() {
+                        continue;
+                    }
+
+                    if matches!(line, ScriptLine::Direction { .. }) {
+                        durations.insert(key, found.iter().map(|c| sounds.effects[&c.name].duration()).max().unwrap_or_default());
+                    }
+
+                    effects.insert(key, found);
+
+                }
+
+            }
+
+        }
+
+    }
+    //
+    let timeline = Timeline::build(episode, pacing, &durations);
+    let mut mix = Mix::default();
+    //
+    for timed in &timeline.lines {
+
+        if let Some(audio) = lines.get(&timed.key) {
+            mix.add(Clip::new(Layer::Dialogue, timed.start, audio.clone()));
+        }
+
+        for cue in effects.get(&timed.key).into_iter().flatten() {
+
+            let start = if cue.at_end { timed.end } else { timed.start };
+
+            mix.add(Clip { gain: options.effect_gain, ..Clip::new(Layer::Effect, start, sounds.effects[&cue.name].clone()) });
+
+        }
+
+    }
+    //
+    // a bed covers its scene, from the end of the previous one, and overlaps its neighbours by
+    // half the crossfade on each side
+    let half = options.crossfade / 2;
+    let mut start = 0;
+
+    while start < timeline.lines.len() {
+
+        let (act, scene) = (timeline.lines[start].key.act, timeline.lines[start].key.scene);
+        let count = timeline.lines[start..].iter().take_while(|l| (l.key.act, l.key.scene) == (act, scene)).count();
+
+        let from = timeline.lines[start].start.saturating_sub(pacing.scene_change).saturating_sub(half);
+        let to = timeline.lines[start + count - 1].end + pacing.gap + half;
+
+        if let Some(bed) = sounds.bed(&episode.acts[act].scenes[scene].heading) {
+
+            mix.add(Clip {
+                gain:       options.bed_gain,
+                fade_in:    options.crossfade,
+                fade_out:   options.crossfade,
+                length:     Some(to - from),
+                ..Clip::new(Layer::Bed, from, bed.clone())
+            });
+
+        }
+
+        start += count;
+
+    }
+    //
+    if !missing.is_empty() {
+        cdebug!(category: "audio", "no sound for the cues {}", missing.join(", "));
+    }
+    //
+    Arrangement { timeline, mix, missing }
+    //
+}
@@ -0,0 +1,634 @@
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Text to speech
+//
+// Every character speaks with its own voice profile, picked from its name and the description
+// of its voice in the bible. The lines are spoken by a small formant synthesizer that needs
+// nothing installed, or by espeak-ng when it is available, then mixed with the sounds of the
+// script to make the track of the episode.
+//
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use error_stack::{IntoReport, Result, ResultExt};
+
+use crate::episode::Episode;
+use crate::script::ScriptLine;
+use crate::series::{Character, Series};
+use crate::timeline::{LineKey, Pacing, Timeline};
+use crate::{cdebug, EGeneral};
+use super::mix::{arrange, MixOptions, SoundBank};
+use super::{Audio, SAMPLE_RATE, WAV_EXTENSION};
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use crate::series::{Character, Series};
+    use crate::timeline::{LineKey, Pacing};
+    use super::super::mix::{MixOptions, SoundBank};
+    use super::super::Audio;
+    use super::{render_episode, spoken, Espeak, FormantSynth, Tts, VoiceProfile};
+
+    #[test]
+    fn synthesized_speech_follows_the_voice() {
+
+        let synth = FormantSynth::default();
+        let voice = VoiceProfile::default();
+
+        let hello = synth.speak("Hello there", &voice).unwrap();
+
+        assert_eq!(hello, synth.speak("Hello there", &voice).unwrap());
+        assert!(hello.samples.iter().all(|s| s.abs() <= 1.0));
+        assert!(hello.samples.iter().any(|s| s.abs() > 0.5));
+
+        let fast = synth.speak("Hello there", &VoiceProfile { rate: 2.0, ..voice }).unwrap();
+        let low = synth.speak("Hello there", &VoiceProfile { pitch: 90.0, ..voice }).unwrap();
+        let pause = synth.speak("Hello. There", &voice).unwrap();
+
+        assert!(fast.duration() * 2 <= hello.duration() + std::time::Duration::from_millis(5));
+        assert_ne!(low, hello);
+        assert!(pause.duration() > hello.duration());
+        assert!(synth.speak("", &voice).unwrap().samples.is_empty());
+
+    }
+
+    #[test]
+    fn characters_have_distinct_voices() {
+
+        let mut deep = Character::new("Bolt");
+        deep.voice = "Deep and slow".to_string();
+
+        let mut high = Character::new("Bolt");
+        high.voice = "high, fast and raspy".to_string();
+
+        let (deep, high) = (VoiceProfile::for_character(&deep), VoiceProfile::for_character(&high));
+
+        assert!(deep.pitch < high.pitch && deep.rate < high.rate && deep.timbre < high.timbre);
+        assert_eq!(VoiceProfile::for_character(&Character::new("Nova")), VoiceProfile::for_character(&Character::new("Nova")));
+
+        let mut series = Series::new("The Night Shift", "Two robots run a diner on the moon");
+        series.characters = (0..6).map(|i| Character::new(&format!("Robot {}", i))).collect();
+
+        let mut pitches: Vec<f32> = VoiceProfile::cast(&series).values().map(|v| v.pitch).collect();
+        pitches.sort_by(f32::total_cmp);
+
+        assert!(pitches.windows(2).all(|p| p[1] >= p[0] * 1.07));
+
+    }
+
+    #[test]
+    fn espeak_is_given_the_voice() {
+
+        let espeak = Espeak::default();
+
+        let arguments = espeak.arguments("-Hi", &VoiceProfile { pitch: 180.0, rate: 1.2, timbre: 0.9 });
+
+        assert_eq!(arguments, ["-v", "en+f4", "-p", "50", "-s", "210", "--stdout", "Hi"]);
+        assert_eq!(spoken("[sighs] Fine (for now). Bye"), "Fine . Bye");
+
+    }
+
+    #[test]
+    fn episode_is_rendered_line_by_line() {
+
+        struct Beep;
+
+        impl Tts for Beep {
+
+            fn speak(&self, text: &str, _: &VoiceProfile) -> error_stack::Result<Audio, crate::EGeneral> {
+
+                Ok(Audio { sample_rate: 1000, samples: vec![0.5; text.len() * 100] })
+
+            }
+
+        }
+
+        let dir = std::env::temp_dir().join(format!("producer-tts-{}", std::process::id()));
+        let series = Series::new("The Night Shift", "Two robots run a diner on the moon");
+
+        let rendered = render_episode(
+            &Beep, &crate::timeline::test::episode(), &VoiceProfile::cast(&series), &SoundBank::default(), &Pacing::default(), &MixOptions::default(), &dir
+        ).unwrap();
+
+        let first = LineKey { act: 0, scene: 0, beat: 0, line: 0 };
+        let line = Audio::load(&rendered.lines[&first]).unwrap();
+        let track = Audio::load(&rendered.track).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // 3 lines of dialogue, 'one two three four five six seven eight' lasts 3.9s
+        assert_eq!(rendered.lines.len(), 3);
+        assert!(rendered.lines[&first].ends_with("lines/01-01-01-01.wav"));
+        assert_eq!(line.duration().as_millis(), 3900);
+        assert_eq!(rendered.timeline.lines[0].end - rendered.timeline.lines[0].start, line.duration());
+        assert_eq!(track.duration(), rendered.timeline.duration());
+
+        // the line starts after the scene change
+        let start = (1.5 * super::SAMPLE_RATE as f64) as usize;
+        assert_eq!(track.samples[start - 1], 0.0);
+        assert!(track.samples[start + 1] > 0.4);
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Voice
+//
+/// How a character sounds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoiceProfile {
+
+    /// base pitch in Hz
+    pub pitch:  f32,
+    /// speed of the speech, 1 is normal
+    pub rate:   f32,
+    /// brightness of the voice, from 0 for a soft voice to 1 for a harsh one
+    pub timbre: f32,
+
+}
+//
+impl Default for VoiceProfile {
+
+    fn default() -> Self { Self { pitch: 140.0, rate: 1.0, timbre: 0.5 } }
+
+}
+//
+// smallest ratio between the pitches of two characters of a cast
+const DISTINCT_PITCH: f32 = 1.08;
+//
+impl VoiceProfile {
+    //
+    /// The voice of a character, always the same for the same name and description
+    ///
+    /// The name gives a voice that the words of the description change, like 'deep', 'high',
+    /// 'fast', 'slow', 'raspy' or 'soft'
+    pub fn for_character(character: &Character) -> Self {
+        //
+        // FNV-1a, stable between the runs unlike the hasher of the std
+        let hash = character.name.to_lowercase().bytes().fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+        let part = |shift: u32| ((hash >> shift) & 0xffff) as f32 / 0xffff as f32;
+
+        let mut voice = Self { pitch: 95.0 + 145.0 * part(0), rate: 0.9 + 0.25 * part(16), timbre: 0.3 + 0.4 * part(32) };
+        //
+        let description = character.voice.to_lowercase();
+
+        for word in description.split(|c: char| !c.is_alphanumeric()) {
+
+            match word {
+
+                "deep" | "low" | "booming" => voice.pitch *= 0.7,
+                "high" | "squeaky" | "shrill" => voice.pitch *= 1.4,
+                "fast" | "quick" | "rapid" => voice.rate *= 1.25,
+                "slow" | "drawling" => voice.rate *= 0.8,
+                "raspy" | "rough" | "gravelly" | "metallic" => voice.timbre += 0.3,
+                "soft" | "warm" | "gentle" => voice.timbre -= 0.3,
+                _ => {}
+
+            }
+
+        }
+        //
+        voice.timbre = voice.timbre.clamp(0.0, 1.0);
+
+        voice
+        //
+    }
+    //
+    //
+    /// The voices of every character of a series, no two of them with close pitches
+    pub fn cast(series: &Series) -> BTreeMap<String, Self> {
+        //
+        let mut voices: Vec<(String, Self)> = series.characters.iter().map(|c| (c.name.clone(), Self::for_character(c))).collect();
+
+        voices.sort_by(|a, b| a.1.pitch.total_cmp(&b.1.pitch));
+        //
+        for i in 1..voices.len() {
+
+            let lowest = voices[i - 1].1.pitch * DISTINCT_PITCH;
+
+            if voices[i].1.pitch < lowest {
+                voices[i].1.pitch = lowest;
+            }
+
+        }
+        //
+        voices.into_iter().collect()
+        //
+    }
+    //
+}
+//
+//
+/// The voice of a speaker, from the cast or from its name when it is not in the bible
+fn voice_of(voices: &BTreeMap<String, VoiceProfile>, speaker: &str) -> VoiceProfile {
+
+    voices.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(speaker))
+        .map(|(_, voice)| *voice)
+        .unwrap_or_else(|| VoiceProfile::for_character(&Character::new(speaker)))
+
+}
+//
+//
+/// The words said in a reply, without the actions between brackets or parentheses
+pub fn spoken(text: &str) -> String {
+    //
+    let mut words = String::new();
+    let mut depth = 0;
+
+    for c in text.chars() {
+
+        match c {
+
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth = (depth - 1).max(0),
+            c if depth == 0 => words.push(c),
+            _ => {}
+
+        }
+
+    }
+    //
+    words.split_whitespace().collect::<Vec<_>>().join(" ")
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Speech
+//
+/// Something that speaks a text with a voice
+pub trait Tts {
+
+    /// Speak a text
+    ///
+    /// # Arguments
+    ///
+    /// * 'text'  - the words to say
+    /// * 'voice' - how they are said
+    ///
+    fn speak(&self, text: &str, voice: &VoiceProfile) -> Result<Audio, EGeneral>;
+
+}
+//
+//
+/// What a letter sounds like in the synthesizer
+#[derive(Clone, Copy, Debug)]
+enum Sound {
+
+    /// the three formants of a vowel or of a voiced consonant and its loudness
+    Voiced([f32; 3], f32),
+    /// the center of the noise of a consonant
+    Noise(f32),
+    Pause,
+
+}
+//
+/// The sound of a letter and how long it lasts in seconds at the normal rate
+fn sound(c: char) -> (Sound, f32) {
+
+    match c {
+
+        'a' => (Sound::Voiced([730.0, 1090.0, 2440.0], 1.0), 0.09),
+        'e' => (Sound::Voiced([530.0, 1840.0, 2480.0], 1.0), 0.08),
+        'i' | 'y' => (Sound::Voiced([270.0, 2290.0, 3010.0], 0.9), 0.08),
+        'o' => (Sound::Voiced([570.0, 840.0, 2410.0], 1.0), 0.09),
+        'u' => (Sound::Voiced([300.0, 870.0, 2240.0], 0.9), 0.08),
+        'm' | 'n' => (Sound::Voiced([280.0, 1300.0, 2500.0], 0.5), 0.06),
+        'l' | 'r' | 'w' => (Sound::Voiced([350.0, 1100.0, 2300.0], 0.6), 0.05),
+        'b' | 'd' | 'g' | 'v' | 'z' | 'j' => (Sound::Voiced([250.0, 1500.0, 2600.0], 0.4), 0.05),
+        's' | 'x' | 'c' => (Sound::Noise(5500.0), 0.07),
+        'f' | 'h' => (Sound::Noise(2000.0), 0.06),
+        'k' | 'p' | 't' | 'q' => (Sound::Noise(3000.0), 0.04),
+        ',' | ';' | ':' => (Sound::Pause, 0.18),
+        '.' | '!' | '?' => (Sound::Pause, 0.32),
+        _ => (Sound::Pause, 0.04)
+
+    }
+
+}
+//
+//
+/// Two pole resonator, the filter of a formant
+#[derive(Default)]
+struct Resonator { y1: f32, y2: f32 }
+//
+impl Resonator {
+
+    fn next(&mut self, x: f32, frequency: f32, bandwidth: f32, sample_rate: f32) -> f32 {
+
+        let r = (-std::f32::consts::PI * bandwidth / sample_rate).exp();
+        let a1 = 2.0 * r * (2.0 * std::f32::consts::PI * frequency / sample_rate).cos();
+        let a2 = -r * r;
+
+        let y = (1.0 - r) * x + a1 * self.y1 + a2 * self.y2;
+
+        self.y2 = self.y1;
+        self.y1 = y;
+
+        y
+
+    }
+
+}
+//
+//
+// loudest sample of a spoken line
+const PEAK: f32 = 0.8;
+//
+// time to fade in and out each sound, in seconds
+const FADE: f32 = 0.008;
+//
+//
+/// A formant synthesizer, robotic but with nothing to install
+pub struct FormantSynth { pub sample_rate: u32 }
+//
+impl Default for FormantSynth {
+
+    fn default() -> Self { Self { sample_rate: SAMPLE_RATE } }
+
+}
+//
+impl Tts for FormantSynth {
+
+    fn speak(&self, text: &str, voice: &VoiceProfile) -> Result<Audio, EGeneral> {
+        //
+        let rate = self.sample_rate as f32;
+        let sounds: Vec<(Sound, f32)> = text.trim().to_lowercase().chars().map(sound).collect();
+        let question = text.trim_end().ends_with('?');
+
+        let total: f32 = sounds.iter().map(|(_, length)| length / voice.rate.max(0.1)).sum();
+        //
+        let mut samples = Vec::with_capacity((total * rate) as usize);
+        let mut formants = [Resonator::default(), Resonator::default(), Resonator::default(), Resonator::default()];
+        let mut current = [500.0, 1500.0, 2500.0];
+        let (mut phase, mut tilt, mut noise) = (0.0f32, 0.0f32, 0x2545f491u32);
+
+        // a bright voice has narrow formants and keeps its harmonics
+        let bandwidth = 1.6 - voice.timbre;
+        let smoothing = 0.7 - 0.5 * voice.timbre;
+        //
+        for (sound, length) in sounds {
+
+            let count = (length / voice.rate.max(0.1) * rate) as usize;
+            let fade = ((FADE * rate) as usize).min(count / 2).max(1);
+
+            for i in 0..count {
+
+                let envelope = (i.min(count - 1 - i) as f32 / fade as f32).min(1.0);
+                let progress = samples.len() as f32 / (total * rate).max(1.0);
+
+                // the pitch falls along a sentence and rises at the end of a question
+                let mut pitch = voice.pitch * (1.08 - 0.16 * progress);
+
+                if question && progress > 0.75 {
+                    pitch *= 1.0 + (progress - 0.75) * 1.2;
+                }
+
+                phase = (phase + pitch / rate).fract();
+
+                let sample = match sound {
+
+                    Sound::Voiced(targets, loudness) => {
+
+                        // the formants glide from the previous sound
+                        for (f, target) in current.iter_mut().zip(targets) {
+                            *f += (target - *f) * 0.004;
+                        }
+
+                        tilt = tilt * smoothing + (1.0 - 2.0 * phase) * (1.0 - smoothing);
+
+                        let shaped = formants[0].next(tilt, current[0], 60.0 * bandwidth, rate)
+                            + 0.6 * formants[1].next(tilt, current[1], 90.0 * bandwidth, rate)
+                            + 0.3 * formants[2].next(tilt, current[2], 120.0 * bandwidth, rate);
+
+                        shaped * loudness
+
+                    },
+
+                    Sound::Noise(center) => {
+
+                        // xorshift, the same noise at each run
+                        noise ^= noise << 13;
+                        noise ^= noise >> 17;
+                        noise ^= noise << 5;
+
+                        let white = noise as f32 / u32::MAX as f32 * 2.0 - 1.0;
+
+                        formants[3].next(white, center, 1500.0, rate) * 0.5
+
+                    },
+
+                    Sound::Pause => 0.0
+
+                };
+
+                samples.push(sample * envelope);
+
+            }
+
+        }
+        //
+        let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+
+        if peak > 0.0 {
+            samples.iter_mut().for_each(|s| *s *= PEAK / peak);
+        }
+        //
+        Ok(Audio { sample_rate: self.sample_rate, samples })
+        //
+    }
+
+}
+//
+//
+/// The espeak-ng program, its voices sound better than the synthesizer
+pub struct Espeak {
+
+    pub program:    String,
+    pub language:   String,
+
+}
+//
+impl Default for Espeak {
+
+    fn default() -> Self { Self { program: "espeak-ng".to_string(), language: "en".to_string() } }
+
+}
+//
+impl Espeak {
+    //
+    /// The arguments of the program, the timbre picks one of its male or female variants
+    fn arguments(&self, text: &str, voice: &VoiceProfile) -> Vec<String> {
+        //
+        let variant = match voice.pitch < 165.0 {
+
+            true => format!("m{}", 1 + (voice.timbre * 6.0).round() as u32),
+            false => format!("f{}", 1 + (voice.timbre * 3.0).round() as u32)
+
+        };
+
+        // espeak-ng takes a pitch from 0 to 99 and a number of words per minute
+        let pitch = ((voice.pitch - 60.0) / 2.4).clamp(0.0, 99.0).round();
+        let speed = (175.0 * voice.rate).round();
+        //
+        vec![
+            "-v".to_string(), format!("{}+{}", self.language, variant),
+            "-p".to_string(), pitch.to_string(),
+            "-s".to_string(), speed.to_string(),
+            "--stdout".to_string(),
+            text.trim_start_matches('-').to_string()
+        ]
+        //
+    }
+    //
+}
+//
+impl Tts for Espeak {
+
+    fn speak(&self, text: &str, voice: &VoiceProfile) -> Result<Audio, EGeneral> {
+        //
+        let output = Command::new(&self.program)
+            .args(self.arguments(text, voice))
+            .output()
+            .into_report()
+            .change_context(EGeneral::Audio)
+            .attach_printable_lazy(|| format!("unable to run '{}', is it installed?", self.program))?;
+        //
+        if !output.status.success() {
+
+            return Err(EGeneral::Audio.as_report().attach_printable(format!(
+                "'{}' failed: {}", self.program, String::from_utf8_lossy(&output.stderr).trim()
+            )));
+
+        }
+        //
+        Audio::from_wav(&output.stdout).attach_printable_lazy(|| format!("in the output of '{}'", self.program))
+        //
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Rendering
+//
+/// name of the file of the whole episode in its audio directory
+pub const TRACK_FILE: &str = "episode.wav";
+//
+/// directory of the lines in the audio directory of an episode
+pub const LINES_DIR: &str = "lines";
+//
+//
+/// The audio files of an episode
+#[derive(Clone, Debug)]
+pub struct Rendered {
+
+    /// the file of each reply
+    pub lines:      BTreeMap<LineKey, PathBuf>,
+    /// when each line is played, with the length of the recorded ones
+    pub timeline:   Timeline,
+    /// the whole episode
+    pub track:      PathBuf,
+    /// the sound effects of the script that were not in the bank
+    pub missing:    Vec<String>,
+
+}
+//
+//
+/// Speak every reply of an episode in its own file, then mix them with the sounds of the script
+/// in the track of the episode
+///
+/// # Arguments
+///
+/// * 'tts'     - what speaks the lines
+/// * 'episode' - the episode to render
+/// * 'voices'  - the voice of each character
+/// * 'sounds'  - the sound effects and the beds
+/// * 'pacing'  - the pauses between the lines
+/// * 'options' - how the track is mixed and written
+/// * 'dir'     - the audio directory of the episode, created if needed
+///
+pub fn render_episode<T: Tts>(
+    tts: &T,
+    episode: &Episode,
+    voices: &BTreeMap<String, VoiceProfile>,
+    sounds: &SoundBank,
+    pacing: &Pacing,
+    options: &MixOptions,
+    dir: &Path
+
+) -> Result<Rendered, EGeneral> {
+    //
+    let lines_dir = dir.join(LINES_DIR);
+
+    std::fs::create_dir_all(&lines_dir)
+        .into_report()
+        .change_context(EGeneral::Audio)
+        .attach_printable_lazy(|| format!("unable to create '{}'", lines_dir.display()))?;
+    //
+    let mut clips: HashMap<LineKey, Audio> = HashMap::new();
+    let mut lines = BTreeMap::new();
+
+    for (a, act) in episode.acts.iter().enumerate() {
+
+        for (s, scene) in act.scenes.iter().enumerate() {
+
+            for (b, beat) in scene.beats.iter().enumerate() {
+
+                for (l, line) in beat.lines.iter().enumerate() {
+
+                    let ScriptLine::Dialogue { speaker, text, .. } = line else { continue };
+
+                    let words = spoken(text);
+
+                    if words.is_empty() {
+                        continue;
+                    }
+
+                    let key = LineKey { act: a, scene: s, beat: b, line: l };
+                    let path = lines_dir.join(format!("{:02}-{:02}-{:02}-{:02}.{}", a + 1, s + 1, b + 1, l + 1, WAV_EXTENSION));
+
+                    let audio = tts.speak(&words, &voice_of(voices, speaker))
+                        .attach_printable_lazy(|| format!("while {} was saying '{}'", speaker, words))?;
+
+                    audio.save(&path)?;
+
+                    lines.insert(key, path);
+                    clips.insert(key, audio);
+
+                }
+
+            }
+
+        }
+
+    }
+    //
+    let arrangement = arrange(episode, pacing, &clips, sounds, options);
+
+    let mut track = arrangement.mix.render(options);
+    let length = Audio::silence(options.sample_rate, arrangement.timeline.duration()).samples.len();
+
+    // the track lasts at least until the last line, a direction can end it in silence
+    if track.samples.len() < length {
+        track.samples.resize(length, 0.0);
+    }
+    //
+    let path = dir.join(TRACK_FILE);
+
+    track.save_as(&path, options.format)?;
+
+    cdebug!(category: "audio", "{} lines rendered in '{}'", lines.len(), path.display());
+    //
+    Ok(Rendered { lines, timeline: arrangement.timeline, track: path, missing: arrangement.missing })
+    //
+}
@@ -0,0 +1,590 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Continuity
+//
+// The facts of the accepted episodes, like who is dead or which name a place has, are kept in
+// a store of the series. The facts about what a prompt talks about are given to the writers of
+// the next episodes, and a new episode is checked against them before it is accepted: a dead
+// character speaking is found without the model, the other contradictions are asked to it.
+//
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use error_stack::{IntoReport, Result, ResultExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::episode::{default_templates, Episode, Scene};
+use crate::logger::InContextExt;
+use crate::script::ScriptLine;
+use crate::series::Series;
+use crate::template::TemplateSet;
+use crate::timeline::LineKey;
+use crate::{cdebug, cinfo, CompletionClient, EGeneral, ModelType, PromptRequestInfo};
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use std::sync::Mutex;
+
+    use error_stack::Result;
+    use futures::executor::block_on;
+
+    use crate::episode::{default_templates, Episode};
+    use crate::script::ScriptLine;
+    use crate::series::{Character, Series};
+    use crate::timeline::LineKey;
+    use crate::{CompletionClient, EGeneral, ModelType, PromptRequestInfo};
+    use super::{Continuity, Fact, FactStore};
+
+    /// answer the extraction and the checks with canned texts
+    struct ScriptedClient { answer: &'static str, prompts: Mutex<Vec<String>> }
+
+    impl CompletionClient for ScriptedClient {
+
+        async fn complete(&self, request: PromptRequestInfo) -> Result<String, EGeneral> {
+
+            self.prompts.lock().unwrap().push(request.prompt);
+
+            Ok(self.answer.to_string())
+
+        }
+
+    }
+
+    fn client(answer: &'static str) -> ScriptedClient { ScriptedClient { answer, prompts: Mutex::new(Vec::new()) } }
+
+    fn series() -> Series {
+
+        let mut series = Series::new("The Night Shift", "Two robots run a diner on the moon");
+        series.characters = vec![Character::new("Bolt"), Character::new("Nova")];
+        series
+
+    }
+
+    fn fact(subject: &str, attribute: &str, value: &str, episode: u32) -> Fact {
+
+        Fact { subject: subject.to_string(), attribute: attribute.to_string(), value: value.to_string(), episode }
+
+    }
+
+    #[test]
+    fn store_keeps_the_latest_facts() {
+
+        let mut store = FactStore::default();
+
+        store.record(1, vec![fact("Bolt", "status", "alive", 1), fact("Diner", "name", "The Crater", 1)]);
+        store.record(2, vec![fact("bolt", "status", "dead", 2)]);
+
+        assert_eq!(store.facts.len(), 2);
+        assert_eq!(store.facts[0].to_string(), "bolt status: dead (episode 2)");
+
+        // an older episode accepted again does not undo the newer facts, and forgets the ones not given
+        store.record(1, vec![fact("Bolt", "status", "alive", 1)]);
+
+        assert_eq!(store.facts.len(), 1);
+        assert_eq!(store.status("BOLT").map(|f| f.episode), Some(2));
+
+        store.record(3, vec![fact("Nova", "job", "cook", 3), fact("Gus", "status", "gone", 3)]);
+
+        let relevant: Vec<String> = store.relevant("Nova cooks", 2).iter().map(|f| f.subject.clone()).collect();
+        assert_eq!(relevant, ["Nova", "Gus"]);
+
+        let prompt = default_templates()
+            .render("logline", &json_context(&store))
+            .unwrap();
+
+        assert!(prompt.contains("What happened in the past episodes:\n- Nova job: cook (episode 3)\n"));
+
+    }
+
+    fn json_context(store: &FactStore) -> serde_json::Value {
+
+        serde_json::json!({
+            "series": series(),
+            "episode": { "number": 4, "logline": "" },
+            "facts": store.relevant("", 10).iter().map(|f| f.to_string()).collect::<Vec<_>>()
+        })
+
+    }
+
+    #[test]
+    fn facts_are_extracted_with_the_schema() {
+
+        let client = client("1. Bolt | status | dead\n- The Diner | owner | Nova\nBolt is sad\nNova | job |");
+        let series = series();
+        let mut episode = crate::timeline::test::episode();
+        episode.number = 5;
+
+        let facts = block_on(Continuity::new(&client, &series, ModelType::Fastest).extract(&episode)).unwrap();
+
+        assert_eq!(facts, [fact("Bolt", "status", "dead", 5), fact("The Diner", "owner", "Nova", 5)]);
+        assert!(client.prompts.lock().unwrap()[0].contains("BOLT: one two three"));
+
+    }
+
+    #[test]
+    fn contradictions_are_flagged() {
+
+        let client = client("LINE 3: Nova moved to Mars\nline 9: out of the scene\nOK");
+        let series = series();
+        let episode = Episode { number: 2, ..crate::timeline::test::episode() };
+
+        let mut store = FactStore::default();
+        store.record(1, vec![fact("Bolt", "status", "destroyed", 1), fact("Nova", "home", "Mars", 1)]);
+
+        let issues = block_on(Continuity::new(&client, &series, ModelType::Fastest).check(&store, &episode)).unwrap();
+
+        let found: Vec<(LineKey, &str)> = issues.iter().map(|i| (i.key, i.reason.as_str())).collect();
+
+        // the dead Bolt speaks in both scenes, the model flags the reply of Nova
+        assert_eq!(found, [
+            (LineKey { act: 0, scene: 0, beat: 0, line: 0 }, "Bolt is destroyed since episode 1"),
+            (LineKey { act: 0, scene: 0, beat: 0, line: 3 }, "Nova moved to Mars"),
+            (LineKey { act: 0, scene: 1, beat: 0, line: 0 }, "Bolt is destroyed since episode 1"),
+        ]);
+        assert!(matches!(&issues[1].line, ScriptLine::Dialogue { text, .. } if text == "Hi."));
+
+        // the notes are not checked, the lines are numbered for the model
+        let prompts = client.prompts.lock().unwrap();
+        assert!(prompts[0].contains("\n2. [Nova rolls in]\n3. NOVA: Hi.\n"));
+        assert!(prompts[0].contains("- Nova home: Mars (episode 1)"));
+
+    }
+
+    #[test]
+    fn later_facts_are_ignored() {
+
+        let client = client("LINE 3: Nova moved to Mars");
+        let series = series();
+        let episode = Episode { number: 1, ..crate::timeline::test::episode() };
+
+        let mut store = FactStore::default();
+        store.record(1, vec![fact("Nova", "mood", "happy", 1)]);
+        store.record(3, vec![fact("Bolt", "status", "dead", 3), fact("Nova", "home", "Mars", 3)]);
+
+        let issues = block_on(Continuity::new(&client, &series, ModelType::Fastest).check(&store, &episode)).unwrap();
+
+        // checked again after episode 3, the episode 1 has no fact before it
+        assert!(issues.is_empty());
+        assert!(client.prompts.lock().unwrap().is_empty());
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Facts
+//
+/// file of the facts in the directory of a series
+pub const CONTINUITY_FILE: &str = "continuity.json";
+//
+// values of the 'status' attribute of a character who can't appear anymore
+const GONE: [&str; 7] = ["dead", "deceased", "destroyed", "gone", "killed", "left", "missing"];
+//
+// maximum tokens of the completions
+const FACTS_TOKENS: u16 = 300;
+const CHECK_TOKENS: u16 = 200;
+//
+/// maximum number of facts given to a prompt
+pub const PROMPT_FACTS: usize = 30;
+//
+//
+/// Something true at the end of an episode, like 'Bolt | status | dead'
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Fact {
+
+    /// a character, a place or a thing
+    pub subject:    String,
+    pub attribute:  String,
+    pub value:      String,
+    /// the episode where it became true
+    pub episode:    u32,
+
+}
+//
+impl Fact {
+    //
+    /// Check if the fact is about the same thing as another one, whatever the case
+    fn same(&self, other: &Fact) -> bool {
+
+        self.subject.eq_ignore_ascii_case(&other.subject) && self.attribute.eq_ignore_ascii_case(&other.attribute)
+
+    }
+    //
+}
+//
+impl Display for Fact {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+
+        write!(f, "{} {}: {} (episode {})", self.subject, self.attribute, self.value, self.episode)
+
+    }
+
+}
+//
+//
+/// The facts of the accepted episodes of a series
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct FactStore { pub facts: Vec<Fact> }
+//
+impl FactStore {
+    //
+    /// Load the facts of a series, none if the file does not exist yet
+    pub fn load(path: &Path) -> Result<Self, EGeneral> {
+        //
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        //
+        let content = std::fs::read_to_string(path)
+            .into_report()
+            .change_context(EGeneral::Continuity)
+            .attach_printable_lazy(|| format!("unable to read '{}'", path.display()))?;
+
+        serde_json::from_str(&content)
+            .into_report()
+            .change_context(EGeneral::Continuity)
+            .attach_printable_lazy(|| format!("in '{}'", path.display()))
+        //
+    }
+    //
+    //
+    /// Save the facts of a series
+    pub fn save(&self, path: &Path) -> Result<(), EGeneral> {
+
+        let content = serde_json::to_string_pretty(self).into_report().change_context(EGeneral::Continuity)?;
+
+        std::fs::write(path, content)
+            .into_report()
+            .change_context(EGeneral::Continuity)
+            .attach_printable_lazy(|| format!("unable to write '{}'", path.display()))
+
+    }
+    //
+    //
+    /// Replace the facts of an episode
+    ///
+    /// A fact replaces the one about the same subject and attribute, unless that one comes from
+    /// a later episode
+    ///
+    /// # Arguments
+    ///
+    /// * 'episode' - the episode accepted
+    /// * 'facts'   - every fact of the episode
+    ///
+    pub fn record(&mut self, episode: u32, facts: Vec<Fact>) {
+        //
+        self.facts.retain(|f| f.episode != episode);
+        //
+        for fact in facts {
+
+            let fact = Fact { episode, ..fact };
+
+            match self.facts.iter().position(|f| f.same(&fact)) {
+
+                Some(i) if self.facts[i].episode > episode => {},
+                Some(i) => self.facts[i] = fact,
+                None => self.facts.push(fact)
+
+            }
+
+        }
+        //
+    }
+    //
+    //
+    /// The status of a character or a thing, like 'dead'
+    pub fn status(&self, subject: &str) -> Option<&Fact> {
+
+        self.facts.iter().find(|f| f.subject.eq_ignore_ascii_case(subject) && f.attribute.eq_ignore_ascii_case("status"))
+
+    }
+    //
+    //
+    /// The facts to give to a prompt, the ones whose subject is in the text then the most recent
+    ///
+    /// # Arguments
+    ///
+    /// * 'text'  - what the prompt talks about
+    /// * 'limit' - the maximum number of facts
+    ///
+    pub fn relevant(&self, text: &str, limit: usize) -> Vec<&Fact> {
+        //
+        let text = text.to_lowercase();
+        let words: HashSet<&str> = text.split(|c: char| !c.is_alphanumeric()).collect();
+
+        let mentioned = |fact: &Fact| {
+
+            let subject = fact.subject.to_lowercase();
+
+            words.contains(subject.as_str()) || subject.contains(' ') && text.contains(&subject)
+
+        };
+        //
+        let mut facts: Vec<&Fact> = self.facts.iter().collect();
+
+        facts.sort_by_key(|f| (!mentioned(f), std::cmp::Reverse(f.episode)));
+        facts.truncate(limit);
+
+        facts
+        //
+    }
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Continuity
+//
+/// A line that contradicts the facts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Issue {
+
+    pub key:    LineKey,
+    pub line:   ScriptLine,
+    pub reason: String,
+
+}
+//
+impl Display for Issue {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+
+        write!(f, "act {} scene {}: {} ({})", self.key.act + 1, self.key.scene + 1, self.line, self.reason)
+
+    }
+
+}
+//
+//
+/// Extract the facts of the episodes and check the new ones against them
+pub struct Continuity<'a, C: CompletionClient> {
+
+    client:     &'a C,
+    series:     &'a Series,
+    templates:  TemplateSet,
+    model:      ModelType,
+
+}
+//
+impl<'a, C: CompletionClient> Continuity<'a, C> {
+    //
+    /// Create a checker using the default templates
+    ///
+    /// # Arguments
+    ///
+    /// * 'client' - what completes the prompts
+    /// * 'series' - the bible of the series
+    /// * 'model'  - the model of every completion
+    ///
+    pub fn new(client: &'a C, series: &'a Series, model: ModelType) -> Self {
+
+        Self { client, series, templates: default_templates(), model }
+
+    }
+    //
+    /// Replace the templates, the missing ones are taken from the defaults
+    pub fn with_templates(mut self, templates: TemplateSet) -> Self {
+
+        self.templates.merge(templates);
+        self
+
+    }
+    //
+    //
+    /// Ask the facts of a finished episode
+    pub async fn extract(&self, episode: &Episode) -> Result<Vec<Fact>, EGeneral> {
+        //
+        let script = episode.scenes()
+            .map(|s| {
+
+                let lines = s.beats.iter().flat_map(|b| &b.lines).map(|l| l.to_string());
+
+                std::iter::once(s.heading.to_uppercase()).chain(lines).collect::<Vec<_>>().join("\n")
+
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let context = json!({
+            "series": self.series,
+            "episode": { "number": episode.number, "logline": episode.logline },
+            "script": script
+        });
+        //
+        let text = self.complete("facts", &context, FACTS_TOKENS).await?;
+
+        let facts = parse_facts(&text, episode.number);
+
+        cinfo!(category: "script", "{} facts in episode {}", facts.len(), episode.number);
+
+        Ok(facts)
+        //
+    }
+    //
+    //
+    /// Find the lines of an episode that contradict the facts of the previous ones
+    ///
+    /// # Arguments
+    ///
+    /// * 'store'   - the facts of the accepted episodes
+    /// * 'episode' - the episode to check, only the facts of the episodes before it are used
+    ///
+    pub async fn check(&self, store: &FactStore, episode: &Episode) -> Result<Vec<Issue>, EGeneral> {
+        //
+        let mut issues = Vec::new();
+
+        let previous = FactStore { facts: store.facts.iter().filter(|f| f.episode < episode.number).cloned().collect() };
+        //
+        for (a, act) in episode.acts.iter().enumerate() {
+
+            for (s, scene) in act.scenes.iter().enumerate() {
+
+                issues.extend(self.check_scene(&previous, scene, a, s).in_context(&[("act", a + 1), ("scene", s + 1)]).await?);
+
+            }
+
+        }
+        //
+        Ok(issues)
+        //
+    }
+    //
+    //
+    /// Check the lines of a scene, the notes are left out
+    async fn check_scene(&self, store: &FactStore, scene: &Scene, a: usize, s: usize) -> Result<Vec<Issue>, EGeneral> {
+        //
+        let lines: Vec<(LineKey, &ScriptLine)> = scene.beats.iter().enumerate()
+            .flat_map(|(b, beat)| beat.lines.iter().enumerate().map(move |(l, line)| (LineKey { act: a, scene: s, beat: b, line: l }, line)))
+            .filter(|(_, line)| !matches!(line, ScriptLine::Note { .. }))
+            .collect();
+        //
+        // the characters who can't appear anymore
+        let mut issues: Vec<Issue> = lines.iter()
+            .filter_map(|(key, line)| match line {
+
+                ScriptLine::Dialogue { speaker, .. } => store.status(speaker)
+                    .filter(|f| GONE.contains(&f.value.trim().to_lowercase().as_str()))
+                    .map(|f| Issue { key: *key, line: (*line).clone(), reason: format!("{} is {} since episode {}", speaker, f.value, f.episode) }),
+
+                _ => None
+
+            })
+            .collect();
+        //
+        let text = std::iter::once(scene.summary.clone()).chain(lines.iter().map(|(_, l)| l.to_string())).collect::<Vec<_>>().join("\n");
+        let facts: Vec<String> = store.relevant(&text, PROMPT_FACTS).iter().map(|f| f.to_string()).collect();
+
+        if facts.is_empty() || lines.is_empty() {
+            return Ok(issues);
+        }
+        //
+        let context = json!({
+            "series": self.series,
+            "facts": facts,
+            "scene": { "heading": scene.heading, "summary": scene.summary },
+            "lines": lines.iter().map(|(_, l)| l.to_string()).collect::<Vec<_>>()
+        });
+
+        let text = self.complete("continuity", &context, CHECK_TOKENS).await?;
+        //
+        for (number, reason) in parse_contradictions(&text) {
+
+            let Some((key, line)) = number.checked_sub(1).and_then(|i| lines.get(i)) else {
+
+                cdebug!(category: "script", "the checker flagged a line {} out of the scene", number);
+                continue
+
+            };
+
+            if !issues.iter().any(|i| i.key == *key) {
+                issues.push(Issue { key: *key, line: (*line).clone(), reason });
+            }
+
+        }
+
+        issues.sort_by_key(|i| i.key);
+        //
+        Ok(issues)
+        //
+    }
+    //
+    //
+    /// Render a template and send it to the client
+    async fn complete(&self, template: &str, context: &Value, max_tokens: u16) -> Result<String, EGeneral> {
+
+        let mut request = PromptRequestInfo::from_template(&self.templates, template, context, self.model)
+            .change_context(EGeneral::Continuity)?;
+
+        request.max_word = Some(max_tokens);
+
+        self.client.complete(request).await
+            .change_context(EGeneral::Continuity)
+            .attach_printable_lazy(|| format!("while asking the {}", template))
+
+    }
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Parsing of the completions
+//
+/// Read the 'subject | attribute | value' lines, the others are ignored
+fn parse_facts(text: &str, episode: u32) -> Vec<Fact> {
+
+    text.lines()
+        .filter_map(|line| {
+
+            let line = line.trim().trim_start_matches(|c: char| c.is_ascii_digit() || matches!(c, '.' | ')' | '-' | '*' | '•'));
+            let parts: Vec<&str> = line.split('|').map(|p| p.trim()).collect();
+
+            match parts.as_slice() {
+
+                [subject, attribute, value] if !subject.is_empty() && !attribute.is_empty() && !value.is_empty() => Some(Fact {
+                    subject:    subject.to_string(),
+                    attribute:  attribute.to_string(),
+                    value:      value.to_string(),
+                    episode
+                }),
+
+                _ => None
+
+            }
+
+        })
+        .collect()
+
+}
+//
+//
+/// Read the 'LINE number: why' lines, the others are ignored
+fn parse_contradictions(text: &str) -> Vec<(usize, String)> {
+
+    text.lines()
+        .filter_map(|line| {
+
+            let line = line.trim();
+            let rest = line.get(..4).filter(|s| s.eq_ignore_ascii_case("line")).map(|_| line[4..].trim_start())?;
+
+            let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            let number = rest[..digits].parse().ok()?;
+            let reason = rest[digits..].trim_start_matches([':', '-', ' ']).trim();
+
+            Some((number, reason.to_string()))
+
+        })
+        .collect()
+
+}
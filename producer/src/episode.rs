@@ -0,0 +1,914 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Episode
+//
+// An episode is written from the top down: a logline, then an outline of the acts and their
+// scenes, then the beats of each scene and finally the dialogue of each beat. Every stage is
+// a completion whose prompt is built from the stages above it, so a single node of the tree
+// can be written again with everything under it, without touching the rest of the episode.
+//
+use std::path::Path;
+use std::sync::mpsc::Sender;
+
+use error_stack::{IntoReport, Result, ResultExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::continuity::{FactStore, PROMPT_FACTS};
+use crate::logger::InContextExt;
+use crate::script::{ScriptLine, ScriptParser};
+use crate::series::Series;
+use crate::template::TemplateSet;
+use crate::{cdebug, cinfo, estimate_tokens, CompletionClient, EGeneral, ModelType, PromptRequestInfo};
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use std::sync::Mutex;
+
+    use error_stack::Result;
+    use futures::executor::block_on;
+
+    use crate::script::ScriptLine;
+    use crate::series::{Character, Series};
+    use crate::{CompletionClient, EGeneral, ModelType, PromptRequestInfo};
+    use super::{parse_beats, parse_outline, strip_keyword, Episode, EpisodeNode, EpisodeWriter};
+
+    /// answer each stage with a canned text, the number of the call is added to the answers
+    #[derive(Default)]
+    struct ScriptedClient { calls: Mutex<Vec<String>> }
+
+    impl ScriptedClient {
+
+        fn count(&self, stage: &str) -> usize {
+            self.calls.lock().unwrap().iter().filter(|s| *s == stage).count()
+        }
+
+    }
+
+    impl CompletionClient for ScriptedClient {
+
+        async fn complete(&self, request: PromptRequestInfo) -> Result<String, EGeneral> {
+
+            let stage = request.prompt.trim_end().rsplit('\n').next().unwrap().to_string();
+            let mut calls = self.calls.lock().unwrap();
+            calls.push(stage.clone());
+            let n = calls.len();
+
+            Ok(match stage.as_str() {
+
+                "Logline:" => format!(" \"The diner runs out of coffee ({})\"", n),
+                "Outline:" => "ACT 1: The shortage\nSCENE: Diner, morning | No coffee\nACT 2: The trip\nSCENE: Crater | They drive\nSCENE: Depot | They find beans".to_string(),
+                "Scenes:" => format!("SCENE: Depot, night | A new scene ({})", n),
+                "Beats:" => format!("1. Bolt complains ({})\n2. Nova has an idea", n),
+                _ => format!("BOLT: Not my circuit. ({})", n)
+
+            })
+
+        }
+
+    }
+
+    fn series() -> Series {
+
+        let mut series = Series::new("The Night Shift", "Two robots run a diner on the moon");
+        series.characters = vec![Character::new("Bolt"), Character::new("Nova")];
+        series
+
+    }
+
+    #[test]
+    fn write_the_whole_tree() {
+
+        let client = ScriptedClient::default();
+        let series = series();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let writer = EpisodeWriter::new(&client, &series, ModelType::Fastest).with_acts(2).with_progress(sender);
+
+        let episode = block_on(writer.write(1, None)).unwrap();
+        let progress: Vec<Episode> = receiver.try_iter().collect();
+
+        assert_eq!(episode.logline, "The diner runs out of coffee (1)");
+        assert_eq!(episode.acts.len(), 2);
+        assert_eq!(episode.acts[1].scenes[1].heading, "Depot");
+        assert_eq!(episode.acts[1].scenes[1].summary, "They find beans");
+        assert!(episode.scenes().all(|s| s.beats.len() == 2 && s.beats.iter().all(|b| b.dialogue.starts_with("BOLT:"))));
+        assert!(matches!(&episode.acts[0].scenes[0].beats[0].lines[..], [ScriptLine::Dialogue { speaker, .. }] if speaker == "Bolt"));
+
+        assert_eq!(client.count("Beats:"), 3);
+        assert_eq!(client.count("Dialogue:"), 6);
+
+        // one snapshot per beat, the last one is the whole episode
+        assert_eq!(progress.len(), 6);
+        assert!(progress[0].acts[1].scenes[0].beats.is_empty());
+        assert_eq!(progress.last(), Some(&episode));
+
+    }
+
+    #[test]
+    fn regenerate_only_the_node_and_its_children() {
+
+        let client = ScriptedClient::default();
+        let series = series();
+        let writer = EpisodeWriter::new(&client, &series, ModelType::Fastest).with_acts(2);
+
+        let mut episode = block_on(writer.write(1, Some("Coffee crisis".to_string()))).unwrap();
+        let before = episode.clone();
+
+        block_on(writer.regenerate(&mut episode, EpisodeNode::Scene(1, 0))).unwrap();
+
+        assert_eq!(episode.acts[0], before.acts[0]);
+        assert_eq!(episode.acts[1].scenes[1], before.acts[1].scenes[1]);
+        assert_ne!(episode.acts[1].scenes[0].beats, before.acts[1].scenes[0].beats);
+        assert_eq!(episode.acts[1].scenes[0].heading, before.acts[1].scenes[0].heading);
+
+        let second = episode.acts[1].clone();
+
+        block_on(writer.regenerate(&mut episode, EpisodeNode::Act(0))).unwrap();
+
+        assert_eq!(episode.acts[0].summary, "The shortage");
+        assert!(episode.acts[0].scenes[0].summary.starts_with("A new scene"));
+        assert_eq!(episode.acts[1], second);
+        assert_eq!(client.count("Outline:"), 1);
+
+        let error = block_on(writer.regenerate(&mut episode, EpisodeNode::Beat(0, 0, 9))).unwrap_err();
+
+        assert!(format!("{:?}", error).contains("there is no beat 10"));
+
+    }
+
+    #[test]
+    fn outline_is_read_despite_the_markdown() {
+
+        let acts = parse_outline("\
+            Here is the outline:\n\
+            **ACT 1: The shortage**\n\
+            - Scene 1: Diner, morning - No coffee\n\
+            ## Act 2 - The trip\n\
+            * SCENE: Crater | They drive\n\
+        ").unwrap();
+
+        assert_eq!(acts.len(), 2);
+        assert_eq!(acts[0].summary, "The shortage");
+        assert_eq!(acts[0].scenes[0].heading, "Diner, morning");
+        assert_eq!(acts[0].scenes[0].summary, "No coffee");
+        assert_eq!(acts[1].summary, "The trip");
+        assert!(parse_outline("nothing useful").is_err());
+
+    }
+
+    #[test]
+    fn keywords_are_whole_words() {
+
+        let acts = parse_outline("\
+            Actually, here is a better outline:\n\
+            ACT 1: Action!\n\
+            Scenery is everywhere - Nothing\n\
+            Scene 1: Porch - Rain\n\
+        ").unwrap();
+
+        assert_eq!(acts.len(), 1);
+        assert_eq!(acts[0].summary, "Action!");
+        assert_eq!(acts[0].scenes.len(), 1);
+        assert_eq!(acts[0].scenes[0].heading, "Porch");
+
+        assert_eq!(strip_keyword("Action!", "act"), None);
+        assert_eq!(strip_keyword("Acté", "act"), None);
+        assert_eq!(strip_keyword("Aé", "act"), None);
+        assert_eq!(strip_keyword("act", "act"), Some(""));
+
+    }
+
+    #[test]
+    fn beats_without_their_bullets() {
+
+        assert_eq!(parse_beats("1. One\n\n- Two\n3) Three").unwrap(), ["One", "Two", "Three"]);
+        assert!(parse_beats("\n \n").is_err());
+
+    }
+
+    #[test]
+    fn episode_is_saved_as_json() {
+
+        let path = std::env::temp_dir().join(format!("producer-episode-{}.json", std::process::id()));
+
+        let client = ScriptedClient::default();
+        let series = series();
+        let episode = block_on(EpisodeWriter::new(&client, &series, ModelType::Fastest).write(2, None)).unwrap();
+
+        episode.save(&path).unwrap();
+        let loaded = Episode::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, episode);
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Episode tree
+//
+/// An episode of the series
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Episode {
+
+    pub number:     u32,
+    /// one sentence summary
+    pub logline:    String,
+    pub acts:       Vec<Act>,
+
+}
+//
+/// An act of an episode
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Act {
+
+    pub summary:    String,
+    pub scenes:     Vec<Scene>,
+
+}
+//
+/// A scene of an act
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Scene {
+
+    /// location and time of the scene
+    pub heading:    String,
+    pub summary:    String,
+    pub beats:      Vec<Beat>,
+
+}
+//
+/// A step of a scene and its dialogue
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Beat {
+
+    pub description:    String,
+    /// text of the completion, empty until the dialogue is written
+    pub dialogue:       String,
+    /// the dialogue read as script lines
+    pub lines:          Vec<ScriptLine>,
+
+}
+//
+impl Episode {
+    //
+    /// Read an episode saved as JSON
+    pub fn load(path: &Path) -> Result<Self, EGeneral> {
+
+        let content = std::fs::read_to_string(path)
+            .into_report()
+            .change_context(EGeneral::Episode)
+            .attach_printable_lazy(|| format!("unable to read '{}'", path.display()))?;
+
+        serde_json::from_str(&content)
+            .into_report()
+            .change_context(EGeneral::Episode)
+            .attach_printable_lazy(|| format!("in '{}'", path.display()))
+
+    }
+    //
+    //
+    /// Write the episode as JSON
+    pub fn save(&self, path: &Path) -> Result<(), EGeneral> {
+
+        let content = serde_json::to_string_pretty(self).into_report().change_context(EGeneral::Episode)?;
+
+        std::fs::write(path, content)
+            .into_report()
+            .change_context(EGeneral::Episode)
+            .attach_printable_lazy(|| format!("unable to write '{}'", path.display()))
+
+    }
+    //
+    //
+    /// Every scene of the episode in order
+    pub fn scenes(&self) -> impl Iterator<Item = &Scene> { self.acts.iter().flat_map(|a| a.scenes.iter()) }
+    //
+    //
+    /// Check that a node exists, the error tells which index is wrong
+    pub fn check(&self, node: EpisodeNode) -> Result<(), EGeneral> {
+        //
+        let missing = |what: &str, index: usize, count: usize| {
+
+            EGeneral::Episode.as_report()
+                .attach_printable(format!("there is no {} {}, there are only {}", what, index + 1, count))
+
+        };
+        //
+        let (act, scene, beat) = match node {
+
+            EpisodeNode::Logline | EpisodeNode::Outline => return Ok(()),
+            EpisodeNode::Act(a) => (a, None, None),
+            EpisodeNode::Scene(a, s) => (a, Some(s), None),
+            EpisodeNode::Beat(a, s, b) => (a, Some(s), Some(b))
+
+        };
+        //
+        let act_node = self.acts.get(act).ok_or_else(|| missing("act", act, self.acts.len()))?;
+
+        if let Some(scene) = scene {
+
+            let scene_node = act_node.scenes.get(scene).ok_or_else(|| missing("scene", scene, act_node.scenes.len()))?;
+
+            if let Some(beat) = beat {
+                scene_node.beats.get(beat).ok_or_else(|| missing("beat", beat, scene_node.beats.len()))?;
+            }
+
+        }
+        //
+        Ok(())
+        //
+    }
+    //
+}
+//
+//
+/// A node of the episode tree, the indexes start at 0
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EpisodeNode {
+
+    Logline,
+    /// every act
+    Outline,
+    Act(usize),
+    Scene(usize, usize),
+    Beat(usize, usize, usize),
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Writer
+//
+/// default number of acts of an episode
+pub const DEFAULT_ACTS: usize = 3;
+//
+// maximum tokens of the completion of each stage
+const LOGLINE_TOKENS:   u16 = 60;
+const OUTLINE_TOKENS:   u16 = 700;
+const ACT_TOKENS:       u16 = 300;
+const BEATS_TOKENS:     u16 = 300;
+const DIALOGUE_TOKENS:  u16 = 500;
+//
+//
+/// Templates used when the series doesn't give its own
+pub fn default_templates() -> TemplateSet {
+
+    let mut set = TemplateSet::new();
+
+    let defaults = [
+        ("bible",       include_str!("../templates/bible.prompt")),
+        ("logline",     include_str!("../templates/logline.prompt")),
+        ("outline",     include_str!("../templates/outline.prompt")),
+        ("act",         include_str!("../templates/act.prompt")),
+        ("beats",       include_str!("../templates/beats.prompt")),
+        ("dialogue",    include_str!("../templates/dialogue.prompt")),
+        ("correction",  include_str!("../templates/correction.prompt")),
+        ("agent",       include_str!("../templates/agent.prompt")),
+        ("director",    include_str!("../templates/director.prompt")),
+        ("reflect",     include_str!("../templates/reflect.prompt")),
+        ("facts",       include_str!("../templates/facts.prompt")),
+        ("continuity",  include_str!("../templates/continuity.prompt")),
+    ];
+
+    for (name, source) in defaults {
+
+        set.add(name, source).expect("the default templates are valid");
+
+    }
+
+    set
+
+}
+//
+//
+/// Write the episodes of a series with a completion client
+pub struct EpisodeWriter<'a, C: CompletionClient> {
+
+    client:     &'a C,
+    series:     &'a Series,
+    templates:  TemplateSet,
+    script:     ScriptParser,
+    model:      ModelType,
+    acts:       usize,
+    /// the dialogue is left to the agents when false
+    dialogue:   bool,
+    /// what happened in the previous episodes
+    facts:      FactStore,
+    /// passages of the previous episodes close to this one
+    recalled:   Vec<String>,
+    /// receives the episode each time a beat is written
+    progress:   Option<Sender<Episode>>,
+
+}
+//
+impl<'a, C: CompletionClient> EpisodeWriter<'a, C> {
+    //
+    /// Create a writer using the default templates
+    ///
+    /// # Arguments
+    ///
+    /// * 'client' - what completes the prompts
+    /// * 'series' - the bible of the series
+    /// * 'model'  - the model of every completion
+    ///
+    pub fn new(client: &'a C, series: &'a Series, model: ModelType) -> Self {
+
+        Self {
+            client,
+            series,
+            templates:  default_templates(),
+            script:     ScriptParser::for_series(series),
+            model,
+            acts:       DEFAULT_ACTS,
+            dialogue:   true,
+            facts:      FactStore::default(),
+            recalled:   Vec::new(),
+            progress:   None
+        }
+
+    }
+    //
+    /// Replace the templates, the missing ones are taken from the defaults
+    pub fn with_templates(mut self, templates: TemplateSet) -> Self {
+
+        self.templates.merge(templates);
+        self
+
+    }
+    //
+    /// Change the number of acts asked in the outline
+    pub fn with_acts(mut self, acts: usize) -> Self {
+
+        self.acts = acts;
+        self
+
+    }
+    //
+    /// Write the beats without their dialogue, to let the agents of a director play them
+    pub fn with_dialogue(mut self, dialogue: bool) -> Self {
+
+        self.dialogue = dialogue;
+        self
+
+    }
+    //
+    /// Give the facts of the previous episodes to the prompts that talk about them
+    pub fn with_facts(mut self, facts: FactStore) -> Self {
+
+        self.facts = facts;
+        self
+
+    }
+    //
+    /// Give passages of the previous episodes to every prompt, like the ones found in the index
+    pub fn with_recalled(mut self, recalled: Vec<String>) -> Self {
+
+        self.recalled = recalled;
+        self
+
+    }
+    //
+    /// Send the episode as it is each time the dialogue of a beat is written, to show it live
+    pub fn with_progress(mut self, progress: Sender<Episode>) -> Self {
+
+        self.progress = Some(progress);
+        self
+
+    }
+    //
+    //
+    /// Write a whole episode
+    ///
+    /// # Arguments
+    ///
+    /// * 'number'  - number of the episode in the series
+    /// * 'logline' - what happens in the episode, written by the model if not given
+    ///
+    pub async fn write(&self, number: u32, logline: Option<String>) -> Result<Episode, EGeneral> {
+        //
+        let mut episode = Episode { number, ..Episode::default() };
+
+        episode.logline = match logline {
+
+            Some(logline) => logline,
+            None => self.logline(&episode).await?
+
+        };
+
+        cinfo!(category: "script", "episode {}: {}", number, episode.logline);
+
+        self.regenerate(&mut episode, EpisodeNode::Outline).await?;
+
+        Ok(episode)
+        //
+    }
+    //
+    //
+    /// Write again a node of the episode and everything under it
+    ///
+    /// # Arguments
+    ///
+    /// * 'episode' - the episode to change
+    /// * 'node'    - the node to write again
+    ///
+    pub async fn regenerate(&self, episode: &mut Episode, node: EpisodeNode) -> Result<(), EGeneral> {
+        //
+        episode.check(node)?;
+        //
+        match node {
+
+            EpisodeNode::Logline => episode.logline = self.logline(episode).await?,
+
+            EpisodeNode::Outline => {
+
+                episode.acts = self.outline(episode).await?;
+
+                for a in 0..episode.acts.len() {
+                    self.write_act_scenes(episode, a).await?;
+                }
+
+            },
+
+            EpisodeNode::Act(a) => {
+
+                episode.acts[a].scenes = self.act(episode, a).await?;
+
+                self.write_act_scenes(episode, a).await?;
+
+            },
+
+            EpisodeNode::Scene(a, s) => self.write_scene(episode, a, s).await?,
+
+            EpisodeNode::Beat(a, s, b) => self.dialogue(episode, a, s, b).await?,
+
+        }
+        //
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Write the beats and the dialogue of every scene of an act
+    async fn write_act_scenes(&self, episode: &mut Episode, a: usize) -> Result<(), EGeneral> {
+
+        for s in 0..episode.acts[a].scenes.len() {
+            self.write_scene(episode, a, s).await?;
+        }
+
+        Ok(())
+
+    }
+    //
+    //
+    /// Write the beats of a scene then the dialogue of each beat
+    async fn write_scene(&self, episode: &mut Episode, a: usize, s: usize) -> Result<(), EGeneral> {
+        //
+        episode.acts[a].scenes[s].beats = self.beats(episode, a, s)
+            .in_context(&[("act", a + 1), ("scene", s + 1)])
+            .await?;
+        //
+        if !self.dialogue {
+            return Ok(());
+        }
+        //
+        for b in 0..episode.acts[a].scenes[s].beats.len() {
+
+            self.dialogue(episode, a, s, b)
+                .in_context(&[("act", a + 1), ("scene", s + 1), ("beat", b + 1)])
+                .await?;
+
+        }
+        //
+        Ok(())
+        //
+    }
+    //
+    //
+    // --------------------------------------------------------------------------------------------
+    // Stages
+    //
+    /// Write the logline of the episode
+    async fn logline(&self, episode: &Episode) -> Result<String, EGeneral> {
+
+        let text = self.complete("logline", self.context(episode, json!({})), LOGLINE_TOKENS).await?;
+
+        parse_logline(&text)
+
+    }
+    //
+    //
+    /// Write the acts and their scenes, without beats
+    async fn outline(&self, episode: &Episode) -> Result<Vec<Act>, EGeneral> {
+
+        let text = self.complete("outline", self.context(episode, json!({})), OUTLINE_TOKENS).await?;
+
+        parse_outline(&text)
+
+    }
+    //
+    //
+    /// Write the scenes of an act, without beats
+    async fn act(&self, episode: &Episode, a: usize) -> Result<Vec<Scene>, EGeneral> {
+        //
+        let act = &episode.acts[a];
+
+        let context = self.context(episode, json!({
+            "act": { "number": a + 1, "summary": act.summary }
+        }));
+        //
+        let text = self.complete("act", context, ACT_TOKENS).await?;
+        //
+        let scenes: Vec<Scene> = parse_outline(&text)?.into_iter().flat_map(|a| a.scenes).collect();
+
+        Ok(scenes)
+        //
+    }
+    //
+    //
+    /// Write the beats of a scene, without dialogue
+    async fn beats(&self, episode: &Episode, a: usize, s: usize) -> Result<Vec<Beat>, EGeneral> {
+        //
+        let scene = &episode.acts[a].scenes[s];
+
+        let context = self.context(episode, json!({
+            "act": { "number": a + 1, "summary": episode.acts[a].summary },
+            "scene": { "number": s + 1, "heading": scene.heading, "summary": scene.summary }
+        }));
+        //
+        let text = self.complete("beats", context, BEATS_TOKENS).await?;
+
+        let beats = parse_beats(&text)?.into_iter()
+            .map(|description| Beat { description, ..Beat::default() })
+            .collect();
+
+        Ok(beats)
+        //
+    }
+    //
+    //
+    /// Write the dialogue of a beat, the dialogue of the previous beat is given to the model
+    async fn dialogue(&self, episode: &mut Episode, a: usize, s: usize, b: usize) -> Result<(), EGeneral> {
+        //
+        let scene = &episode.acts[a].scenes[s];
+
+        let previous = match b {
+
+            0 => String::new(),
+            _ => scene.beats[b - 1].dialogue.clone()
+
+        };
+
+        let context = self.context(episode, json!({
+            "scene": { "number": s + 1, "heading": scene.heading, "summary": scene.summary },
+            "beat": { "number": b + 1, "description": scene.beats[b].description },
+            "previous": previous
+        }));
+        //
+        let request = self.request("dialogue", &context, DIALOGUE_TOKENS)?;
+
+        let (text, script) = self.script.complete(self.client, &self.templates, request).await
+            .change_context(EGeneral::Episode)
+            .attach_printable("while writing the dialogue")?;
+        //
+        let beat = &mut episode.acts[a].scenes[s].beats[b];
+
+        beat.dialogue = text.trim().to_string();
+        beat.lines = script.lines;
+
+        // nobody may be watching anymore, the episode is still written
+        if let Some(progress) = &self.progress {
+            let _ = progress.send(episode.clone());
+        }
+
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Render a template and send it to the client
+    ///
+    /// # Arguments
+    ///
+    /// * 'template'   - name of the template of the stage
+    /// * 'context'    - the values of the variables
+    /// * 'max_tokens' - maximum length of the completion
+    ///
+    async fn complete(&self, template: &str, context: Value, max_tokens: u16) -> Result<String, EGeneral> {
+
+        let request = self.request(template, &context, max_tokens)?;
+
+        self.client.complete(request).await
+            .change_context(EGeneral::Episode)
+            .attach_printable_lazy(|| format!("while writing the {}", template))
+        //
+    }
+    //
+    //
+    /// Render the prompt of a stage
+    fn request(&self, template: &str, context: &Value, max_tokens: u16) -> Result<PromptRequestInfo, EGeneral> {
+        //
+        let mut request = PromptRequestInfo::from_template(&self.templates, template, context, self.model)
+            .change_context(EGeneral::Episode)?;
+
+        request.max_word = Some(max_tokens);
+        //
+        cdebug!(category: "script", "writing the {} with a prompt of about {} tokens", template, estimate_tokens(&request.prompt));
+
+        Ok(request)
+        //
+    }
+    //
+    //
+    /// Variables of the templates, the series and the episode are always given
+    ///
+    /// # Arguments
+    ///
+    /// * 'episode' - the episode being written
+    /// * 'extra'   - the variables of the stage
+    ///
+    fn context(&self, episode: &Episode, extra: Value) -> Value {
+        //
+        let mut context = json!({
+            "series": self.series,
+            "episode": { "number": episode.number, "logline": episode.logline },
+            "acts": self.acts
+        });
+        //
+        if let (Some(context), Value::Object(extra)) = (context.as_object_mut(), extra) {
+            context.extend(extra);
+        }
+        //
+        // the facts about what the stage talks about, the series itself is left out
+        if !self.facts.facts.is_empty() {
+
+            let mut text = String::new();
+            strings(&context, &mut text);
+
+            let facts: Vec<String> = self.facts.relevant(&text, PROMPT_FACTS).iter().map(|f| f.to_string()).collect();
+
+            context["facts"] = json!(facts);
+
+        }
+        //
+        if !self.recalled.is_empty() {
+            context["recalled"] = json!(self.recalled);
+        }
+        //
+        context
+        //
+    }
+    //
+}
+//
+//
+/// Gather the texts of the variables, except the ones of the series
+fn strings(value: &Value, text: &mut String) {
+
+    match value {
+
+        Value::String(s) => { text.push_str(s); text.push('\n'); },
+        Value::Array(list) => list.iter().for_each(|v| strings(v, text)),
+        Value::Object(map) => map.iter().filter(|(k, _)| *k != "series").for_each(|(_, v)| strings(v, text)),
+        _ => {}
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Parsing of the completions
+//
+/// Remove the markdown and the list markers at the start and the end of a line
+fn clean_line(line: &str) -> &str {
+    //
+    let line = line.trim().trim_matches(|c| matches!(c, '*' | '_' | '#' | '`')).trim();
+    //
+    // '-', '*', '1.' or '1)' markers
+    let without_number = line.trim_start_matches(|c: char| c.is_ascii_digit());
+
+    let line = match without_number.strip_prefix(['.', ')']) {
+
+        Some(rest) if without_number.len() < line.len() => rest,
+        _ => line.strip_prefix(['-', '*', '•']).unwrap_or(line)
+
+    };
+    //
+    line.trim().trim_matches(|c| matches!(c, '*' | '_')).trim()
+    //
+}
+//
+//
+/// Remove a keyword like 'ACT 2:' at the start of a line, none if the line doesn't start with it
+///
+/// The keyword must be a whole word, 'Actually' doesn't start with 'act'
+fn strip_keyword<'l>(line: &'l str, keyword: &str) -> Option<&'l str> {
+    //
+    let end = line.char_indices().nth(keyword.chars().count()).map_or(line.len(), |(i, _)| i);
+    let (head, rest) = line.split_at(end);
+
+    if !head.eq_ignore_ascii_case(keyword) || rest.starts_with(char::is_alphanumeric) {
+        return None;
+    }
+    //
+    let rest = rest.trim_start();
+    let rest = rest.trim_start_matches(|c: char| c.is_ascii_digit()).trim_start();
+
+    let rest = rest.strip_prefix([':', '-', '–', '.']).unwrap_or(rest);
+
+    Some(rest.trim().trim_matches(|c| matches!(c, '*' | '_')).trim())
+    //
+}
+//
+//
+/// Read the logline from its completion
+fn parse_logline(text: &str) -> Result<String, EGeneral> {
+
+    text.lines()
+        .map(clean_line)
+        .map(|line| strip_keyword(line, "logline").unwrap_or(line))
+        .map(|line| line.trim_matches(|c| matches!(c, '"' | '\'' | '“' | '”')).trim())
+        .find(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .ok_or_else(|| EGeneral::Episode.as_report().attach_printable("the logline is empty"))
+
+}
+//
+//
+/// Read the acts and their scenes from an outline
+///
+/// The lines starting with 'ACT' start an act and the ones starting with 'SCENE' add a scene
+/// to it, the location and the summary of a scene are split on '|' or ' - '
+fn parse_outline(text: &str) -> Result<Vec<Act>, EGeneral> {
+    //
+    let mut acts: Vec<Act> = Vec::new();
+    //
+    for line in text.lines().map(clean_line) {
+
+        if let Some(summary) = strip_keyword(line, "act") {
+
+            acts.push(Act { summary: summary.to_string(), scenes: Vec::new() });
+
+        } else if let Some(scene) = strip_keyword(line, "scene") {
+
+            let (heading, summary) = scene.split_once('|')
+                .or_else(|| scene.split_once(" - "))
+                .unwrap_or(("", scene));
+
+            if acts.is_empty() {
+                acts.push(Act::default());
+            }
+
+            acts.last_mut().unwrap().scenes.push(Scene {
+                heading:    heading.trim().to_string(),
+                summary:    summary.trim().to_string(),
+                beats:      Vec::new()
+            });
+
+        }
+
+    }
+    //
+    acts.retain(|a| !a.scenes.is_empty());
+
+    match acts.is_empty() {
+
+        true => Err(EGeneral::Episode.as_report().attach_printable(format!("no scene found in the outline:\n{}", text))),
+        false => Ok(acts)
+
+    }
+    //
+}
+//
+//
+/// Read the beats of a scene, one per line
+fn parse_beats(text: &str) -> Result<Vec<String>, EGeneral> {
+
+    let beats: Vec<String> = text.lines()
+        .map(clean_line)
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect();
+
+    match beats.is_empty() {
+
+        true => Err(EGeneral::Episode.as_report().attach_printable("no beat found in the completion")),
+        false => Ok(beats)
+
+    }
+
+}
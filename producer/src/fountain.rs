@@ -0,0 +1,890 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Fountain
+//
+// Read and write the Fountain screenplay format (https://fountain.io). The acts are written as
+// '#' sections, the beats as '##' sections and the summaries as '=' synopses, so an exported
+// episode can be read back with its whole tree. A script written by hand without sections is
+// read as a single act with one beat per scene.
+//
+use error_stack::Result;
+
+use crate::episode::{Act, Beat, Episode, Scene};
+use crate::script::{is_heading, is_transition, ScriptLine};
+use crate::series::Series;
+use crate::EGeneral;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use crate::episode::{Act, Beat, Episode, Scene};
+    use crate::script::ScriptLine;
+    use crate::series::{Character, Series};
+    use super::{export, import, Element, Fountain};
+
+    fn series() -> Series {
+
+        let mut series = Series::new("The Night Shift", "Two robots run a diner on the moon");
+        series.characters = vec![Character::new("Bolt"), Character::new("Nova")];
+        series
+
+    }
+
+    fn dialogue(speaker: &str, parenthetical: Option<&str>, text: &str) -> ScriptLine {
+
+        ScriptLine::Dialogue {
+            speaker:        speaker.to_string(),
+            parenthetical:  parenthetical.map(|p| p.to_string()),
+            text:           text.to_string()
+        }
+
+    }
+
+    #[test]
+    fn every_element_is_read() {
+
+        let fountain = Fountain::parse("\
+Title: The Night Shift
+Credit: Written by
+Author: Someone
+    and Someone Else
+
+/* a scene that was cut
+INT. NOWHERE */
+EXT. MOON CRATER - DAY #12#
+
+Nova rolls in. [[slower?]]
+The lights flicker.
+
+BOLT (V.O.)
+Not my circuit.
+(beat)
+Ask Nova.
+
+@McCLANE
+Yippee.
+
+[[check the timing]]
+
+CUT TO:
+
+> FADE OUT.
+
+!LOUD NOISE.
+");
+
+        assert_eq!(fountain.title_page, [
+            ("Title".to_string(), "The Night Shift".to_string()),
+            ("Credit".to_string(), "Written by".to_string()),
+            ("Author".to_string(), "Someone\nand Someone Else".to_string()),
+        ]);
+
+        let lines: Vec<&ScriptLine> = fountain.elements.iter()
+            .filter_map(|e| match e { Element::Line(l) => Some(l), _ => None })
+            .collect();
+
+        assert_eq!(lines, [
+            &ScriptLine::Heading { text: "EXT. MOON CRATER - DAY".to_string() },
+            &ScriptLine::Direction { text: "Nova rolls in. The lights flicker.".to_string() },
+            &dialogue("BOLT", Some("V.O."), "Not my circuit."),
+            &dialogue("BOLT", Some("beat"), "Ask Nova."),
+            &dialogue("McCLANE", None, "Yippee."),
+            &ScriptLine::Note { text: "check the timing".to_string() },
+            &ScriptLine::Transition { text: "CUT TO:".to_string() },
+            &ScriptLine::Transition { text: "FADE OUT.".to_string() },
+            &ScriptLine::Direction { text: "LOUD NOISE.".to_string() },
+        ]);
+
+    }
+
+    #[test]
+    fn episode_survives_a_round_trip() {
+
+        let beat = |description: &str, lines: Vec<ScriptLine>| Beat {
+            description: description.to_string(),
+            dialogue: lines.iter().map(|l| l.to_string()).collect::<Vec<_>>().join("\n"),
+            lines
+        };
+
+        let episode = Episode {
+            number:     4,
+            logline:    "The diner runs out of coffee".to_string(),
+            acts:       vec![Act {
+                summary:    "The shortage".to_string(),
+                scenes:     vec![Scene {
+                    heading:    "Diner, morning".to_string(),
+                    summary:    "No coffee".to_string(),
+                    beats:      vec![
+                        beat("Bolt complains", vec![
+                            ScriptLine::Direction { text: "STEAM.".to_string() },
+                            dialogue("Bolt", Some("angry"), "Not my circuit."),
+                            ScriptLine::Note { text: "funnier".to_string() },
+                        ]),
+                        beat("Nova has an idea", vec![
+                            dialogue("Nova", None, "Road trip."),
+                            ScriptLine::Transition { text: "SMASH CUT TO:".to_string() },
+                        ]),
+                    ]
+                }]
+            }]
+        };
+
+        let text = export(&episode, &series());
+
+        assert!(text.starts_with("Title: The Night Shift\nEpisode: 4\nLogline: The diner runs out of coffee\n\n# Act 1\n= The shortage\n\n.Diner, morning\n= No coffee\n"));
+        assert!(text.contains("\n!STEAM.\n\nBOLT\n(angry)\nNot my circuit.\n"));
+
+        assert_eq!(import(&text, &series()).unwrap(), episode);
+
+    }
+
+    #[test]
+    fn script_without_sections() {
+
+        let episode = import("INT. DINER - NIGHT\n\nBOLT\nHello.\n\nEXT. CRATER\n\nNOVA\nBye.", &series()).unwrap();
+
+        assert_eq!(episode.number, 0);
+        assert_eq!(episode.acts.len(), 1);
+        assert_eq!(episode.acts[0].scenes.len(), 2);
+        assert_eq!(episode.acts[0].scenes[1].beats[0].lines, [dialogue("Nova", None, "Bye.")]);
+        assert_eq!(episode.acts[0].scenes[1].beats[0].dialogue, "NOVA: Bye.");
+
+        assert!(import("Title: Empty\n\nJust an action.", &series()).is_err());
+
+    }
+
+    #[test]
+    fn script_opening_on_a_transition() {
+
+        let fountain = Fountain::parse("\
+FADE IN:
+
+INT. DINER - NIGHT
+
+BOLT
+Hello.
+");
+
+        assert!(fountain.title_page.is_empty());
+        assert_eq!(fountain.elements[0], Element::Line(ScriptLine::Direction { text: "FADE IN:".to_string() }));
+
+        // a known key can have its value on the next lines
+        let fountain = Fountain::parse("Title:\n    The Night Shift\n\nFADE IN:\n");
+
+        assert_eq!(fountain.title_page, [("Title".to_string(), "The Night Shift".to_string())]);
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Document
+//
+/// extension of the Fountain files
+pub const FOUNTAIN_EXTENSION: &str = "fountain";
+//
+// keys of a title page, the other ones need a value on their line
+const TITLE_KEYS: [&str; 12] = [
+    "Title", "Credit", "Author", "Authors", "Source", "Draft date", "Date", "Contact", "Copyright",
+    "Notes", "Episode", "Logline"
+];
+//
+//
+/// A part of a Fountain document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Element {
+
+    /// '# text', the depth is the number of '#'
+    Section { depth: usize, text: String },
+
+    /// '= text'
+    Synopsis { text: String },
+
+    Line(ScriptLine),
+
+}
+//
+//
+/// A Fountain document
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Fountain {
+
+    /// keys and values of the title page in their order
+    pub title_page:     Vec<(String, String)>,
+    pub elements:       Vec<Element>,
+
+}
+//
+impl Fountain {
+    //
+    /// Value of a key of the title page, whatever its case
+    pub fn title(&self, key: &str) -> Option<&str> {
+
+        self.title_page.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| v.as_str())
+
+    }
+    //
+    //
+    /// Read a Fountain document, what is not recognized is read as action
+    pub fn parse(text: &str) -> Self {
+        //
+        let text = remove_boneyard(&text.replace("\r\n", "\n"));
+        let lines: Vec<&str> = text.lines().collect();
+        //
+        let mut fountain = Self::default();
+        let mut i = fountain.read_title_page(&lines);
+        let mut after_blank = true;
+        //
+        while i < lines.len() {
+
+            let trimmed = lines[i].trim();
+            let next_blank = lines.get(i + 1).is_none_or(|l| l.trim().is_empty());
+
+            i += 1;
+
+            if trimmed.is_empty() {
+
+                after_blank = true;
+                continue;
+
+            }
+
+            let element = if trimmed.chars().all(|c| c == '=') && trimmed.len() >= 3 {
+
+                // page break
+                None
+
+            } else if let Some(note) = trimmed.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+
+                Some(Element::Line(ScriptLine::Note { text: note.trim().to_string() }))
+
+            } else if trimmed.starts_with('#') {
+
+                let depth = trimmed.chars().take_while(|c| *c == '#').count();
+
+                Some(Element::Section { depth, text: trimmed[depth..].trim().to_string() })
+
+            } else if let Some(synopsis) = trimmed.strip_prefix('=') {
+
+                Some(Element::Synopsis { text: synopsis.trim().to_string() })
+
+            } else if let Some(heading) = forced_heading(trimmed, after_blank) {
+
+                Some(Element::Line(ScriptLine::Heading { text: heading }))
+
+            } else if let Some(centered) = trimmed.strip_prefix('>').and_then(|l| l.strip_suffix('<')) {
+
+                Some(Element::Line(ScriptLine::Direction { text: centered.trim().to_string() }))
+
+            } else if let Some(transition) = trimmed.strip_prefix('>') {
+
+                Some(Element::Line(ScriptLine::Transition { text: transition.trim().to_string() }))
+
+            } else if after_blank && next_blank && is_transition(trimmed) {
+
+                Some(Element::Line(ScriptLine::Transition { text: trimmed.to_string() }))
+
+            } else if let Some(cue) = character_cue(trimmed, after_blank, next_blank) {
+
+                let end = lines[i..].iter().position(|l| l.trim().is_empty()).map_or(lines.len(), |p| i + p);
+
+                fountain.push_dialogue(cue, &lines[i..end]);
+                i = end;
+
+                None
+
+            } else {
+
+                // an action paragraph ends at the next blank line
+                let end = lines[i..].iter().position(|l| l.trim().is_empty()).map_or(lines.len(), |p| i + p);
+
+                let paragraph = std::iter::once(trimmed)
+                    .chain(lines[i..end].iter().map(|l| l.trim()))
+                    .map(strip_notes)
+                    .filter(|l| !l.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                i = end;
+
+                let paragraph = paragraph.strip_prefix('!').unwrap_or(&paragraph).trim().to_string();
+
+                Some(Element::Line(ScriptLine::Direction { text: paragraph }))
+
+            };
+
+            fountain.elements.extend(element);
+            after_blank = false;
+
+        }
+        //
+        fountain
+        //
+    }
+    //
+    //
+    /// Read the title page at the start of the document, give back the index of the first
+    /// line after it
+    fn read_title_page(&mut self, lines: &[&str]) -> usize {
+        //
+        let mut i = 0;
+        //
+        while let Some(line) = lines.get(i) {
+
+            if line.trim().is_empty() {
+                break;
+            }
+
+            let continuation = line.starts_with("   ") || line.starts_with('\t');
+
+            match (continuation, self.title_page.last_mut(), title_key(line)) {
+
+                (true, Some((_, value)), _) => {
+
+                    if !value.is_empty() {
+                        value.push('\n');
+                    }
+
+                    value.push_str(line.trim());
+
+                },
+
+                (false, _, Some((key, value))) => self.title_page.push((key.to_string(), value.to_string())),
+
+                // not a title page
+                _ => {
+
+                    self.title_page.clear();
+                    return 0;
+
+                }
+
+            }
+
+            i += 1;
+
+        }
+        //
+        i
+        //
+    }
+    //
+    //
+    /// Add the replies of a character, a parenthetical starts a new reply
+    ///
+    /// # Arguments
+    ///
+    /// * 'cue'   - the name of the character and its extension like 'V.O.'
+    /// * 'lines' - the lines under the cue until the next blank line
+    ///
+    fn push_dialogue(&mut self, (speaker, extension): (String, Option<String>), lines: &[&str]) {
+        //
+        let mut parenthetical = extension;
+        let mut text: Vec<String> = Vec::new();
+        //
+        let flush = |parenthetical: Option<String>, text: &mut Vec<String>, elements: &mut Vec<Element>| {
+
+            if !text.is_empty() {
+
+                elements.push(Element::Line(ScriptLine::Dialogue {
+                    speaker:        speaker.clone(),
+                    parenthetical,
+                    text:           text.join("\n")
+                }));
+
+                text.clear();
+
+            }
+
+        };
+        //
+        for line in lines.iter().map(|l| l.trim()) {
+
+            match line.strip_prefix('(').and_then(|l| l.strip_suffix(')')) {
+
+                Some(inner) => {
+
+                    flush(parenthetical.take(), &mut text, &mut self.elements);
+                    parenthetical = Some(inner.trim().to_string());
+
+                },
+
+                None => {
+
+                    let line = strip_notes(line);
+
+                    if !line.is_empty() {
+                        text.push(line);
+                    }
+
+                }
+
+            }
+
+        }
+        //
+        flush(parenthetical, &mut text, &mut self.elements);
+        //
+    }
+    //
+}
+//
+//
+/// Write the document, the elements are separated by blank lines
+impl std::fmt::Display for Fountain {
+
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        //
+        for (key, value) in &self.title_page {
+
+            let mut values = value.lines();
+
+            writeln!(f, "{}: {}", key, values.next().unwrap_or(""))?;
+
+            for value in values {
+                writeln!(f, "    {}", value)?;
+            }
+
+        }
+        //
+        if !self.title_page.is_empty() {
+            writeln!(f)?;
+        }
+        //
+        let mut previous: Option<&Element> = None;
+
+        for element in &self.elements {
+
+            // a synopsis stays under its section or its heading
+            let attached = matches!(element, Element::Synopsis { .. })
+                && matches!(previous, Some(Element::Section { .. } | Element::Line(ScriptLine::Heading { .. })));
+
+            if previous.is_some() && !attached {
+                writeln!(f)?;
+            }
+
+            match element {
+
+                Element::Section { depth, text } => writeln!(f, "{}", format!("{} {}", "#".repeat(*depth), text).trim_end())?,
+                Element::Synopsis { text } => writeln!(f, "= {}", text)?,
+                Element::Line(line) => write_line(f, line)?
+
+            }
+
+            previous = Some(element);
+
+        }
+        //
+        Ok(())
+        //
+    }
+
+}
+//
+//
+/// Write a script line as Fountain, the elements that would be read as something else are forced
+fn write_line(f: &mut std::fmt::Formatter<'_>, line: &ScriptLine) -> std::fmt::Result {
+
+    match line {
+
+        ScriptLine::Heading { text } if is_heading(text) => writeln!(f, "{}", text),
+        ScriptLine::Heading { text } => writeln!(f, ".{}", text),
+
+        ScriptLine::Direction { text } if needs_forced_action(text) => writeln!(f, "!{}", text),
+        ScriptLine::Direction { text } => writeln!(f, "{}", text),
+
+        ScriptLine::Dialogue { speaker, parenthetical, text } => {
+
+            let cue = speaker.to_uppercase();
+
+            match cue.chars().any(|c| c.is_alphabetic()) {
+
+                true => writeln!(f, "{}", cue)?,
+                false => writeln!(f, "@{}", speaker)?
+
+            }
+
+            if let Some(parenthetical) = parenthetical {
+                writeln!(f, "({})", parenthetical)?;
+            }
+
+            writeln!(f, "{}", text)
+
+        },
+
+        ScriptLine::Transition { text } if is_transition(text) => writeln!(f, "{}", text),
+        ScriptLine::Transition { text } => writeln!(f, "> {}", text),
+
+        ScriptLine::Note { text } => writeln!(f, "[[{}]]", text)
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Episode
+//
+/// Write an episode as Fountain, the acts and the beats are sections
+///
+/// # Arguments
+///
+/// * 'episode' - the episode to write
+/// * 'series'  - its series, for the title page
+///
+pub fn export(episode: &Episode, series: &Series) -> String {
+    //
+    let mut fountain = Fountain {
+        title_page: vec![
+            ("Title".to_string(), series.title.clone()),
+            ("Episode".to_string(), episode.number.to_string()),
+        ],
+        elements: Vec::new()
+    };
+
+    if !episode.logline.is_empty() {
+        fountain.title_page.push(("Logline".to_string(), episode.logline.clone()));
+    }
+    //
+    let synopsis = |text: &str| Element::Synopsis { text: text.to_string() };
+    let elements = &mut fountain.elements;
+    //
+    for (a, act) in episode.acts.iter().enumerate() {
+
+        elements.push(Element::Section { depth: 1, text: format!("Act {}", a + 1) });
+
+        if !act.summary.is_empty() {
+            elements.push(synopsis(&act.summary));
+        }
+
+        for (s, scene) in act.scenes.iter().enumerate() {
+
+            // a scene without heading would be merged with the previous one
+            let heading = match scene.heading.is_empty() {
+
+                true => format!("Scene {}", s + 1),
+                false => scene.heading.clone()
+
+            };
+
+            elements.push(Element::Line(ScriptLine::Heading { text: heading }));
+
+            if !scene.summary.is_empty() {
+                elements.push(synopsis(&scene.summary));
+            }
+
+            for (b, beat) in scene.beats.iter().enumerate() {
+
+                // the lines right under a heading are read as a first beat without description
+                if b > 0 || !beat.description.is_empty() {
+                    elements.push(Element::Section { depth: 2, text: beat.description.clone() });
+                }
+
+                elements.extend(beat.lines.iter().cloned().map(Element::Line));
+
+            }
+
+        }
+
+    }
+    //
+    fountain.to_string()
+    //
+}
+//
+//
+/// Read an episode written in Fountain
+///
+/// The '#' sections start the acts and the '##' sections the beats, a synopsis under a section
+/// or a heading is its summary. The speakers get the names of the characters of the series.
+///
+/// # Arguments
+///
+/// * 'text'   - the Fountain document
+/// * 'series' - the series of the episode
+///
+pub fn import(text: &str, series: &Series) -> Result<Episode, EGeneral> {
+    //
+    let fountain = Fountain::parse(text);
+    //
+    if !fountain.elements.iter().any(|e| matches!(e, Element::Line(ScriptLine::Heading { .. }))) {
+        return Err(EGeneral::Fountain.as_report().attach_printable("the script has no scene heading"));
+    }
+    //
+    let mut episode = Episode {
+        number:     fountain.title("Episode").and_then(|n| n.trim().parse().ok()).unwrap_or(0),
+        logline:    fountain.title("Logline").unwrap_or("").to_string(),
+        acts:       Vec::new()
+    };
+    //
+    // what a synopsis is the summary of
+    #[derive(PartialEq)]
+    enum Last { Act, Scene, Other }
+
+    let mut last = Last::Other;
+    //
+    for element in fountain.elements {
+
+        match element {
+
+            Element::Section { depth: 1, text } => {
+
+                let summary = match text.get(..3).is_some_and(|t| t.eq_ignore_ascii_case("act")) {
+
+                    true => String::new(),
+                    false => text
+
+                };
+
+                episode.acts.push(Act { summary, scenes: Vec::new() });
+                last = Last::Act;
+
+            },
+
+            Element::Section { depth: 2, text } => {
+
+                current_scene(&mut episode).beats.push(Beat { description: text, ..Beat::default() });
+                last = Last::Other;
+
+            },
+
+            Element::Synopsis { text } if last == Last::Act => {
+                current_act(&mut episode).summary = text;
+            },
+
+            Element::Synopsis { text } if last == Last::Scene => {
+                current_scene(&mut episode).summary = text;
+            },
+
+            Element::Line(ScriptLine::Heading { text }) => {
+
+                current_act(&mut episode).scenes.push(Scene { heading: text, ..Scene::default() });
+                last = Last::Scene;
+
+            },
+
+            Element::Line(mut line) => {
+
+                if let ScriptLine::Dialogue { speaker, .. } = &mut line {
+
+                    if let Some(character) = series.character(speaker) {
+                        *speaker = character.name.clone();
+                    }
+
+                }
+
+                current_beat(&mut episode).lines.push(line);
+                last = Last::Other;
+
+            },
+
+            _ => {}
+
+        }
+
+    }
+    //
+    episode.acts.retain(|a| !a.scenes.is_empty());
+    //
+    // the dialogue is given to the model as the previous beat
+    for beat in episode.acts.iter_mut().flat_map(|a| a.scenes.iter_mut()).flat_map(|s| s.beats.iter_mut()) {
+
+        beat.dialogue = beat.lines.iter().map(|l| l.to_string()).collect::<Vec<_>>().join("\n");
+
+    }
+    //
+    Ok(episode)
+    //
+}
+//
+//
+/// The last act, created if there is none
+fn current_act(episode: &mut Episode) -> &mut Act {
+
+    if episode.acts.is_empty() {
+        episode.acts.push(Act::default());
+    }
+
+    episode.acts.last_mut().unwrap()
+
+}
+//
+//
+/// The last scene of the last act, created if there is none
+fn current_scene(episode: &mut Episode) -> &mut Scene {
+
+    let act = current_act(episode);
+
+    if act.scenes.is_empty() {
+        act.scenes.push(Scene::default());
+    }
+
+    act.scenes.last_mut().unwrap()
+
+}
+//
+//
+/// The last beat of the last scene, created if there is none
+fn current_beat(episode: &mut Episode) -> &mut Beat {
+
+    let scene = current_scene(episode);
+
+    if scene.beats.is_empty() {
+        scene.beats.push(Beat::default());
+    }
+
+    scene.beats.last_mut().unwrap()
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Lines
+//
+/// Remove the '/* */' comments, even across lines
+fn remove_boneyard(text: &str) -> String {
+    //
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    //
+    while let Some(start) = rest.find("/*") {
+
+        result.push_str(&rest[..start]);
+
+        rest = match rest[start..].find("*/") {
+
+            Some(end) => &rest[start + end + 2..],
+            None => ""
+
+        };
+
+    }
+    //
+    result.push_str(rest);
+    result
+    //
+}
+//
+//
+/// Remove the '[[ ]]' notes inside a line
+fn strip_notes(line: &str) -> String {
+    //
+    let mut result = String::new();
+    let mut rest = line;
+    //
+    while let Some(start) = rest.find("[[") {
+
+        result.push_str(&rest[..start]);
+
+        rest = match rest[start..].find("]]") {
+
+            Some(end) => &rest[start + end + 2..],
+            None => ""
+
+        };
+
+    }
+    //
+    result.push_str(rest);
+
+    result.split_whitespace().collect::<Vec<_>>().join(" ")
+    //
+}
+//
+//
+/// Key and value of a title page line like 'Title: The Night Shift'
+///
+/// A key without a value must be a known one, 'FADE IN:' is not a title page
+fn title_key(line: &str) -> Option<(&str, &str)> {
+
+    let (key, value) = line.split_once(':')?;
+    let (key, value) = (key.trim(), value.trim());
+
+    let valid = key.starts_with(|c: char| c.is_alphabetic())
+        && key.chars().all(|c| c.is_alphanumeric() || c == ' ' || c == '_')
+        && (!value.is_empty() || TITLE_KEYS.iter().any(|k| k.eq_ignore_ascii_case(key)));
+
+    valid.then_some((key, value))
+
+}
+//
+//
+/// Text of a scene heading, '.' forces it, a scene number like '#12#' is removed
+fn forced_heading(line: &str, after_blank: bool) -> Option<String> {
+    //
+    if !after_blank {
+        return None;
+    }
+    //
+    let heading = match line.strip_prefix('.') {
+
+        Some(forced) if !forced.starts_with('.') => forced,
+        _ if is_heading(line) => line,
+        _ => return None
+
+    };
+    //
+    let heading = match (heading.trim_end().strip_suffix('#'), heading.rfind(" #")) {
+
+        (Some(_), Some(start)) => &heading[..start],
+        _ => heading
+
+    };
+    //
+    Some(heading.trim().to_string())
+    //
+}
+//
+//
+/// Name and extension of a character cue, a line in capitals followed by the dialogue or a
+/// name forced with '@'
+fn character_cue(line: &str, after_blank: bool, next_blank: bool) -> Option<(String, Option<String>)> {
+    //
+    if !after_blank || next_blank {
+        return None;
+    }
+    //
+    // '^' marks a dual dialogue, read as a normal one
+    let line = line.trim_end_matches('^').trim();
+
+    let (forced, line) = match line.strip_prefix('@') {
+
+        Some(name) => (true, name.trim()),
+        None => (false, line)
+
+    };
+    //
+    let (name, extension) = match (line.find('('), line.ends_with(')')) {
+
+        (Some(start), true) => (line[..start].trim(), Some(line[start + 1..line.len() - 1].trim().to_string())),
+        _ => (line, None)
+
+    };
+    //
+    let shouted = name.chars().any(|c| c.is_alphabetic()) && !name.chars().any(|c| c.is_lowercase());
+
+    match !name.is_empty() && (forced || shouted) {
+
+        true => Some((name.to_string(), extension)),
+        false => None
+
+    }
+    //
+}
+//
+//
+/// Check if an action would be read as another element and needs a '!'
+fn needs_forced_action(text: &str) -> bool {
+
+    text.starts_with(['.', '!', '@', '#', '=', '>', '~', '['])
+        || is_heading(text)
+        || (text.chars().any(|c| c.is_alphabetic()) && !text.chars().any(|c| c.is_lowercase()))
+
+}
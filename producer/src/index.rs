@@ -0,0 +1,466 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Vector index
+//
+// The scenes of the past episodes, the entries of the bible and the continuity facts are kept
+// with their embedding in a file of the series. A prompt can then be given the few passages
+// closest to what it talks about instead of the whole history. The index is small enough to be
+// searched by comparing the query to every entry.
+//
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use error_stack::{IntoReport, Result, ResultExt};
+use serde::{Deserialize, Serialize};
+
+use crate::continuity::FactStore;
+use crate::episode::Episode;
+use crate::script::ScriptLine;
+use crate::series::Series;
+use crate::{cdebug, EGeneral, EmbeddingClient, EMBEDDING_MODEL};
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use std::sync::Mutex;
+
+    use error_stack::Result;
+    use futures::executor::block_on;
+
+    use crate::series::{Character, Series};
+    use crate::{EGeneral, EmbeddingClient};
+    use super::{bible_documents, cosine, episode_documents, Document, Source, VectorIndex};
+
+    /// embed a text as the number of times it contains 'coffee', 'moon' and 'toaster'
+    #[derive(Default)]
+    struct WordClient { texts: Mutex<Vec<String>> }
+
+    impl EmbeddingClient for WordClient {
+
+        async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, EGeneral> {
+
+            self.texts.lock().unwrap().extend(texts.iter().cloned());
+
+            Ok(texts.iter()
+                .map(|t| ["coffee", "moon", "toaster"].iter().map(|w| t.to_lowercase().matches(w).count() as f32).collect())
+                .collect())
+
+        }
+
+    }
+
+    fn document(key: &str, text: &str) -> Document {
+
+        Document { source: Source::Script, key: key.to_string(), text: text.to_string() }
+
+    }
+
+    #[test]
+    fn cosine_similarity() {
+
+        assert!((cosine(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine(&[1.0, 0.0], &[0.0, 3.0]).abs() < 1e-6);
+        assert_eq!(cosine(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine(&[1.0], &[1.0, 0.0]), 0.0);
+
+    }
+
+    #[test]
+    fn closest_entries_are_found() {
+
+        let client = WordClient::default();
+        let mut index = VectorIndex::default();
+
+        let mut documents = vec![
+            document("e1/s1", "No coffee on the moon"),
+            document("e1/s2", "The toaster explodes"),
+            document("e2/s1", "Coffee coffee coffee"),
+        ];
+        documents.push(Document { source: Source::Fact, ..document("fact", "The toaster is broken") });
+
+        block_on(index.add(&client, documents.clone())).unwrap();
+
+        let found = block_on(index.query(&client, "more coffee", 2, &[])).unwrap();
+        let keys: Vec<&str> = found.iter().map(|(_, e)| e.key.as_str()).collect();
+
+        assert_eq!(keys, ["e2/s1", "e1/s1"]);
+
+        let found = block_on(index.query(&client, "toaster", 5, &[Source::Fact])).unwrap();
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1.to_string(), "The toaster is broken");
+
+        // nothing similar at all is not given
+        assert!(block_on(index.query(&client, "nothing", 5, &[])).unwrap().is_empty());
+
+    }
+
+    #[test]
+    fn only_changed_documents_are_embedded() {
+
+        let client = WordClient::default();
+        let mut index = VectorIndex::default();
+
+        let mut series = Series::new("The Night Shift", "Two robots run a diner on the moon");
+        series.characters = vec![Character::new("Bolt")];
+
+        block_on(index.add(&client, bible_documents(&series))).unwrap();
+
+        series.characters[0].personality = "Grumpy".to_string();
+
+        block_on(index.add(&client, bible_documents(&series))).unwrap();
+        block_on(index.add(&client, episode_documents(&crate::timeline::test::episode()))).unwrap();
+
+        let texts = client.texts.lock().unwrap();
+
+        assert_eq!(texts.len(), 5);
+        assert_eq!(texts[2], "Bolt: Grumpy");
+        assert!(texts[3].starts_with("Episode 0, act 1 scene 1\nBOLT: one two"));
+        assert!(!texts[3].contains("not played"));
+        assert_eq!(index.entries.len(), 4);
+
+        block_on(index.replace(&client, "episode/0/", vec![])).unwrap();
+
+        assert_eq!(index.entries.len(), 2);
+
+        let path = std::env::temp_dir().join(format!("producer-index-{}.json", std::process::id()));
+
+        index.save(&path).unwrap();
+        let loaded = VectorIndex::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, index);
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Documents
+//
+/// file of the index in the directory of a series
+pub const INDEX_FILE: &str = "index.json";
+//
+// texts sent in a single request
+const BATCH: usize = 64;
+//
+//
+/// Where a text of the index comes from
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+
+    Script,
+    Bible,
+    Fact,
+
+}
+//
+//
+/// A text to index, a document with the same key replaces it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Document {
+
+    pub source: Source,
+    pub key:    String,
+    pub text:   String,
+
+}
+//
+//
+/// The entries of the bible: the premise, the setting, each character and the running gags
+pub fn bible_documents(series: &Series) -> Vec<Document> {
+    //
+    let mut documents = vec![
+        ("premise".to_string(), format!("{}: {}", series.title, series.premise)),
+        ("setting".to_string(), series.setting.clone()),
+        ("running_gags".to_string(), series.running_gags.join("\n")),
+    ];
+    //
+    for character in &series.characters {
+
+        let mut text = character.name.clone();
+
+        if !character.personality.is_empty() {
+            text = format!("{}: {}", text, character.personality);
+        }
+
+        for (name, what) in &character.relationships {
+            text = format!("{}\n{}: {}", text, name, what);
+        }
+
+        documents.push((format!("character/{}", character.name.to_lowercase()), text));
+
+    }
+    //
+    documents.into_iter()
+        .filter(|(_, text)| !text.trim().is_empty())
+        .map(|(key, text)| Document { source: Source::Bible, key: format!("bible/{}", key), text })
+        .collect()
+    //
+}
+//
+//
+/// The scenes of an episode with their lines, the notes are left out
+pub fn episode_documents(episode: &Episode) -> Vec<Document> {
+
+    episode.acts.iter().enumerate()
+        .flat_map(|(a, act)| act.scenes.iter().enumerate().map(move |(s, scene)| (a, s, scene)))
+        .map(|(a, s, scene)| {
+
+            let lines = scene.beats.iter()
+                .flat_map(|b| &b.lines)
+                .filter(|l| !matches!(l, ScriptLine::Note { .. }))
+                .map(|l| l.to_string());
+
+            let title = format!("Episode {}, act {} scene {}", episode.number, a + 1, s + 1);
+            let summary = [scene.heading.as_str(), scene.summary.as_str()].iter()
+                .filter(|t| !t.is_empty())
+                .copied()
+                .collect::<Vec<_>>()
+                .join(": ");
+
+            let text = std::iter::once(title)
+                .chain((!summary.is_empty()).then_some(summary))
+                .chain(lines)
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            Document { source: Source::Script, key: format!("episode/{}/{}/{}", episode.number, a + 1, s + 1), text }
+
+        })
+        .collect()
+
+}
+//
+//
+/// The continuity facts, one document per subject and attribute
+pub fn fact_documents(store: &FactStore) -> Vec<Document> {
+
+    store.facts.iter()
+        .map(|f| Document {
+            source: Source::Fact,
+            key:    format!("fact/{}/{}", f.subject.to_lowercase(), f.attribute.to_lowercase()),
+            text:   f.to_string()
+        })
+        .collect()
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Index
+//
+/// A text and its embedding
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Entry {
+
+    pub source: Source,
+    pub key:    String,
+    pub text:   String,
+    pub vector: Vec<f32>,
+
+}
+//
+impl Display for Entry {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { write!(f, "{}", self.text) }
+
+}
+//
+//
+/// The embedded texts of a series
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct VectorIndex {
+
+    /// the vectors of different models can't be compared
+    pub model:      String,
+    pub entries:    Vec<Entry>,
+
+}
+//
+impl Default for VectorIndex {
+
+    fn default() -> Self { Self { model: EMBEDDING_MODEL.to_string(), entries: Vec::new() } }
+
+}
+//
+impl VectorIndex {
+    //
+    /// Load the index of a series, empty if the file does not exist yet or was built with
+    /// another model
+    pub fn load(path: &Path) -> Result<Self, EGeneral> {
+        //
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        //
+        let content = std::fs::read_to_string(path)
+            .into_report()
+            .change_context(EGeneral::Index)
+            .attach_printable_lazy(|| format!("unable to read '{}'", path.display()))?;
+
+        let index: Self = serde_json::from_str(&content)
+            .into_report()
+            .change_context(EGeneral::Index)
+            .attach_printable_lazy(|| format!("in '{}'", path.display()))?;
+        //
+        if index.model != EMBEDDING_MODEL {
+
+            cdebug!(category: "api", "the index was built with '{}', it is built again", index.model);
+            return Ok(Self::default());
+
+        }
+        //
+        Ok(index)
+        //
+    }
+    //
+    //
+    /// Save the index of a series
+    pub fn save(&self, path: &Path) -> Result<(), EGeneral> {
+
+        let content = serde_json::to_string(self).into_report().change_context(EGeneral::Index)?;
+
+        std::fs::write(path, content)
+            .into_report()
+            .change_context(EGeneral::Index)
+            .attach_printable_lazy(|| format!("unable to write '{}'", path.display()))
+
+    }
+    //
+    //
+    /// Embed and add documents, the ones already indexed with the same text are not sent again
+    ///
+    /// # Arguments
+    ///
+    /// * 'client'    - what embeds the texts
+    /// * 'documents' - the texts to index
+    ///
+    pub async fn add<C: EmbeddingClient>(&mut self, client: &C, documents: Vec<Document>) -> Result<(), EGeneral> {
+        //
+        let changed: Vec<Document> = documents.into_iter()
+            .filter(|d| !self.entries.iter().any(|e| e.key == d.key && e.text == d.text))
+            .collect();
+        //
+        for batch in changed.chunks(BATCH) {
+
+            let vectors = client.embed(batch.iter().map(|d| d.text.clone()).collect()).await
+                .change_context(EGeneral::Index)?;
+
+            for (document, vector) in batch.iter().zip(vectors) {
+
+                let entry = Entry { source: document.source, key: document.key.clone(), text: document.text.clone(), vector };
+
+                match self.entries.iter().position(|e| e.key == entry.key) {
+
+                    Some(i) => self.entries[i] = entry,
+                    None => self.entries.push(entry)
+
+                }
+
+            }
+
+        }
+        //
+        cdebug!(category: "api", "{} texts embedded, {} in the index", changed.len(), self.entries.len());
+
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Replace every entry whose key starts with a prefix, like the scenes of an episode
+    ///
+    /// # Arguments
+    ///
+    /// * 'client'    - what embeds the texts
+    /// * 'prefix'    - the start of the keys of the documents
+    /// * 'documents' - the texts to index, the other entries with the prefix are removed
+    ///
+    pub async fn replace<C: EmbeddingClient>(&mut self, client: &C, prefix: &str, documents: Vec<Document>) -> Result<(), EGeneral> {
+
+        self.entries.retain(|e| !e.key.starts_with(prefix) || documents.iter().any(|d| d.key == e.key));
+
+        self.add(client, documents).await
+
+    }
+    //
+    //
+    /// The entries closest to a vector, the most similar first
+    ///
+    /// # Arguments
+    ///
+    /// * 'vector'  - the embedding of the query
+    /// * 'limit'   - the maximum number of entries
+    /// * 'sources' - the kinds of entries to search, all of them if empty
+    ///
+    pub fn search(&self, vector: &[f32], limit: usize, sources: &[Source]) -> Vec<(f32, &Entry)> {
+        //
+        let mut found: Vec<(f32, &Entry)> = self.entries.iter()
+            .filter(|e| sources.is_empty() || sources.contains(&e.source))
+            .map(|e| (cosine(vector, &e.vector), e))
+            .filter(|(score, _)| *score > 0.0)
+            .collect();
+
+        found.sort_by(|a, b| b.0.total_cmp(&a.0));
+        found.truncate(limit);
+
+        found
+        //
+    }
+    //
+    //
+    /// Embed a text and give the entries closest to it
+    ///
+    /// # Arguments
+    ///
+    /// * 'client'  - what embeds the text
+    /// * 'text'    - what the prompt talks about
+    /// * 'limit'   - the maximum number of entries
+    /// * 'sources' - the kinds of entries to search, all of them if empty
+    ///
+    pub async fn query<C: EmbeddingClient>(&self, client: &C, text: &str, limit: usize, sources: &[Source]) -> Result<Vec<(f32, &Entry)>, EGeneral> {
+        //
+        if self.entries.is_empty() {
+            return Ok(Vec::new());
+        }
+        //
+        let vector = client.embed(vec![text.to_string()]).await
+            .change_context(EGeneral::Index)?
+            .pop()
+            .ok_or_else(|| EGeneral::Index.as_report().attach_printable("no embedding received for the query"))?;
+
+        Ok(self.search(&vector, limit, sources))
+        //
+    }
+    //
+}
+//
+//
+/// Cosine similarity of two vectors, 0 when they can't be compared
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    //
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    //
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    match norm > 0.0 {
+
+        true => dot / norm,
+        false => 0.0
+
+    }
+    //
+}
@@ -1,7 +1,25 @@
 extern crate core;
 
 mod openai_call;
-mod logger;
+pub mod agent;
+#[cfg(feature = "tts")]
+pub mod audio;
+pub mod continuity;
+pub mod episode;
+pub mod fountain;
+pub mod index;
+pub mod logger;
+pub mod playback;
+pub mod project;
+pub mod script;
+pub mod series;
+pub mod subtitle;
+pub mod template;
+pub mod timeline;
+
+pub use openai_call::{
+    estimate_tokens, CompletionClient, Connection, EmbeddingClient, ModelType, PromptRequestInfo, EMBEDDING_MODEL
+};
 
 
 
@@ -12,7 +30,6 @@ mod logger;
 //
 #[cfg(test)]
 mod tests {
-    use super::*;
 
 }
 //
@@ -26,7 +43,20 @@ use error_stack::{Context,Report};
 #[derive(Debug,Copy, Clone)]
 pub enum EGeneral {
 
-    LogSys
+    LogSys,
+    LogFilter,
+    Model,
+    Series,
+    Template,
+    Api,
+    Episode,
+    Script,
+    Fountain,
+    Agent,
+    Continuity,
+    Index,
+    Project,
+    Audio
 
 
 }
@@ -38,6 +68,19 @@ impl std::fmt::Display for EGeneral {
         match self {
 
             Self::LogSys =>     write!(f, "Log System Error"),
+            Self::LogFilter =>  write!(f, "Invalid Log Filter"),
+            Self::Model =>      write!(f, "Invalid Model"),
+            Self::Series =>     write!(f, "Invalid Series Bible"),
+            Self::Template =>   write!(f, "Template Error"),
+            Self::Api =>        write!(f, "Api Error"),
+            Self::Episode =>    write!(f, "Episode Generation Error"),
+            Self::Script =>     write!(f, "Script Parsing Error"),
+            Self::Fountain =>   write!(f, "Invalid Fountain Script"),
+            Self::Agent =>      write!(f, "Character Agent Error"),
+            Self::Continuity => write!(f, "Continuity Error"),
+            Self::Index =>      write!(f, "Vector Index Error"),
+            Self::Project =>    write!(f, "Project Store Error"),
+            Self::Audio =>      write!(f, "Audio Error"),
 
         }
 
@@ -4,13 +4,36 @@
 
 
 
-use std::sync::{Mutex, MutexGuard};
+mod capture;
+mod category;
+mod context;
+mod filter;
+mod metrics;
+mod panic;
+mod sink;
+mod timestamp;
+mod worker;
+mod wrap;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, TryLockError};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{Duration,Instant};
 
-use colored::{Colorize,ColoredString};
-use error_stack::{Result, ResultExt};
+use chrono::{DateTime, Utc};
+use colored::{Color,Colorize,ColoredString};
+use error_stack::Result;
 
 use super::EGeneral;
+use filter::Filter;
+
+pub use metrics::{log_stats,reset_log_stats,set_rate_limit,CallSiteStats,LogStats,RateLimit};
+pub use panic::{fmt_report,install_panic_hook};
+pub use capture::{capture_logs,LogCapture};
+pub use category::{register_category,set_category_enabled};
+pub use context::{enter_context,with_context,ContextGuard,InContext,InContextExt};
+pub use sink::{Sink,StdoutSink};
+pub use timestamp::{Clock,Precision,TimeMode,Timestamp};
 
 #[cfg(debug_assertions)]
 pub(crate) const RELEASE:u8 = 0;
@@ -18,38 +41,20 @@ pub(crate) const RELEASE:u8 = 0;
 #[cfg(not(debug_assertions))]
 pub(crate) const RELEASE:u8 = 1;
 
-pub const FHOUR_AS_SECONDS: f32 =   3600.0;
-pub const FMIN_AS_SECONDS:  f32 =   60.0;
-
 // ------------------------------------------------------------------------------------------------
 // Clock information
 //
 //
 lazy_static::lazy_static! {
 
-    static ref APPS_CLOCK:  Mutex<Instant> = Mutex::new(Instant::now());
+    // never modified so it can be read without locking from every thread
+    static ref APPS_CLOCK:  Instant = Instant::now();
 
 }
 
 
 /// Return the elapsed time since the program started
-pub fn get_prog_elapsed_time() -> Duration {
-    //
-    match APPS_CLOCK.lock() {
-
-        Ok(clock) => clock.elapsed(),
-        Err(e) => {
-
-            eprintln!("unable to access the engine's clock because: {}", e.to_string());
-            eprintln!("a duration value of 0 will be returned");
-
-            Duration::new(0, 0)
-
-        }
-
-    }
-    //
-}
+pub fn get_prog_elapsed_time() -> Duration { APPS_CLOCK.elapsed() }
 //
 //
 //
@@ -62,25 +67,318 @@ pub fn get_prog_elapsed_time() -> Duration {
 #[cfg(test)]
 mod test {
 
-    use super::{init,CDEBUGS};
+    use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use super::{init,log_enabled,set_module_level,capture_logs,Level,LogCapture};
+    use super::{log_stats,set_rate_limit,RateLimit};
+    use super::{add_sink,flush,install_panic_hook,start_async,Log,Sink};
+    use super::{LogQueue,LogSystem,Metadata,Record,LOG_MAX_QUEUE_SIZE,LOG_SYSTEM};
+
+    /// the tests changing the state of the whole log system run one at a time
+    static GLOBAL_STATE: Mutex<()> = Mutex::new(());
+
+    fn global_state() -> MutexGuard<'static, ()> { GLOBAL_STATE.lock().unwrap_or_else(PoisonError::into_inner) }
+
+    /// count the log entries that contain a marker
+    struct CountSink { marker: &'static str, count: Arc<AtomicUsize> }
+
+    impl Sink for CountSink {
+
+        fn write(&mut self, log: &Log) {
+
+            if log.record().message.contains(self.marker) {
+                self.count.fetch_add(1, Ordering::SeqCst);
+            }
+
+        }
+
+    }
+
+    /// keep the message of every log entry
+    struct MessageSink(Arc<Mutex<Vec<String>>>);
+
+    impl Sink for MessageSink {
+
+        fn write(&mut self, log: &Log) { self.0.lock().unwrap().push(log.record().message.clone()); }
+
+    }
 
     #[test]
     fn logs_with_argument() {
 
         init();
 
-        CDEBUGS("Test with one value {}" ,&[&1.to_string()]);
-        CDEBUGS("Test with two value {} {}",&[&1.to_string(),&2.to_string()]);
-        CDEBUGS("Test with three value {} {} {}",&[&1.to_string(),&2.to_string(),&3.to_string()]);
+        let capture = LogCapture::start();
+
+        crate::cdebug!("Test with one value {}", 1);
+        crate::cdebug!("Test with two value {} {}", 1, 2);
+        crate::cdebug!("Test with three value {} {} {}", 1, 2, 3);
+
+        capture.assert_contains(Level::DEBUG, "Test with one value 1");
+        capture.assert_contains(Level::DEBUG, "Test with two value 1 2");
+        capture.assert_contains(Level::DEBUG, "Test with three value 1 2 3");
+
+    }
+
+    #[test]
+    fn logs_with_debug_and_named_argument() {
+
+        init();
+
+        let scene = "Café à l'aube";
+
+        let (_, records) = capture_logs(|| {
+
+            crate::cdebug!("Test with a debug value {:?}", vec!["ça", "va"]);
+            crate::cdebug!("Test with a named value {scene} and {n}", n = 2);
+
+        });
+
+        assert_eq!(records[0].message, r#"Test with a debug value ["ça", "va"]"#);
+        assert_eq!(records[1].message, "Test with a named value Café à l'aube and 2");
+        assert_eq!(records[1].meta.target, module_path!());
+
+    }
+
+    #[test]
+    fn change_filter_at_runtime() {
+
+        init();
+
+        set_module_level("producer::logger::test::runtime", Level::WARN);
+
+        assert!(!log_enabled(Level::INFO, "producer::logger::test::runtime", None));
+        assert!(log_enabled(Level::WARN, "producer::logger::test::runtime", None));
+        assert!(log_enabled(Level::ERROR, "producer::logger::test::runtime::inner", None));
+
+    }
+
+    #[test]
+    fn async_logs_are_written_on_flush() {
+
+        let count = Arc::new(AtomicUsize::new(0));
+
+        add_sink(Box::new(CountSink { marker: "[async-marker]", count: count.clone() }));
+
+        let _guard = start_async(64).unwrap();
+
+        let handles: Vec<_> = (0..4).map(|t| std::thread::spawn(move || {
+
+            for i in 0..10 {
+                crate::cerror!("[async-marker] thread {} entry {}", t, i);
+            }
+
+        })).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        flush();
+
+        assert_eq!(count.load(Ordering::SeqCst), 40);
+
+    }
+
+    #[test]
+    fn logs_before_init_are_replayed() {
+
+        let count = Arc::new(AtomicUsize::new(0));
+
+        let mut sys = LogSystem {
+            queue:  LogQueue::new(),
+            init:   false,
+            early:  Vec::new(),
+            sinks:  vec![Box::new(CountSink { marker: "early", count: count.clone() })],
+            repeat: None,
+            last:   None
+        };
+
+        let meta = Metadata { level: Level::INFO, target: module_path!(), file: file!(), line: line!(), category: None };
+
+        for _ in 0..LOG_MAX_QUEUE_SIZE + 10 {
+            sys.push_log(Record::new(meta, "early".to_string()));
+        }
+
+        assert!(sys.is_init());
+        assert_eq!(sys.queue.content.len(), LOG_MAX_QUEUE_SIZE);
+        assert_eq!(count.load(Ordering::SeqCst), LOG_MAX_QUEUE_SIZE + 10);
+
+        // a few logs are only written when the log system is flushed
+        sys.init = false;
+
+        for _ in 0..3 {
+            sys.push_log(Record::new(meta, "early".to_string()));
+        }
+
+        assert_eq!(count.load(Ordering::SeqCst), LOG_MAX_QUEUE_SIZE + 10);
+
+        sys.flush();
+
+        assert_eq!(count.load(Ordering::SeqCst), LOG_MAX_QUEUE_SIZE + 13);
+
+    }
+
+    #[test]
+    fn repeated_messages_are_collapsed() {
+
+        let messages = Arc::new(Mutex::new(Vec::new()));
+
+        let mut sys = LogSystem {
+            queue:  LogQueue::new(),
+            init:   true,
+            early:  Vec::new(),
+            sinks:  vec![Box::new(MessageSink(messages.clone()))],
+            repeat: Some(Duration::from_secs(60)),
+            last:   None
+        };
+
+        let meta = Metadata { level: Level::WARN, target: module_path!(), file: file!(), line: line!(), category: None };
+
+        for _ in 0..58 {
+            sys.push_log(Record::new(meta, "invalid temperature".to_string()));
+        }
+
+        sys.push_log(Record::new(meta, "invalid top_p".to_string()));
+        sys.push_log(Record::new(meta, "invalid top_p".to_string()));
+        sys.flush();
+
+        assert_eq!(*messages.lock().unwrap(), [
+            "invalid temperature",
+            "last message repeated 57 times",
+            "invalid top_p",
+            "last message repeated 1 times"
+        ]);
+
+    }
+
+    #[test]
+    fn flood_is_rate_limited_and_counted() {
+
+        let _global = global_state();
+
+        let count = Arc::new(AtomicUsize::new(0));
+
+        add_sink(Box::new(CountSink { marker: "[flood-marker]", count: count.clone() }));
+
+        set_rate_limit(Some(RateLimit::default()));
+
+        let site = line!() + 3;
+
+        for i in 0..RateLimit::default().max * 3 {
+            crate::cwarn!("[flood-marker] invalid value {}", i);
+        }
+
+        flush();
+
+        let stats = log_stats();
+        let site = stats.call_site(file!(), site).unwrap();
+
+        assert_eq!(site.count, RateLimit::default().max * 3);
+        assert!(site.suppressed > 0);
+        assert!(stats.level(Level::WARN) >= site.count);
+        assert_eq!(count.load(Ordering::SeqCst) + site.suppressed, site.count);
+
+        set_rate_limit(None);
 
     }
 
     #[test]
-    fn log_with_to_much_arg() {
+    fn long_unicode_message_is_wrapped() {
+
+        let meta = Metadata { level: Level::INFO, target: module_path!(), file: file!(), line: line!(), category: None };
+
+        let line = "Le héros répète « ça ne finira jamais » ";
+        let log = Log::new(Record::new(meta, line.repeat(10)));
+
+        let content = log.as_string();
+        let lines: Vec<&str> = content.lines().collect();
+
+        assert!(lines.len() > 1);
+        // the header is '[hh:mm:ss.mmm] [INFO]: '
+        assert!(lines[1..].iter().all(|l| l.starts_with(&" ".repeat(24)) && !l.starts_with(&" ".repeat(25))));
+
+    }
+
+    #[test]
+    fn category_is_shown_after_the_level() {
+
+        let (_, records) = capture_logs(|| crate::cinfo!(category: "audio", "mixing scene {}", 2));
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].meta.category, Some("audio"));
+
+        let content = Log::new(records[0].clone()).as_string();
+
+        assert!(content.contains("[INFO]: ") && content.contains("[AUDIO]:") && content.contains("mixing scene 2"));
+
+    }
+
+    #[test]
+    fn colored_category_keeps_the_indent() {
+
+        let meta = Metadata { level: Level::INFO, target: module_path!(), file: file!(), line: line!(), category: Some("audio") };
+
+        colored::control::set_override(true);
+
+        let content = Log::new(Record::new(meta, "mixing the beds of scene two ".repeat(10))).as_string();
+
+        colored::control::unset_override();
+
+        let lines: Vec<&str> = content.lines().collect();
+
+        assert!(lines.len() > 1 && lines[0].contains('\x1b'));
+        // the header is '[hh:mm:ss.mmm] [INFO]: [AUDIO]: '
+        assert!(lines[1..].iter().all(|l| l.starts_with(&" ".repeat(33)) && !l.starts_with(&" ".repeat(34))));
+
+    }
+
+    #[test]
+    fn panic_is_logged_as_fatal() {
+
+        let _global = global_state();
+
+        let count = Arc::new(AtomicUsize::new(0));
+
+        add_sink(Box::new(CountSink { marker: "[panic-marker]", count: count.clone() }));
+
+        // the hook is global, the one of the test harness still runs and is put back at the end
+        let harness: Arc<dyn Fn(&std::panic::PanicHookInfo) + Send + Sync> = std::panic::take_hook().into();
+        let chained = harness.clone();
+
+        std::panic::set_hook(Box::new(move |info| chained(info)));
+
+        install_panic_hook();
+
+        let _ = std::thread::spawn(|| panic!("[panic-marker] the show must go on")).join();
+
+        std::panic::set_hook(Box::new(move |info| harness(info)));
+
+        flush();
+
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+    }
+
+    #[test]
+    fn recover_from_poisoned_log_system() {
+
+        let _global = global_state();
+
+        let _ = std::thread::spawn(|| {
+
+            let _sys = LOG_SYSTEM.lock();
+            panic!("poison the log system");
+
+        }).join();
+
+        crate::cerror!("still able to log after a poisoned mutex");
 
         init();
 
-        CDEBUGS("1 {} 2 {} and 3",&["test","test","One more"]);
+        assert!(!LOG_SYSTEM.is_poisoned());
 
     }
 
@@ -97,29 +395,59 @@ lazy_static::lazy_static! {
         LogSystem {
             queue:      LogQueue::new(),
             init:       false,
-            debug_log:  true,
-            info_log:   true,
-            warn_log:   true,
-            trace_log:  true,
-            vulkan:     true
+            early:      Vec::new(),
+            sinks:      vec![Box::new(StdoutSink::default())],
+            repeat:     None,
+            last:       None
         }
     );
     //
+    // kept outside of the LOG_SYSTEM so checking a level never wait for a log being written,
+    // created from the environment the first time a level is checked
+    static ref FILTER: RwLock<Filter> = {
+
+        let filter = env_filter();
+
+        MAX_LEVEL.store(filter.max_level() as usize, Ordering::Relaxed);
+
+        RwLock::new(filter)
+
+    };
+    //
 }
 //
+// columns of the message before it is wrapped, 0 to use the width of the terminal
+static LINE_WIDTH: AtomicUsize = AtomicUsize::new(0);
+//
+// most verbose level allowed by the filter, let the log macros skip a disabled level
+// without locking the FILTER
+static MAX_LEVEL: AtomicUsize = AtomicUsize::new(Level::TRACE as usize);
+//
 // ------------------------------------------------------------------------------------------------
 // Constant
 //
+/// environment variable read at initialization to set the log filter
+/// ex: SHOW_LOG="producer::openai_call=trace,warn"
+pub const LOG_FILTER_ENV:       &str        = "SHOW_LOG";
+/// default number of records that can wait for the log worker
+pub const LOG_CHANNEL_SIZE:     usize       = 1024;
+/// usual window given to `set_repeat_window`, the messages are never collapsed by default
+pub const REPEAT_WINDOW:        Duration    = Duration::from_secs(10);
+// how long a panic wait for the log worker to write the pending records
+const       PANIC_FLUSH_TIMEOUT:Duration    = Duration::from_secs(1);
+// how many records are kept before the initialization, the log system initialize itself
+// with the default sinks when it is full or flushed
+const       EARLY_BUFFER_SIZE:  usize       = 256;
 const       LOG_BUFFER_SIZE:    usize       = 20000;
 const       LOG_MAX_QUEUE_SIZE: usize       = 300;
 const       MAX_LINE_LEN:       usize       = 100;
 const       CFAILURE:           u8          = 0;
 const       CSUCCESS:           u8          = 1;
-const       LEVEL_STRING:       [&str;7]    = [
-    "[FATAL]:","[ERROR]:","[WARN]: ","[INFO]: ", "[DEBUG]:","[TRACE]:","[VLK]:  "
+const       LEVEL_STRING:       [&str;6]    = [
+    "[FATAL]:","[ERROR]:","[WARN]: ","[INFO]: ", "[DEBUG]:","[TRACE]:"
 ];
-// tab jump for if a log have multiple lines, they start all at the same position
-const TAB_MESSAGE: &str = "\n                       "; // 23 columns of whitespace
+// the terminal width is ignored if it leave less columns than this for the message
+const MIN_LINE_LEN:         usize       = 20;
 //
 //
 // ------------------------------------------------------------------------------------------------
@@ -131,178 +459,474 @@ struct LogSystem {
 
     queue:      LogQueue,
     init:       bool,
-    debug_log:  bool,
-    info_log:   bool,
-    warn_log:   bool,
-    trace_log:  bool,
-    vulkan:     bool
+    // records emitted before the initialization, replayed once the sinks are configured
+    early:      Vec<Record>,
+    sinks:      Vec<Box<dyn Sink>>,
+    // how long an identical message is collapsed, none to write every message
+    repeat:     Option<Duration>,
+    // last message written and how many times it has been repeated since
+    last:       Option<(Record, usize)>
 
 }
 //
 impl LogSystem {
     //
-    /// Initialize the log subsystem
+    /// Initialize the log subsystem and write the records emitted before
     fn initialize(&mut self) {
         //
-        // they are all enable by default
+        if self.init {
+            return;
+        }
+        //
+        self.init = true;
         //
-        // check if in release mode and if so disable debug and trace logging
-        if RELEASE == 1{
+        for record in std::mem::take(&mut self.early) {
 
-            self.debug_log = false;
-            self.info_log  = false;
+            self.write(record);
 
         }
         //
-        self.init = true;
-        // add the initialize log
-        //CTRACE("Start the log subsystem");
+    }
+    //
+    //
+    /// Count a log entry, then add it to the end of the LOG_QUEUE and write it to every sink
+    ///
+    /// A log entry over the rate limit of its call site is only counted
+    ///
+    /// # Parameters
+    ///
+    /// * record - the log entry with its formatted message
+    ///
+    fn push_log(&mut self,record: Record) {
+        //
+        match metrics::check(&record.meta, record.elapsed) {
+
+            metrics::Verdict::Write => (),
+
+            metrics::Verdict::Suppress => return,
+
+            metrics::Verdict::Resume(suppressed) => {
+
+                let meta = Metadata { level: Level::WARN, ..record.meta };
 
+                self.keep(Record {
+                    elapsed: record.elapsed,
+                    ..Record::new(meta, format!(
+                        "{} log entries from {}:{} were suppressed by the rate limit",
+                        suppressed, meta.file, meta.line
+                    ))
+                });
+
+            }
+
+        }
         //
+        self.keep(record);
         //
     }
     //
-    //
-    /// Add a log entry to the end of the LOG_QUEUE
+    /// Add a log entry to the end of the LOG_QUEUE and write it to every sink
+    ///
+    /// Before the initialization the log entry is kept until the sinks are configured
     ///
     /// # Parameters
     ///
-    /// * log - a log entry to be added to the queue
+    /// * record - the log entry with its formatted message
     ///
-    fn push_log(&mut self,level: Level,msg: &str) {
+    fn keep(&mut self,record: Record) {
         //
-        // check to make sure that the log subsystem is initialized
         if !self.is_init(){
-            // TODO: found a better solution because the log subsystem
-            //       should not make the application crash
-            panic!("try to add a log but the log subsystem is not initialized");
-            //
+
+            self.early.push(record);
+
+            // nobody initialized the log system, use the default sinks instead of losing logs
+            if self.early.len() >= EARLY_BUFFER_SIZE {
+                self.initialize();
+            }
+
+            return;
+
         }
         //
-        // check if the level is enabled and then pass it to the queue
-        match level {
-            //
-            Level::DEBUG => {
+        self.write(record);
+        //
+    }
+    //
+    /// Format a log entry, write it to every sink and add it to the LOG_QUEUE
+    ///
+    /// # Parameters
+    ///
+    /// * record - the log entry with its formatted message
+    ///
+    /// The same message repeated right after is only counted until another message is
+    /// written or the repeat window is over
+    ///
+    fn write(&mut self,record: Record) {
+        //
+        if let (Some(window), Some((last, repeated))) = (self.repeat, &mut self.last) {
+
+            if is_repeat(last, &record) && record.elapsed.saturating_sub(last.elapsed) < window {
 
-                if self.debug_log {
+                *repeated += 1;
+                return;
 
-                    self.queue.push(Log::new(level, msg.green()));
+            }
+
+        }
+        //
+        self.write_repeated();
+
+        self.last = Some((record.clone(), 0));
+
+        self.write_log(Log::new(record));
+        //
+    }
+    //
+    /// Write how many times the last message has been repeated since it was written
+    fn write_repeated(&mut self) {
+        //
+        let (last, repeated) = match &mut self.last {
+
+            Some((last, repeated)) if *repeated > 0 => (last, std::mem::take(repeated)),
+            _ => return
+
+        };
+        //
+        let mut record = Record::new(last.meta, format!("last message repeated {} times", repeated));
+        record.context = last.context.clone();
+
+        self.write_log(Log::new(record));
+        //
+    }
+    //
+    /// Write a formatted log entry to every sink and add it to the LOG_QUEUE
+    fn write_log(&mut self, log: Log) {
+        //
+        for sink in self.sinks.iter_mut() {
+
+            sink.write(&log);
+
+        }
+        //
+        self.queue.push(log);
+        //
+    }
+    //
+    /// Flush every sink, the records kept before the initialization are written first
+    fn flush(&mut self) {
+
+        self.initialize();
+
+        self.write_repeated();
+
+        for sink in self.sinks.iter_mut() {
+
+            sink.flush();
+
+        }
+
+    }
+    //
+    /// Check if the sub system logging have been initialize
+    fn is_init(&self) -> bool { self.init }
+    //
+    //
+}
+//
+/// Initialize the log system
+///
+/// The logs emitted before are kept and written once this is called, so the sinks
+/// should be added before. Logging without calling it is still allowed, the logs kept
+/// are then written when there are too many of them, or by `flush` and `shutdown`.
+pub fn init() {
+
+    get_access_mutex().initialize();
+
+}
+//
+//
+/// Collapse the identical messages written one after the other, off by default
+///
+/// # Arguments
+///
+/// * 'window' - how long after the first one a message is collapsed, none to write every message
+///
+pub fn set_repeat_window(window: Option<Duration>) {
+
+    let mut sys = get_access_mutex();
+
+    sys.write_repeated();
+    sys.repeat = window;
+
+}
+//
+//
+/// Check if a record is the same message emitted from the same place as the previous one
+fn is_repeat(previous: &Record, record: &Record) -> bool {
+
+    previous.meta.file == record.meta.file
+        && previous.meta.line == record.meta.line
+        && previous.meta.level == record.meta.level
+        && previous.message == record.message
+        && previous.context == record.context
+
+}
+//
+//
+/// Default filter when none is given, debug and info logs are disabled in release mode
+fn default_filter() -> Filter {
+
+    if RELEASE == 1 { Filter::new(Level::WARN) } else { Filter::new(Level::TRACE) }
+
+}
+//
+//
+/// Filter from the environment variable, the default one if it is not set or invalid
+fn env_filter() -> Filter {
+
+    match std::env::var(LOG_FILTER_ENV) {
+
+        Ok(spec) => Filter::parse(&spec).unwrap_or_else(|e| {
+
+            eprintln!("invalid {} value, the default log filter will be used: {:?}", LOG_FILTER_ENV, e);
+            default_filter()
+
+        }),
+
+        Err(_) => default_filter()
+
+    }
+
+}
+//
+//
+/// Replace the log filter with a new spec, can be called while the show is running
+///
+/// # Arguments
+///
+/// * 'spec' - comma separated list of directives like `producer::openai_call=trace,warn`
+///
+pub fn set_filter(spec: &str) -> Result<(),EGeneral> {
+
+    replace_filter(Filter::parse(spec)?);
+
+    Ok(())
+
+}
+//
+//
+/// Change the most verbose level allowed for the modules without override
+///
+/// # Arguments
+///
+/// * 'level' - the most verbose level to keep
+///
+pub fn set_max_level(level: Level) {
+
+    let mut filter = get_access_filter().clone();
+    filter.set_max_level(level);
+
+    replace_filter(filter);
+
+}
+//
+//
+/// Change the most verbose level allowed for a module and its children
+///
+/// # Arguments
+///
+/// * 'module' - module path like `producer::openai_call`
+/// * 'level'  - the most verbose level to keep
+///
+pub fn set_module_level(module: &str, level: Level) {
+
+    let mut filter = get_access_filter().clone();
+    filter.set_module(module, level);
+
+    replace_filter(filter);
+
+}
+//
+//
+/// Replace the log filter and update the most verbose level allowed
+fn replace_filter(filter: Filter) {
+
+    // a panic while the filter was written can't leave it half replaced, so the poison is ignored
+    let mut current = FILTER.write().unwrap_or_else(PoisonError::into_inner);
+
+    MAX_LEVEL.store(filter.max_level() as usize, Ordering::Relaxed);
+
+    *current = filter;
+
+}
+//
+//
+/// remove boilerplate code for reading the log filter
+fn get_access_filter() -> RwLockReadGuard<'static,Filter> {
+
+    FILTER.read().unwrap_or_else(PoisonError::into_inner)
+
+}
+//
+//
+/// Replace every destination of the log entries, including the default standard output
+///
+/// # Arguments
+///
+/// * 'sinks' - where the log entries will be written
+///
+pub fn set_sinks(sinks: Vec<Box<dyn Sink>>) {
+
+    get_access_mutex().sinks = sinks;
+
+}
+//
+//
+/// Add a destination for the log entries
+///
+/// # Arguments
+///
+/// * 'sink' - where the log entries will be written
+///
+pub fn add_sink(sink: Box<dyn Sink>) {
 
-                }
+    get_access_mutex().sinks.push(sink);
 
-            },
-            //
-            Level::INFO => {
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Asynchronous mode
+//
+//
+/// Stop the asynchronous mode when dropped, keep it alive until the end of the show
+#[must_use = "the asynchronous logging stop when the guard is dropped"]
+pub struct AsyncGuard;
+//
+impl Drop for AsyncGuard {
 
-                if self.info_log {
+    fn drop(&mut self) { shutdown(); }
 
-                    self.queue.push(Log::new(level, msg.blue()));
+}
+//
+//
+/// Write the logs from a background thread, the log macros then only send the record
+/// through a bounded channel
+///
+/// When the channel is full the least important records are dropped, Fatal and Error
+/// records wait for a place instead. The pending records are written on shutdown, and when
+/// the program panic once `install_panic_hook` has been called.
+///
+/// # Arguments
+///
+/// * 'capacity' - how many records can wait for the worker
+///
+pub fn start_async(capacity: usize) -> Result<AsyncGuard,EGeneral> {
+    //
+    init();
 
-                }
+    worker::start(capacity)?;
+    //
+    Ok(AsyncGuard)
+    //
+}
+//
+//
+/// Wait until every pending record is written and flush the sinks
+pub fn flush() {
 
-            },
-            //
-            Level::TRACE => {
+    worker::flush(None);
 
-                if self.trace_log {
+    // the current thread could already hold the LOG_SYSTEM if it panicked while writing
+    match LOG_SYSTEM.try_lock() {
 
-                    self.queue.push(Log::new(level, msg.magenta()));
+        Ok(mut sys) => sys.flush(),
+        Err(TryLockError::Poisoned(e)) => { LOG_SYSTEM.clear_poison(); e.into_inner().flush() },
+        Err(TryLockError::WouldBlock) => ()
 
-                }
+    }
 
-            },
-            //
-            Level::WARN => {
+}
+//
+//
+/// Write the pending records without waiting forever, the panicking thread could be the one
+/// holding the LOG_SYSTEM
+fn flush_on_panic() {
 
-                if self.warn_log {
+    worker::flush(Some(PANIC_FLUSH_TIMEOUT));
 
-                    self.queue.push(Log::new(level,msg.yellow()));
+    if let Ok(mut sys) = LOG_SYSTEM.try_lock() {
 
-                }
+        sys.flush();
 
-            },
-            //
-            // Fatal and Error types are not allowed to be disabled so no need to be checked
-            _ => self.queue.push(Log::new(level,msg.red()))
-            //
-            //
-        }
-        //
-        self.print();
-        //
     }
-    //
-    /// wrapper for the macro println! but only for the
-    /// log message this function will probably be unable to be used by default
-    fn print(&self) {
-        //
-        println!("{}", match self.queue.content.last(){
-
-            Some(v) => v.as_string(),
-            None => "".to_string(),
 
-        });
-        //
-    }
-    //
-    /// Check if the sub system logging have been initialize
-    fn is_init(&self) -> bool { self.init }
-    //
-    //
-    /// Change the status of the Info log type
-    pub(crate) fn set_info(&mut self,value:bool) { self.info_log = value; }
-    //
-    /// Change the status of the Trace log type
-    pub(crate) fn set_trace(&mut self,value:bool) { self.trace_log = value; }
-    //
-    //
 }
 //
-impl Drop for LogSystem {
+//
+/// Stop the asynchronous mode after every pending record is written, the logs are then
+/// written synchronously
+///
+/// The logs kept before the initialization are written too, it should be called before the
+/// program exits
+pub fn shutdown() {
 
-    fn drop(&mut self) {
+    worker::stop();
 
-        let mutx = get_access_mutex().change_context(EGeneral::LogSys)
-            .attach_printable("Cant drop the log system mutex")
-            .unwrap();
+    flush();
 
-        drop(mutx)
+}
+//
+//
+//
+/// Change the number of columns of a message before it is wrapped on the next line
+///
+/// # Arguments
+///
+/// * 'width' - number of columns, None to follow the width of the terminal
+///
+pub fn set_line_width(width: Option<usize>) {
 
-    }
+    LINE_WIDTH.store(width.unwrap_or(0), Ordering::Relaxed);
 
 }
 //
 //
-/// Initialize the log system
-pub fn init() -> Result<(),EGeneral> {
-    //
+/// Columns available for the message, the width of the terminal if none has been set
+///
+/// # Arguments
+///
+/// * 'header_width' - columns taken by the time and the level before the message
+///
+fn message_width(header_width: usize) -> usize {
 
-    get_access_mutex().change_context(EGeneral::LogSys)
-        .attach_printable("Can't initialize the Log System")?
-        .initialize();
+    match LINE_WIDTH.load(Ordering::Relaxed) {
 
-    Ok(())
+        0 => wrap::terminal_width()
+            .map(|w| w.saturating_sub(header_width))
+            .filter(|w| *w >= MIN_LINE_LEN)
+            .unwrap_or(MAX_LINE_LEN),
+
+        width => width
+
+    }
 
 }
 //
 //
 /// remove boilerplate code for accessing the log system
-fn get_access_mutex() -> Result<MutexGuard<'static,LogSystem>,EGeneral> {
+///
+/// A thread that panicked while writing a log poison the mutex, the log system is still
+/// usable after that so the poison is cleared instead of losing every following logs
+fn get_access_mutex() -> MutexGuard<'static,LogSystem> {
 
-    match LOG_SYSTEM.lock() {
+    LOG_SYSTEM.lock().unwrap_or_else(|e| {
 
-        Ok(sys) => Ok(sys),
-        Err(e) => return Err(
-            EGeneral::LogSys
-                .as_report()
-                .attach_printable(
-                    format!(
-                        "Can't access LOG_SYSTEM caused by {}",
-                        e.to_string()
-                    )
-                )
-        )
-    }
+        LOG_SYSTEM.clear_poison();
+
+        e.into_inner()
+
+    })
 
 }
 //
@@ -313,102 +937,105 @@ fn get_access_mutex() -> Result<MutexGuard<'static,LogSystem>,EGeneral> {
 //
 /// Parsing log entry to a string
 ///
+/// The lines of a long message all start at the same column, after the header
+///
 /// # Parameters
 ///
-/// * level - the type of log
-/// * message - what the log says
+/// * record - the log entry
+/// * timestamp - how the time of the log is shown
 ///
-fn fmt_log(level: Level, message: String) -> String{
+fn fmt_log(record: &Record, timestamp: &Timestamp) -> String{
     //
     // level represent the index in the CLEVEL_STRING
-    // " {TIME} {TYPE} {MESSAGE}"
-    let msg = format!(
-        "{} {} {}",
-        fmt_duration_log(get_prog_elapsed_time()),
-        LEVEL_STRING[level as usize],
-        message
-    );
+    // "{TIME} {TYPE} {CATEGORY} {MESSAGE}", the category column is only there if the entry has one
+    let (category, color) = match record.meta.category {
 
-    msg
-    //
-}
-//
-//
-/// Format the duration entry to a string
-///
-/// # Parameters
-///
-/// * dur - Duration since Engine initialized
-///
-fn fmt_duration_log(dur:Duration) -> String {
+        Some(name) => category::category_label(name),
+        None => (String::new(), Color::White)
 
-    let mut secs = dur.as_secs_f32();
-    let mut min:u32 = 0;
-    let mut hour:u32 = 0;
+    };
 
-    if secs >= FHOUR_AS_SECONDS {
+    let header = format!("{} {} ", timestamp.format(record), LEVEL_STRING[record.meta.level as usize]);
 
-        hour = (secs / FHOUR_AS_SECONDS) as u32;
-        secs -= FHOUR_AS_SECONDS*hour as f32;
+    // measured without the escape codes of the color, they take no column
+    let header_width = match category.is_empty() {
 
-    }
+        true => wrap::width_of(&header),
+        false => wrap::width_of(&header) + wrap::width_of(&category) + 1
 
-    if secs >= FMIN_AS_SECONDS {
+    };
 
-        min = (secs / FMIN_AS_SECONDS) as u32;
-        secs -= FMIN_AS_SECONDS*min as f32;
+    let header = match category.is_empty() {
 
-    }
+        true => header,
+        false => format!("{}{} ", header, category.color(color))
+
+    };
+    //
+    // tab jump for if a log have multiple lines
+    let tab_message = format!("\n{}", " ".repeat(header_width));
 
-    let millis_sec = ((secs - (secs as u32) as f32 ) * 100_f32) as u32;
+    let lines = match &record.context {
 
-    let sec = secs as u32;
+        Some(context) => wrap::wrap(&format!("[{}] {}", context, record.message), message_width(header_width)),
+        None => wrap::wrap(&record.message, message_width(header_width))
 
-    let f_hour =    format_single_digit_value(hour);
-    let f_min =     format_single_digit_value(min);
-    let f_secs =    format_single_digit_value(sec);
-    let f_millis =  format_single_digit_value(millis_sec);
+    };
 
-    format!("[{}:{}:{}:{}]",f_hour,f_min,f_secs,f_millis)
+    let mut msg = format!(
+        "{}{}",
+        header,
+        colored_message(record.meta.level, &lines.join(&tab_message))
+    );
+    //
+    // check if the len of the message is bigger than the max allowed
+    wrap::truncate_bytes(&mut msg, LOG_BUFFER_SIZE);
 
+    msg
+    //
 }
 //
 //
-/// parse an unsigned integer to a string and if it is a single digit number, it add a zero
-/// before it
+/// Color the message depending on the level of the log
 ///
-/// # Arguments
+/// # Parameters
 ///
-/// * 'value' - a unsigned integer to parse into a string
+/// * level - the type of log
+/// * message - what the log says
 ///
-fn format_single_digit_value(value: u32) -> String {
-    //
-    if value < 9 {
-        return format!("0{}",value);
+fn colored_message(level: Level, message: &str) -> ColoredString {
+
+    match level {
+
+        Level::DEBUG => message.green(),
+        Level::INFO  => message.blue(),
+        Level::TRACE => message.magenta(),
+        Level::WARN  => message.yellow(),
+        _ => message.red()
+
     }
 
-    format!("{}",value)
-    //
 }
 //
+//
 // ------------------------------------------------------------------------------------------------
 // Log Struct
 //
 /// Vector that store every log entry
-struct LogQueue { content: Vec<Log> }
+struct LogQueue { content: VecDeque<Log> }
 //
 impl LogQueue {
     //
     /// initialize the queue
     fn new() -> Self {
         //
-        let q :Vec<Log> = Vec::with_capacity(LOG_MAX_QUEUE_SIZE);
+        let q :VecDeque<Log> = VecDeque::with_capacity(LOG_MAX_QUEUE_SIZE);
 
         LogQueue{ content: q}
         //
     }
     //
-    /// add a log entry to the end queue
+    /// add a log entry to the end queue, the oldest entry is removed when it is full
     ///
     /// # Arguments
     ///
@@ -416,15 +1043,13 @@ impl LogQueue {
     ///
     fn push(&mut self,log:Log) {
         //
-        if self.content.len() + 1 == LOG_MAX_QUEUE_SIZE {
+        if self.content.len() == LOG_MAX_QUEUE_SIZE {
 
-            //TODO: implement something for remedy this situation
-            //      I just dont have idea for now
-            unimplemented!();
+            self.content.pop_front();
 
         }
 
-        self.content.push(log);
+        self.content.push_back(log);
         //
     }
     //
@@ -432,8 +1057,47 @@ impl LogQueue {
 }
 //
 //
-/// Store a log entry
-struct Log{ level: Level, content:String }
+/// A log entry as it has been emitted, before being formatted
+#[derive(Clone, Debug)]
+pub struct Record {
+
+    pub meta:       Metadata,
+    pub message:    String,
+    /// time since the program started
+    pub elapsed:    Duration,
+    /// wall clock time
+    pub time:       DateTime<Utc>,
+    /// path of the logging context active when the log has been emitted
+    pub context:    Option<Arc<str>>,
+
+}
+//
+impl Record {
+    //
+    /// Create a record emitted now
+    ///
+    /// # Arguments
+    ///
+    /// * 'meta'    - type of log entry and where it comes from
+    /// * 'message' - the formatted message
+    ///
+    pub fn new(meta: Metadata, message: String) -> Self {
+
+        Self {
+            meta,
+            message,
+            elapsed:    get_prog_elapsed_time(),
+            time:       Utc::now(),
+            context:    context::current_path()
+        }
+
+    }
+    //
+}
+//
+//
+/// Store a formatted log entry
+pub struct Log{ record: Record, content:String }
 //
 impl Log{
     //
@@ -441,67 +1105,38 @@ impl Log{
     ///
     /// # Arguments
     ///
-    /// * 'level'   - type of log entry
-    /// * 'message' - colored message that the log entry should show
+    /// * 'record' - the log entry to format
     ///
-    fn new(level:Level, message:ColoredString) -> Self {
+    fn new(record: Record) -> Self {
         //
-        // format version message
-        #[allow(unused_assignments)]
-            let mut fmt_msg = String::new();
-        //
-        if message.len() > MAX_LINE_LEN {
-            //
-            // get the message color and if none set default one (white)
-            let msg_color = match message.fgcolor() {
-
-                Some(color) => color,
-                None => colored::Color::White
-
-            };
-            //
-            // how many lines the message should have
-            let nb_lines = message.len() as f32 / MAX_LINE_LEN as f32;
-            //
-            // will store each line
-            let mut lines: Vec<String> = Vec::new();
-            //
-            //
-            for line in 0..nb_lines.ceil() as i32 {
-                //
-                // store the 100 range of characters depending on the value of 'line'
-                lines.push(message[line as usize*MAX_LINE_LEN..].to_string());
-                //
-            }
-            // align the multiple lines together
-            fmt_msg = lines.join(TAB_MESSAGE).color(msg_color).to_string();
-            //
-            //
-        } else{
+        let content = fmt_log(&record, &Timestamp::default());
 
-            fmt_msg = message.to_string();
-
-        }
-        //
-        //
-        // add the header to the message
-        let mut msg = fmt_log(level,fmt_msg);
+        Log{ record, content }
         //
-        // check if the len of the message is bigger than the max allowed
-        if msg.len() > LOG_BUFFER_SIZE - 1 {
-
-            msg = msg[..LOG_BUFFER_SIZE].to_string();
+    }
+    //
+    /// format the log entry with another timestamp than the default one
+    ///
+    /// # Arguments
+    ///
+    /// * 'timestamp' - how the time of the log is shown
+    ///
+    pub fn format(&self, timestamp: &Timestamp) -> String {
 
+        if *timestamp == Timestamp::default() {
+            return self.content.to_string();
         }
 
-        Log{ level: level, content: msg}
-        //
-        //
+        fmt_log(&self.record, timestamp)
+
     }
     //
     /// return the log as a string
     pub fn as_string(&self) -> String { self.content.to_string() }
     //
+    /// the log entry before being formatted
+    pub fn record(&self) -> &Record { &self.record }
+    //
     //
 }
 //
@@ -510,7 +1145,8 @@ impl Log{
 // Level enum
 //
 #[repr(usize)]
-#[derive(PartialEq,Clone, Copy)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+#[allow(clippy::upper_case_acronyms)]
 /// represents the index of the type of log in the const LEVEL_STRING
 pub enum Level{
 
@@ -520,298 +1156,248 @@ pub enum Level{
     INFO  = 3,
     DEBUG = 4,
     TRACE = 5,
-    VLK   = 6,
 
 }
 //
 //
 // ------------------------------------------------------------------------------------------------
-// Log call functions
+// Log call macros
+//
 //
+/// Information captured at the call site of a log macro
+#[derive(Clone, Copy, Debug)]
+pub struct Metadata {
+
+    pub level:  Level,
+    pub target: &'static str,
+    pub file:   &'static str,
+    pub line:   u32,
+    /// subsystem of the log entry, see `register_category`
+    pub category: Option<&'static str>,
+
+}
 //
 //
-/// Slice the given string where brackets couple are founded
+/// Check if a log entry of the given level would be kept by the log subsystem
+///
+/// Used by the log macros so that the message is never formatted when the level is disabled
 ///
 /// # Arguments
 ///
-/// * 'msg' - the message to be slice
+/// * 'level'    - the type of log entry
+/// * 'target'   - the module path where the log entry is emitted
+/// * 'category' - the subsystem of the log entry if it has one
 ///
-fn slice_brackets_str(msg:&str) -> (Vec<String>,usize){
-
-    let mut slices:Vec<String> = Vec::new();
-
-    // iterator index through all the characters of the message
-    let mut f_index:usize = 0;
-    // iterator over how many {} founded
-    let mut founding:usize = 0;
-
-    for (index,c) in msg.chars().enumerate() {
-
-        // if we found this '{}' we slice the message there
-        if c == '{' && msg.chars().nth(index + 1) == Some('}') {
-
-            let slice = &msg[f_index .. index];
-
-            // add the left side of the {} founded
-            slices.push(slice.to_string());
-            // add {} to be able to replaced them with arguments
-            slices.push("{}".to_string());
-
-            // then we jump over {}
-            f_index = index + 2;
-
-            founding += 1;
-
-
-        }
+pub fn log_enabled(level: Level, target: &str, category: Option<&str>) -> bool {
+    //
+    // a test capturing the logs want every level
+    if capture::is_active() {
+        return true;
     }
-    // get the last slice and add it with the others
-    let slice_end = &msg[f_index .. msg.chars().count()];
-
-    slices.push(slice_end.to_string());
     //
-    (slices,founding)
+    if level as usize > MAX_LEVEL.load(Ordering::Relaxed) {
+        return false;
+    }
     //
+    if let Some(name) = category {
+
+        if !category::category_enabled(level, name) {
+            return false;
+        }
+
+    }
     //
+    get_access_filter().enabled(level, target)
+
 }
 //
 //
-/// validate that the string passed is a valid string with
-/// valid number of arguments
+/// Entry point of the log macros, should not be called directly
 ///
 /// # Arguments
 ///
-/// * 'msg' - the string message to be validated
-/// * 'args' - the arguments to be validated and passed to the message
-fn validate_msg(msg: &str,args:&[&str]) -> String {
-    //
-    let (msg_sliced,nb_brackets) = slice_brackets_str(&msg);
-    //
-    // validate that the number of arguments passed are the same as the number of
-    // couple brackets
-    if args.len() > nb_brackets {
+/// * 'meta' - where the log entry has been emitted
+/// * 'args' - the already checked format arguments of the message
+///
+#[doc(hidden)]
+pub fn __private_log(meta: Metadata, args: std::fmt::Arguments) {
 
-        panic!(
-            "the message:[ {} ] contains {} format bracket(s) but you have passed {} arguments ",
-            msg,
-            nb_brackets,
-            args.len()
-        );
+    dispatch(Record::new(meta, args.to_string()));
 
-    }
+}
+//
+//
+/// Send a record to the worker or write it from the current thread
+fn dispatch(record: Record) {
     //
-    // replace each bracket by the arguments
-    let mut iter_brk:usize = 0;
-    let mut f_msg = String::new();
-
-    for slice in msg_sliced.iter() {
-
-        if slice != &"{}" {
+    if capture::is_active() {
 
-            f_msg = format!("{}{}",f_msg,slice);
+        capture::capture(&record);
 
+        // the level can be enabled only for the capture
+        if !get_access_filter().enabled(record.meta.level, record.meta.target) {
+            return;
         }
-        else if args.len() > iter_brk as usize {
 
-            f_msg = format!("{}{}",f_msg,args[iter_brk]);
+        if let Some(name) = record.meta.category {
+
+            if !category::category_enabled(record.meta.level, name) {
+                return;
+            }
 
-            iter_brk += 1;
         }
 
     }
     //
-    f_msg
-    //
+    emit(record);
     //
 }
 //
 //
-/// Fatal log with no arguments
-pub fn CFATAL(msg:&str) {
-
-    match get_access_mutex() {
+/// Give a record to the worker, write it from the current thread if there is none
+fn emit(record: Record) {
 
-        Ok(mut sys) => sys.push_log(Level::FATAL, msg),
+    // the worker give back the record when the logs are written synchronously
+    if let Some(record) = worker::send(record) {
 
-        Err(e) => eprintln!("{}",e.to_string())
+        write_record(record);
 
     }
 
 }
 //
-/// Fatal log with arguments
-pub fn CFATALS(msg:&str,args:&[&str]) {
-
-    match get_access_mutex() {
-
-        Ok(mut sys) => {
+//
+/// Write a record to the sinks from the current thread
+fn write_record(record: Record) { get_access_mutex().push_log(record); }
+//
+//
+/// Write a record from a thread that could already hold the LOG_SYSTEM, like a panicking one
+///
+/// Give back the record if the LOG_SYSTEM is still locked after the timeout
+///
+/// # Arguments
+///
+/// * 'record'  - the log entry to be written
+/// * 'timeout' - how long to wait for the LOG_SYSTEM
+///
+#[allow(clippy::result_large_err)]
+fn try_write_record(record: Record, timeout: Duration) -> std::result::Result<(),Record> {
+    //
+    let start = Instant::now();
+    //
+    loop {
 
-            let v = validate_msg(msg, args);
+        match LOG_SYSTEM.try_lock() {
 
-            sys.push_log(Level::FATAL,&v);
+            Ok(mut sys) => { sys.push_log(record); return Ok(()) },
 
-        },
+            Err(TryLockError::Poisoned(e)) => {
 
-        Err(e) => eprintln!("{}",e.to_string())
+                LOG_SYSTEM.clear_poison();
+                e.into_inner().push_log(record);
+                return Ok(());
 
-    }
+            },
 
-}
-//
-/// Error log with no arguments
-pub fn CERROR(msg:&str) {
+            Err(TryLockError::WouldBlock) if start.elapsed() < timeout => {
 
-    match get_access_mutex() {
+                std::thread::sleep(Duration::from_millis(1));
 
-        Ok(mut sys) => sys.push_log(Level::ERROR, msg),
+            },
 
-        Err(e) => eprintln!("{}",e.to_string())
+            Err(TryLockError::WouldBlock) => return Err(record)
 
+        }
 
     }
+    //
 }
 //
-/// Error log with arguments
-pub fn CERRORS(msg:&str,args:&[&str]) {
-
-    match get_access_mutex() {
-
-        Ok(mut sys) => {
-
-            let v = validate_msg(msg, args);
+//
+/// Flush the sinks from the current thread
+fn flush_sinks() { get_access_mutex().flush(); }
+//
+//
+/// Log a message at the given level with the `format!` syntax
+///
+/// The message is only formatted if the level and the category are enabled, the category is
+/// given first like `clog!(Level::INFO, category: "api", "sent {}", id)`
+#[macro_export]
+macro_rules! clog {
 
-            sys.push_log(Level::ERROR,&v);
+    (@log $level:expr, $category:expr, $($arg:tt)+) => {{
 
-        },
+        let level = $level;
+        let category: ::core::option::Option<&'static str> = $category;
 
-        Err(e) => eprintln!("{}",e.to_string())
+        if $crate::logger::log_enabled(level, module_path!(), category) {
 
-    }
+            $crate::logger::__private_log(
+                $crate::logger::Metadata {
+                    level,
+                    target: module_path!(),
+                    file:   file!(),
+                    line:   line!(),
+                    category,
+                },
+                format_args!($($arg)+)
+            );
 
-}
-//
-/// Warn log with no arguments
-pub fn CWARN(msg:&str) {
+        }
 
-    match get_access_mutex() {
+    }};
 
-        Ok(mut sys) => sys.push_log(Level::WARN, msg),
+    ($level:expr, category: $category:expr, $($arg:tt)+) => {
+        $crate::clog!(@log $level, ::core::option::Option::Some($category), $($arg)+)
+    };
 
-        Err(e) => { eprintln!("{}",e.to_string()) }
+    ($level:expr, $($arg:tt)+) => {
+        $crate::clog!(@log $level, ::core::option::Option::None, $($arg)+)
+    };
 
-    }
 }
 //
-/// Warn log with arguments
-pub fn CWARNS(msg:&str,args:&[&str]) {
-
-    match get_access_mutex() {
-
-        Ok(mut sys) => {
-
-            let v = validate_msg(msg, args);
-
-            sys.push_log(Level::WARN,&v);
-
-        },
-
-        Err(e) => eprintln!("{}",e.to_string())
-
-    }
-
+/// Fatal log
+#[macro_export]
+macro_rules! cfatal {
+    ($($arg:tt)+) => { $crate::clog!($crate::logger::Level::FATAL, $($arg)+) };
 }
 //
-/// Info log with no arguments
-pub fn CINFO(msg:&str) {
-
-    match get_access_mutex() {
-
-        Ok(mut sys) => sys.push_log(Level::INFO, msg),
-
-        Err(e) => eprintln!("{}",e.to_string())
-
-    }
-
+/// Error log
+#[macro_export]
+macro_rules! cerror {
+    ($($arg:tt)+) => { $crate::clog!($crate::logger::Level::ERROR, $($arg)+) };
 }
 //
-/// Info log with arguments
-pub fn CINFOS(msg:&str,args:&[&str]) {
-
-    match get_access_mutex() {
-
-        Ok(mut sys) => {
-
-            let v = validate_msg(msg, args);
-
-            sys.push_log(Level::INFO,&v);
-
-        },
-
-        Err(e) => eprintln!("{}",e.to_string())
-
-    }
+/// Warn log
+#[macro_export]
+macro_rules! cwarn {
+    ($($arg:tt)+) => { $crate::clog!($crate::logger::Level::WARN, $($arg)+) };
 }
 //
-/// Debug log with no arguments
-pub fn CDEBUG(msg:&str) {
-
-    match get_access_mutex() {
-
-        Ok(mut sys) => sys.push_log(Level::DEBUG, msg),
-
-        Err(e) => eprintln!("{}",e.to_string())
-
-    }
-
+/// Info log
+#[macro_export]
+macro_rules! cinfo {
+    ($($arg:tt)+) => { $crate::clog!($crate::logger::Level::INFO, $($arg)+) };
 }
 //
-/// Debug log with arguments
-pub fn CDEBUGS(msg:&str,args:&[&str]) {
-
-    match get_access_mutex() {
-
-        Ok(mut sys) => {
-
-            let v = validate_msg(msg, args);
-
-            sys.push_log(Level::DEBUG,&v);
-
-        },
-
-        Err(e) => eprintln!("{}",e.to_string())
-
-    }
-
+/// Debug log
+#[macro_export]
+macro_rules! cdebug {
+    ($($arg:tt)+) => { $crate::clog!($crate::logger::Level::DEBUG, $($arg)+) };
 }
 //
-/// Trace log with no arguments
-pub fn CTRACE(msg:&str) {
-
-    match get_access_mutex() {
-
-        Ok(mut sys) => sys.push_log(Level::TRACE, msg),
-
-        Err(e) => eprintln!("{}",e.to_string())
-
-    }
-
+/// Trace log
+#[macro_export]
+macro_rules! ctrace {
+    ($($arg:tt)+) => { $crate::clog!($crate::logger::Level::TRACE, $($arg)+) };
 }
 //
-/// Trace log with arguments
-pub fn CTRACES(msg:&str,args:&[&str]) {
-
-    match get_access_mutex() {
-
-        Ok(mut sys) => {
-
-            let v = validate_msg(msg, args);
-
-            sys.push_log(Level::TRACE,&v);
-
-        },
-
-        Err(e) => eprintln!("{}",e.to_string())
-
-    }
-
+/// Log an error_stack report with all its frames as one entry, at the Error level by default
+#[macro_export]
+macro_rules! creport {
+    ($report:expr) => { $crate::creport!($crate::logger::Level::ERROR, $report) };
+    ($level:expr, $report:expr) => {
+        $crate::clog!($level, "{}", $crate::logger::fmt_report(&$report))
+    };
 }
@@ -0,0 +1,247 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Log capture
+//
+// Keep in memory the records emitted by the current thread so a test can check them. The
+// capture is per thread, so the tests running in parallel never see the logs of each
+// other even if they share the LOG_SYSTEM. Every level is captured, whatever the filter.
+//
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use super::{Level, Record};
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use super::{capture_logs,LogCapture};
+    use crate::logger::Level;
+
+    #[test]
+    fn only_the_current_thread_is_captured() {
+
+        let capture = LogCapture::start();
+
+        crate::cinfo!("captured {}", 1);
+
+        std::thread::spawn(|| crate::cinfo!("not captured")).join().unwrap();
+
+        capture.assert_contains(Level::INFO, "captured 1");
+        capture.assert_not_contains(Level::INFO, "not captured");
+        assert_eq!(capture.count(Level::INFO), 1);
+
+    }
+
+    #[test]
+    fn nested_capture_keep_its_own_records() {
+
+        let outer = LogCapture::start();
+
+        let (_, inner) = capture_logs(|| crate::cwarn!("inner"));
+
+        crate::cwarn!("outer");
+
+        assert_eq!(inner.len(), 1);
+        assert_eq!(inner[0].message, "inner");
+
+        outer.assert_contains(Level::WARN, "outer");
+        outer.assert_not_contains(Level::WARN, "inner");
+
+    }
+
+    #[test]
+    fn disabled_levels_are_captured() {
+
+        crate::logger::set_module_level(module_path!(), Level::ERROR);
+
+        let (_, records) = capture_logs(|| crate::ctrace!("hidden from the sinks"));
+
+        assert_eq!(records.len(), 1);
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Capture
+//
+type Records = Arc<Mutex<Vec<Record>>>;
+//
+thread_local! {
+
+    // the innermost capture receive the records
+    static CAPTURES: RefCell<Vec<Records>> = const { RefCell::new(Vec::new()) };
+
+}
+//
+//
+/// Check if a capture is active on the current thread
+pub(crate) fn is_active() -> bool {
+
+    CAPTURES.try_with(|c| !c.borrow().is_empty()).unwrap_or(false)
+
+}
+//
+//
+/// Keep a copy of the record if a capture is active on the current thread
+pub(crate) fn capture(record: &Record) {
+
+    let _ = CAPTURES.try_with(|c| {
+
+        if let Some(records) = c.borrow().last() {
+
+            records.lock().unwrap_or_else(PoisonError::into_inner).push(record.clone());
+
+        }
+
+    });
+
+}
+//
+//
+/// Records emitted by the current thread while it is alive
+///
+/// The capture end when it is dropped, it can't be sent to another thread
+pub struct LogCapture {
+
+    records:    Records,
+    _not_send:  PhantomData<*const ()>
+
+}
+//
+impl LogCapture {
+    //
+    /// Start to capture the records emitted by the current thread
+    pub fn start() -> Self {
+        //
+        let records: Records = Arc::new(Mutex::new(Vec::new()));
+
+        CAPTURES.with(|c| c.borrow_mut().push(records.clone()));
+
+        Self { records, _not_send: PhantomData }
+        //
+    }
+    //
+    //
+    /// Copy of every record captured until now
+    pub fn records(&self) -> Vec<Record> {
+
+        self.records.lock().unwrap_or_else(PoisonError::into_inner).clone()
+
+    }
+    //
+    //
+    /// Remove the records captured until now
+    pub fn clear(&self) { self.records.lock().unwrap_or_else(PoisonError::into_inner).clear(); }
+    //
+    //
+    /// Number of records captured with the given level
+    pub fn count(&self, level: Level) -> usize {
+
+        self.records().iter().filter(|r| r.meta.level == level).count()
+
+    }
+    //
+    //
+    /// Check if a record with the given level contains the text in its message
+    ///
+    /// # Arguments
+    ///
+    /// * 'level' - the type of log entry
+    /// * 'text'  - text that should be found in the message
+    ///
+    pub fn contains(&self, level: Level, text: &str) -> bool {
+
+        self.records().iter().any(|r| r.meta.level == level && r.message.contains(text))
+
+    }
+    //
+    //
+    /// Panic if no record with the given level contains the text in its message
+    ///
+    /// # Arguments
+    ///
+    /// * 'level' - the type of log entry
+    /// * 'text'  - text that should be found in the message
+    ///
+    #[track_caller]
+    pub fn assert_contains(&self, level: Level, text: &str) {
+
+        if !self.contains(level, text) {
+
+            panic!("no {:?} log contains '{}', captured:\n{}", level, text, self.summary());
+
+        }
+
+    }
+    //
+    //
+    /// Panic if a record with the given level contains the text in its message
+    ///
+    /// # Arguments
+    ///
+    /// * 'level' - the type of log entry
+    /// * 'text'  - text that should not be found in the message
+    ///
+    #[track_caller]
+    pub fn assert_not_contains(&self, level: Level, text: &str) {
+
+        if self.contains(level, text) {
+
+            panic!("a {:?} log contains '{}', captured:\n{}", level, text, self.summary());
+
+        }
+
+    }
+    //
+    //
+    /// One line per record captured, used in the assertion messages
+    fn summary(&self) -> String {
+
+        self.records().iter()
+            .map(|r| format!("  {:?} {}: {}", r.meta.level, r.meta.target, r.message))
+            .collect::<Vec<String>>()
+            .join("\n")
+
+    }
+    //
+}
+//
+impl Drop for LogCapture {
+
+    fn drop(&mut self) {
+
+        let _ = CAPTURES.try_with(|c| {
+
+            c.borrow_mut().retain(|records| !Arc::ptr_eq(records, &self.records));
+
+        });
+
+    }
+
+}
+//
+//
+/// Run a function and return the records it emitted on the current thread
+///
+/// # Arguments
+///
+/// * 'f' - the function to run
+///
+pub fn capture_logs<R>(f: impl FnOnce() -> R) -> (R, Vec<Record>) {
+
+    let capture = LogCapture::start();
+
+    let result = f();
+
+    (result, capture.records())
+
+}
@@ -0,0 +1,200 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Log categories
+//
+// A category tag the log entries of a subsystem of the show (api, script, audio, ...). Each
+// category has its own label shown after the level, its own color and can be disabled
+// without touching the others. Fatal and Error entries are kept even if their category is
+// disabled.
+//
+use std::sync::{PoisonError, RwLock};
+
+use colored::Color;
+
+use super::Level;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use colored::Color;
+
+    use super::{category_enabled,category_label,register_category,set_category_enabled};
+    use crate::logger::Level;
+
+    #[test]
+    fn disabled_category_keep_errors() {
+
+        register_category("test-disabled", "TST", Color::Cyan);
+        set_category_enabled("test-disabled", false).unwrap();
+
+        assert!(!category_enabled(Level::INFO, "test-disabled"));
+        assert!(category_enabled(Level::ERROR, "test-disabled"));
+
+        set_category_enabled("test-disabled", true).unwrap();
+
+        assert!(category_enabled(Level::TRACE, "test-disabled"));
+
+    }
+
+    #[test]
+    fn unknown_category_use_its_name() {
+
+        assert!(category_enabled(Level::INFO, "test-unknown"));
+        assert!(category_label("test-unknown").0.contains("[TEST-UNKNOWN]:"));
+        assert!(set_category_enabled("test-unknown", false).is_err());
+
+    }
+
+    #[test]
+    fn label_is_padded_like_the_level() {
+
+        register_category("test-pad", "PAD", Color::Green);
+
+        assert_eq!(category_label("test-pad"), ("[PAD]:  ".to_string(), Color::Green));
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Category
+//
+/// A subsystem whose log entries can be shown and filtered on their own
+#[derive(Clone, Debug)]
+struct Category {
+
+    name:       String,
+    label:      String,
+    color:      Color,
+    enabled:    bool,
+
+}
+//
+lazy_static::lazy_static! {
+    //
+    // the subsystems of the show are registered by default
+    static ref CATEGORIES: RwLock<Vec<Category>> = RwLock::new(vec![
+        Category { name: "api".to_string(),    label: "API".to_string(),    color: Color::Cyan,          enabled: true },
+        Category { name: "script".to_string(), label: "SCRIPT".to_string(), color: Color::BrightWhite,   enabled: true },
+        Category { name: "audio".to_string(),  label: "AUDIO".to_string(),  color: Color::BrightGreen,   enabled: true },
+        Category { name: "stage".to_string(),  label: "STAGE".to_string(),  color: Color::BrightMagenta, enabled: true },
+    ]);
+    //
+}
+//
+// same width as the columns of LEVEL_STRING
+const LABEL_WIDTH: usize = 8;
+//
+//
+/// Add a category or replace the label and the color of an existing one, a new category
+/// is enabled
+///
+/// # Arguments
+///
+/// * 'name'  - name given to the log macros like `cinfo!(category: "api", ...)`
+/// * 'label' - text shown in the log entries
+/// * 'color' - color of the label
+///
+pub fn register_category(name: &str, label: &str, color: Color) {
+    //
+    let mut categories = CATEGORIES.write().unwrap_or_else(PoisonError::into_inner);
+
+    match categories.iter_mut().find(|c| c.name == name) {
+
+        Some(category) => {
+
+            category.label = label.to_string();
+            category.color = color;
+
+        },
+
+        None => categories.push(Category {
+            name:       name.to_string(),
+            label:      label.to_string(),
+            color,
+            enabled:    true
+        })
+
+    }
+    //
+}
+//
+//
+/// Show or hide the log entries of a registered category
+///
+/// # Arguments
+///
+/// * 'name'    - name of the category
+/// * 'enabled' - if the log entries are kept
+///
+pub fn set_category_enabled(name: &str, enabled: bool) -> error_stack::Result<(),crate::EGeneral> {
+    //
+    let mut categories = CATEGORIES.write().unwrap_or_else(PoisonError::into_inner);
+
+    match categories.iter_mut().find(|c| c.name == name) {
+
+        Some(category) => { category.enabled = enabled; Ok(()) },
+
+        None => Err(
+            crate::EGeneral::LogFilter
+                .as_report()
+                .attach_printable(format!("the log category '{}' is not registered", name))
+        )
+
+    }
+    //
+}
+//
+//
+/// Check if a log entry of the category is kept, an unknown category is always enabled
+///
+/// # Arguments
+///
+/// * 'level' - type of the log entry
+/// * 'name'  - name of the category
+///
+pub(crate) fn category_enabled(level: Level, name: &str) -> bool {
+    //
+    // Fatal and Error types are not allowed to be disabled
+    if level <= Level::ERROR {
+        return true;
+    }
+    //
+    CATEGORIES.read().unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .find(|c| c.name == name)
+        .map(|c| c.enabled)
+        .unwrap_or(true)
+    //
+}
+//
+//
+/// Label of a category padded to the width of the level column, and its color
+///
+/// The label is plain so its width can be measured, the color is applied when it is written
+///
+/// # Arguments
+///
+/// * 'name' - name of the category
+///
+pub(crate) fn category_label(name: &str) -> (String, Color) {
+    //
+    let categories = CATEGORIES.read().unwrap_or_else(PoisonError::into_inner);
+
+    let (label, color) = match categories.iter().find(|c| c.name == name) {
+
+        Some(category) => (category.label.clone(), category.color),
+        None => (name.to_uppercase(), Color::White)
+
+    };
+    //
+    (format!("{:<width$}", format!("[{}]:", label), width = LABEL_WIDTH), color)
+    //
+}
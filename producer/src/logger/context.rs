@@ -0,0 +1,266 @@
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Logging context
+//
+// A context is a list of fields (episode, scene, request, ...) that tag every log entry
+// emitted while it is active. Contexts can be nested, the entries then show the whole
+// path like '[episode=3 > scene=2]'. Entering and leaving a context is traced with the time
+// spent inside it.
+//
+// The active context is kept per thread, a future that moves between threads have to be
+// wrapped with 'in_context' so its context follows it at every poll.
+//
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use std::time::Instant;
+
+use super::{dispatch, log_enabled, Level, Metadata, Record};
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use super::{current_path,enter_context,with_context,InContextExt};
+
+    #[test]
+    fn context_is_nested() {
+
+        assert_eq!(current_path(), None);
+
+        with_context(&[("episode", 3)], || {
+
+            let _scene = enter_context(&[("scene", "2"), ("take", "b")]);
+
+            assert_eq!(current_path().as_deref(), Some("episode=3 > scene=2 take=b"));
+
+        });
+
+        assert_eq!(current_path(), None);
+
+    }
+
+    #[test]
+    fn context_follow_the_future() {
+
+        let future = async { current_path() }.in_context(&[("request", 7)]);
+
+        let path = std::thread::spawn(move || futures::executor::block_on(future)).join().unwrap();
+
+        assert_eq!(path.as_deref(), Some("request=7"));
+        assert_eq!(current_path(), None);
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Context
+//
+thread_local! {
+
+    // innermost context active on this thread
+    static CURRENT: RefCell<Option<Arc<Context>>> = const { RefCell::new(None) };
+
+}
+//
+//
+/// A set of fields tagging the log entries
+struct Context {
+
+    // fields of every parent context followed by the fields of this one
+    path:       Arc<str>,
+    started:    Instant,
+
+}
+//
+impl Context {
+    //
+    /// Create a context inside the one active on this thread
+    ///
+    /// # Arguments
+    ///
+    /// * 'fields' - name and value of each field
+    ///
+    fn new<T: Display>(fields: &[(&str, T)]) -> Arc<Self> {
+        //
+        let own = fields.iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let path = match current_path() {
+
+            Some(parent) => format!("{} > {}", parent, own),
+            None => own
+
+        };
+        //
+        Arc::new(Self { path: path.into(), started: Instant::now() })
+        //
+    }
+    //
+    //
+    /// Trace the entry or the exit of the context
+    fn trace(&self, message: String) {
+
+        let meta = Metadata { level: Level::TRACE, target: module_path!(), file: file!(), line: line!(), category: None };
+
+        if log_enabled(meta.level, meta.target, None) {
+
+            let mut record = Record::new(meta, message);
+            record.context = Some(self.path.clone());
+
+            dispatch(record);
+
+        }
+
+    }
+    //
+}
+//
+impl Drop for Context {
+
+    fn drop(&mut self) { self.trace(format!("exit after {:?}", self.started.elapsed())); }
+
+}
+//
+//
+/// Replace the context active on this thread, return the previous one
+fn swap_current(context: Option<Arc<Context>>) -> Option<Arc<Context>> {
+
+    CURRENT.with(|current| current.replace(context))
+
+}
+//
+//
+/// Path of the context active on this thread
+pub(crate) fn current_path() -> Option<Arc<str>> {
+
+    // the thread local can already be destroyed when a context is left at the end of a thread
+    CURRENT.try_with(|current| current.borrow().as_ref().map(|c| c.path.clone()))
+        .ok()
+        .flatten()
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Guard
+//
+/// Leave the context when dropped
+///
+/// The guard can't be sent to another thread, use `in_context` for a future
+#[must_use = "the context is left when the guard is dropped"]
+pub struct ContextGuard {
+
+    previous:   Option<Arc<Context>>,
+    // the context is kept per thread
+    _not_send:  PhantomData<*const ()>
+
+}
+//
+impl Drop for ContextGuard {
+
+    fn drop(&mut self) { swap_current(self.previous.take()); }
+
+}
+//
+//
+/// Enter a context on this thread until the guard is dropped
+///
+/// # Arguments
+///
+/// * 'fields' - name and value of each field like `&[("episode", id)]`
+///
+pub fn enter_context<T: Display>(fields: &[(&str, T)]) -> ContextGuard {
+    //
+    let context = Context::new(fields);
+
+    let previous = swap_current(Some(context.clone()));
+
+    context.trace("enter".to_string());
+
+    ContextGuard { previous, _not_send: PhantomData }
+    //
+}
+//
+//
+/// Run a function inside a context
+///
+/// # Arguments
+///
+/// * 'fields' - name and value of each field like `&[("episode", id)]`
+/// * 'f'      - the function to run
+///
+pub fn with_context<T: Display, R>(fields: &[(&str, T)], f: impl FnOnce() -> R) -> R {
+
+    let _guard = enter_context(fields);
+
+    f()
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Future
+//
+/// A future that run inside a context at every poll, even across `.await` points
+pub struct InContext<F> {
+
+    future:     Pin<Box<F>>,
+    context:    Arc<Context>
+
+}
+//
+impl<F: Future> Future for InContext<F> {
+
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+
+        let previous = swap_current(Some(self.context.clone()));
+
+        let poll = self.future.as_mut().poll(cx);
+
+        swap_current(previous);
+
+        poll
+
+    }
+
+}
+//
+//
+/// Run a future inside a context
+pub trait InContextExt: Future + Sized {
+    //
+    /// Wrap the future so every log emitted while it is polled is tagged with the context,
+    /// the context is created inside the one active when this is called
+    ///
+    /// # Arguments
+    ///
+    /// * 'fields' - name and value of each field like `&[("episode", id)]`
+    ///
+    fn in_context<T: Display>(self, fields: &[(&str, T)]) -> InContext<Self> {
+
+        let context = Context::new(fields);
+
+        context.trace("enter".to_string());
+
+        InContext { future: Box::pin(self), context }
+
+    }
+    //
+}
+//
+impl<F: Future> InContextExt for F {}
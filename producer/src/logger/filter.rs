@@ -0,0 +1,245 @@
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Log filter
+//
+// A filter spec is a list of directives separated by commas:
+//
+//      warn                            -> global max level
+//      producer::openai_call=trace     -> max level of a module and its children
+//      producer::openai_call           -> same as '=trace'
+//
+// ex: "producer::openai_call=trace,warn"
+//
+use error_stack::Result;
+
+use super::Level;
+use crate::EGeneral;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use super::{Filter,Level};
+
+    #[test]
+    fn parse_global_and_module_levels() {
+
+        let filter = Filter::parse("producer::openai_call=trace,warn").unwrap();
+
+        assert!(filter.enabled(Level::TRACE, "producer::openai_call"));
+        assert!(filter.enabled(Level::DEBUG, "producer::openai_call::inner"));
+        assert!(filter.enabled(Level::WARN, "producer::logger"));
+        assert!(!filter.enabled(Level::INFO, "producer::logger"));
+        assert!(!filter.enabled(Level::TRACE, "producer::openai_callx"));
+
+    }
+
+    #[test]
+    fn longest_module_wins() {
+
+        let filter = Filter::parse("producer=info,producer::logger=error,debug").unwrap();
+
+        assert!(filter.enabled(Level::INFO, "producer::openai_call"));
+        assert!(!filter.enabled(Level::WARN, "producer::logger"));
+        assert!(filter.enabled(Level::DEBUG, "show"));
+        assert_eq!(filter.max_level(), Level::DEBUG);
+
+    }
+
+    #[test]
+    fn error_and_fatal_cant_be_disabled() {
+
+        let filter = Filter::parse("producer=off").unwrap();
+
+        assert!(filter.enabled(Level::ERROR, "producer"));
+        assert!(filter.enabled(Level::FATAL, "producer"));
+        assert!(!filter.enabled(Level::WARN, "producer"));
+
+    }
+
+    #[test]
+    fn invalid_spec() {
+
+        assert!(Filter::parse("producer=loud").is_err());
+        assert!(Filter::parse("=info").is_err());
+        assert!(Filter::parse("a=info=debug").is_err());
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Filter
+//
+/// Decide which log entries are kept depending on their level and the module they come from
+#[derive(Clone, Debug)]
+pub(crate) struct Filter {
+
+    max_level:  Level,
+    // sorted from the longest module path to the shortest
+    modules:    Vec<(String,Level)>
+
+}
+//
+impl Filter {
+    //
+    /// Create a filter without any module override
+    ///
+    /// # Arguments
+    ///
+    /// * 'max_level' - the most verbose level allowed
+    ///
+    pub(crate) fn new(max_level: Level) -> Self { Self { max_level, modules: Vec::new() } }
+    //
+    //
+    /// Parse a filter spec like `producer::openai_call=trace,warn`
+    ///
+    /// # Arguments
+    ///
+    /// * 'spec' - comma separated list of directives
+    ///
+    pub(crate) fn parse(spec: &str) -> Result<Self,EGeneral> {
+        //
+        let mut filter = Self::new(Level::ERROR);
+        //
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+
+            let mut parts = directive.split('=');
+
+            let first = parts.next().unwrap_or_default().trim();
+            let second = parts.next().map(str::trim);
+
+            if parts.next().is_some() || first.is_empty() {
+
+                return Err(
+                    EGeneral::LogFilter
+                        .as_report()
+                        .attach_printable(format!("invalid directive '{directive}'"))
+                );
+
+            }
+
+            match second {
+
+                Some(level) => filter.set_module(first, parse_level(level)?),
+
+                None => match parse_level(first) {
+
+                    Ok(level) => filter.max_level = level,
+                    // a module without a level enable everything for this module
+                    Err(_) => filter.set_module(first, Level::TRACE)
+
+                }
+
+            }
+
+        }
+        //
+        Ok(filter)
+        //
+    }
+    //
+    //
+    /// Change the most verbose level allowed for the modules without override
+    pub(crate) fn set_max_level(&mut self, level: Level) { self.max_level = level; }
+    //
+    //
+    /// Add or replace the override of a module and its children
+    ///
+    /// # Arguments
+    ///
+    /// * 'module' - module path like `producer::openai_call`
+    /// * 'level'  - the most verbose level allowed for this module
+    ///
+    pub(crate) fn set_module(&mut self, module: &str, level: Level) {
+        //
+        match self.modules.iter_mut().find(|(m,_)| m == module) {
+
+            Some((_,l)) => *l = level,
+
+            None => {
+
+                self.modules.push((module.to_string(), level));
+                self.modules.sort_by_key(|(m,_)| std::cmp::Reverse(m.len()));
+
+            }
+
+        }
+        //
+    }
+    //
+    //
+    /// Check if a log entry is kept by the filter
+    ///
+    /// # Arguments
+    ///
+    /// * 'level'  - type of the log entry
+    /// * 'target' - module path where the log entry has been emitted
+    ///
+    pub(crate) fn enabled(&self, level: Level, target: &str) -> bool {
+        //
+        // Fatal and Error types are not allowed to be disabled
+        if level <= Level::ERROR {
+            return true;
+        }
+        //
+        let max = self.modules.iter()
+            .find(|(module,_)| is_module_of(module, target))
+            .map(|(_,l)| *l)
+            .unwrap_or(self.max_level);
+
+        level <= max
+        //
+    }
+    //
+    //
+    /// The most verbose level that can pass the filter for any module
+    pub(crate) fn max_level(&self) -> Level {
+
+        self.modules.iter().map(|(_,l)| *l).fold(self.max_level, std::cmp::max)
+
+    }
+    //
+    //
+}
+//
+//
+/// Check if the target is the module or one of its children
+fn is_module_of(module: &str, target: &str) -> bool {
+
+    match target.strip_prefix(module) {
+
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false
+
+    }
+
+}
+//
+//
+/// Parse the name of a level, 'off' keep only the levels that can't be disabled
+fn parse_level(name: &str) -> Result<Level,EGeneral> {
+
+    match name.to_ascii_lowercase().as_str() {
+
+        "off" | "error" => Ok(Level::ERROR),
+        "fatal"         => Ok(Level::FATAL),
+        "warn"          => Ok(Level::WARN),
+        "info"          => Ok(Level::INFO),
+        "debug"         => Ok(Level::DEBUG),
+        "trace"         => Ok(Level::TRACE),
+
+        _ => Err(
+            EGeneral::LogFilter
+                .as_report()
+                .attach_printable(format!("unknown log level '{name}'"))
+        )
+
+    }
+
+}
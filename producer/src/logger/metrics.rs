@@ -0,0 +1,360 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Log metrics
+//
+// Count the log entries emitted per level and per call site, and limit how many entries a
+// single call site can emit in a window of time. A validation failing in a loop can then no
+// longer hide the other log entries during a long show run. The entries dropped by the limit
+// are still counted and a summary is written once the call site is allowed to log again.
+//
+// The entries are counted where they are written, on the worker thread when the logs are
+// written asynchronously, the thread emitting them never waits for the counters. The rate
+// limit is off until it is set with `set_rate_limit`.
+//
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use super::{Level, Metadata, LEVEL_STRING};
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use std::time::Duration;
+
+    use super::{Metrics, RateLimit, Verdict};
+    use crate::logger::{Level, Metadata};
+
+    fn meta(level: Level, line: u32) -> Metadata {
+
+        Metadata { level, target: module_path!(), file: file!(), line, category: None }
+
+    }
+
+    #[test]
+    fn count_per_level_and_call_site() {
+
+        let mut metrics = Metrics::new(None);
+        let now = Duration::ZERO;
+
+        for _ in 0..3 {
+            metrics.check(&meta(Level::WARN, 1), now);
+        }
+
+        metrics.check(&meta(Level::ERROR, 2), now);
+
+        let stats = metrics.stats();
+
+        assert_eq!(stats.level(Level::WARN), 3);
+        assert_eq!(stats.level(Level::ERROR), 1);
+        assert_eq!(stats.total(), 4);
+        assert_eq!(stats.call_site(file!(), 1).unwrap().count, 3);
+        assert_eq!(stats.call_site(file!(), 2).unwrap().suppressed, 0);
+
+    }
+
+    #[test]
+    fn call_site_is_limited_per_window() {
+
+        let mut metrics = Metrics::new(Some(RateLimit { max: 2, window: Duration::from_secs(1) }));
+        let now = Duration::ZERO;
+
+        assert_eq!(metrics.check(&meta(Level::WARN, 1), now), Verdict::Write);
+        assert_eq!(metrics.check(&meta(Level::WARN, 1), now), Verdict::Write);
+        assert_eq!(metrics.check(&meta(Level::WARN, 1), now), Verdict::Suppress);
+        assert_eq!(metrics.check(&meta(Level::WARN, 1), now), Verdict::Suppress);
+
+        // another call site has its own limit
+        assert_eq!(metrics.check(&meta(Level::WARN, 2), now), Verdict::Write);
+
+        let later = now + Duration::from_millis(1500);
+
+        assert_eq!(metrics.check(&meta(Level::WARN, 1), later), Verdict::Resume(2));
+        assert_eq!(metrics.check(&meta(Level::WARN, 1), later), Verdict::Write);
+
+        let stats = metrics.stats();
+
+        assert_eq!(stats.call_site(file!(), 1).unwrap().count, 6);
+        assert_eq!(stats.call_site(file!(), 1).unwrap().suppressed, 2);
+
+    }
+
+    #[test]
+    fn errors_are_never_limited() {
+
+        let mut metrics = Metrics::new(Some(RateLimit { max: 1, window: Duration::from_secs(1) }));
+        let now = Duration::ZERO;
+
+        for _ in 0..5 {
+            assert_eq!(metrics.check(&meta(Level::FATAL, 1), now), Verdict::Write);
+            assert_eq!(metrics.check(&meta(Level::ERROR, 2), now), Verdict::Write);
+        }
+
+        assert_eq!(metrics.check(&meta(Level::WARN, 3), now), Verdict::Write);
+        assert_eq!(metrics.check(&meta(Level::WARN, 3), now), Verdict::Suppress);
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Rate limit
+//
+/// Maximum number of log entries a call site can emit in a window of time, the Error and Fatal
+/// entries are never limited
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+
+    pub max:    usize,
+    pub window: Duration,
+
+}
+//
+impl Default for RateLimit {
+
+    fn default() -> Self { Self { max: 100, window: Duration::from_secs(1) } }
+
+}
+//
+//
+/// What to do with a log entry after it has been counted
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Verdict {
+
+    Write,
+    // over the limit of its call site
+    Suppress,
+    // first entry of a new window, the number of entries suppressed in the previous ones
+    Resume(usize),
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Statistics
+//
+/// Counters of a single call site
+#[derive(Clone, Debug)]
+pub struct CallSiteStats {
+
+    pub target:     &'static str,
+    pub file:       &'static str,
+    pub line:       u32,
+    pub level:      Level,
+    /// every entry emitted, suppressed ones included
+    pub count:      usize,
+    /// entries dropped by the rate limit
+    pub suppressed: usize,
+
+}
+//
+//
+/// Copy of the log counters, see `log_stats`
+#[derive(Clone, Debug, Default)]
+pub struct LogStats {
+
+    levels: [usize; LEVEL_STRING.len()],
+    sites:  Vec<CallSiteStats>,
+
+}
+//
+impl LogStats {
+    //
+    /// Number of entries emitted with the given level
+    pub fn level(&self, level: Level) -> usize { self.levels[level as usize] }
+    //
+    /// Number of entries emitted with any level
+    pub fn total(&self) -> usize { self.levels.iter().sum() }
+    //
+    /// Every call site that emitted an entry, the busiest first
+    pub fn call_sites(&self) -> &[CallSiteStats] { &self.sites }
+    //
+    /// Counters of the call site at the given line
+    ///
+    /// # Arguments
+    ///
+    /// * 'file' - the file of the call site as given by `file!()`
+    /// * 'line' - the line of the call site
+    ///
+    pub fn call_site(&self, file: &str, line: u32) -> Option<&CallSiteStats> {
+
+        self.sites.iter().find(|s| s.file == file && s.line == line)
+
+    }
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Metrics
+//
+/// State of a call site
+struct CallSite {
+
+    stats:          CallSiteStats,
+    window_start:   Duration,
+    in_window:      usize,
+    // suppressed since the last summary
+    pending:        usize,
+
+}
+//
+//
+/// Counters and rate limit of every call site
+pub(crate) struct Metrics {
+
+    levels: [usize; LEVEL_STRING.len()],
+    sites:  HashMap<(&'static str, u32), CallSite>,
+    limit:  Option<RateLimit>,
+
+}
+//
+impl Metrics {
+    //
+    /// Create empty counters
+    ///
+    /// # Arguments
+    ///
+    /// * 'limit' - the rate limit of every call site, none to never suppress an entry
+    ///
+    fn new(limit: Option<RateLimit>) -> Self {
+
+        Self { levels: [0; LEVEL_STRING.len()], sites: HashMap::new(), limit }
+
+    }
+    //
+    //
+    /// Count a log entry and check it against the rate limit of its call site
+    ///
+    /// # Arguments
+    ///
+    /// * 'meta' - where the log entry has been emitted
+    /// * 'now'  - when the log entry has been emitted, since the program started
+    ///
+    fn check(&mut self, meta: &Metadata, now: Duration) -> Verdict {
+        //
+        self.levels[meta.level as usize] += 1;
+        //
+        let site = self.sites.entry((meta.file, meta.line)).or_insert_with(|| CallSite {
+            stats: CallSiteStats {
+                target:     meta.target,
+                file:       meta.file,
+                line:       meta.line,
+                level:      meta.level,
+                count:      0,
+                suppressed: 0,
+            },
+            window_start:   now,
+            in_window:      0,
+            pending:        0,
+        });
+
+        site.stats.count += 1;
+        //
+        let limit = match self.limit {
+
+            Some(limit) if meta.level > Level::ERROR => limit,
+            _ => return Verdict::Write
+
+        };
+        //
+        if now.saturating_sub(site.window_start) >= limit.window {
+
+            site.window_start = now;
+            site.in_window = 0;
+
+        }
+
+        site.in_window += 1;
+        //
+        if site.in_window > limit.max {
+
+            site.stats.suppressed += 1;
+            site.pending += 1;
+
+            return Verdict::Suppress;
+
+        }
+        //
+        match std::mem::take(&mut site.pending) {
+
+            0 => Verdict::Write,
+            suppressed => Verdict::Resume(suppressed)
+
+        }
+        //
+    }
+    //
+    //
+    /// Copy of the counters
+    fn stats(&self) -> LogStats {
+
+        let mut sites: Vec<CallSiteStats> = self.sites.values().map(|s| s.stats.clone()).collect();
+
+        sites.sort_by(|a, b| b.count.cmp(&a.count).then(a.file.cmp(b.file)).then(a.line.cmp(&b.line)));
+
+        LogStats { levels: self.levels, sites }
+
+    }
+    //
+}
+//
+lazy_static::lazy_static! {
+
+    static ref METRICS: Mutex<Metrics> = Mutex::new(Metrics::new(None));
+
+}
+//
+//
+/// Lock the METRICS, a panic while it was locked can only leave the counters behind
+fn get_access_metrics() -> MutexGuard<'static, Metrics> {
+
+    METRICS.lock().unwrap_or_else(|e| {
+
+        METRICS.clear_poison();
+        e.into_inner()
+
+    })
+
+}
+//
+//
+/// Count a log entry when it is written
+///
+/// # Arguments
+///
+/// * 'meta'    - where the log entry has been emitted
+/// * 'elapsed' - when the log entry has been emitted, since the program started
+///
+pub(crate) fn check(meta: &Metadata, elapsed: Duration) -> Verdict { get_access_metrics().check(meta, elapsed) }
+//
+//
+/// Copy of the counters of every level and call site since the start or the last reset
+pub fn log_stats() -> LogStats { get_access_metrics().stats() }
+//
+//
+/// Set every counter back to zero, the rate limit is kept
+pub fn reset_log_stats() {
+
+    let mut metrics = get_access_metrics();
+
+    metrics.levels = [0; LEVEL_STRING.len()];
+    metrics.sites.clear();
+
+}
+//
+//
+/// Change the rate limit of every call site, there is none by default
+///
+/// # Arguments
+///
+/// * 'limit' - how many entries a call site can emit per window, none to never suppress an entry
+///
+pub fn set_rate_limit(limit: Option<RateLimit>) { get_access_metrics().limit = limit; }
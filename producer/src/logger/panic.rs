@@ -0,0 +1,188 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Panic and error report
+//
+// The panics and the error_stack reports are written through the log system like any other
+// log entry, so they end up in every sink instead of only on the standard error.
+//
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::panic::{Location, PanicHookInfo};
+
+use error_stack::{AttachmentKind, FrameKind, Report};
+
+use super::{flush_on_panic, try_write_record, worker, Level, Metadata, Record, PANIC_FLUSH_TIMEOUT};
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use error_stack::Report;
+
+    use crate::EGeneral;
+    use super::fmt_report;
+
+    #[test]
+    fn report_show_every_context_and_attachment() {
+
+        let report = Report::new(EGeneral::LogSys)
+            .attach_printable("the mutex is poisoned")
+            .change_context(EGeneral::LogFilter)
+            .attach_printable("invalid directive 'a=b=c'");
+
+        let text = fmt_report(&report);
+        let position = |line: &str| text.lines().position(|l| l == line).unwrap();
+
+        assert_eq!(position("Invalid Log Filter"), 0);
+        assert!(text.lines().nth(1).unwrap().starts_with("  at ") && text.contains("panic.rs"));
+        assert!(position("  - invalid directive 'a=b=c'") < position("caused by: Log System Error"));
+        assert!(position("caused by: Log System Error") < position("  - the mutex is poisoned"));
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Panic hook
+//
+/// Write the panics as Fatal log entries with their location and backtrace, the records
+/// waiting for the asynchronous worker are written and the sinks are flushed right after
+///
+/// The hook installed before still runs after the log entry is written, the default one
+/// printing the panic on the standard error can be replaced first to only keep the log entry
+pub fn install_panic_hook() {
+
+    let previous = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+
+        log_panic(info);
+
+        previous(info);
+
+    }));
+
+}
+//
+//
+/// Write the panic as a Fatal log entry
+fn log_panic(info: &PanicHookInfo) {
+    //
+    let payload = match info.payload().downcast_ref::<&str>() {
+
+        Some(s) => s.to_string(),
+
+        None => match info.payload().downcast_ref::<String>() {
+
+            Some(s) => s.to_string(),
+            None => "Box<dyn Any>".to_string()
+
+        }
+
+    };
+
+    let location = match info.location() {
+
+        Some(location) => location.to_string(),
+        None => "an unknown location".to_string()
+
+    };
+
+    let message = format!(
+        "thread '{}' panicked at {}:\n{}\nbacktrace:\n{}",
+        std::thread::current().name().unwrap_or("<unnamed>"),
+        location,
+        payload,
+        Backtrace::force_capture()
+    );
+    //
+    let meta = Metadata { level: Level::FATAL, target: module_path!(), file: file!(), line: line!(), category: None };
+    //
+    // the panic can come from the log system itself, the message is then printed directly
+    if let Some(record) = worker::send(Record::new(meta, message)) {
+
+        if let Err(record) = try_write_record(record, PANIC_FLUSH_TIMEOUT) {
+
+            eprintln!("{}", record.message);
+
+        }
+
+    }
+
+    flush_on_panic();
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Report
+//
+/// Render an error_stack report on multiple lines, each context is followed by its attachments
+/// and the location where it was created
+///
+/// # Arguments
+///
+/// * 'report' - the report to render
+///
+pub fn fmt_report<C>(report: &Report<C>) -> String {
+    //
+    let mut lines: Vec<String> = Vec::new();
+    //
+    // the attachments are found before the context they are attached to
+    let mut attachments: Vec<String> = Vec::new();
+    //
+    for frame in report.frames() {
+
+        match frame.kind() {
+
+            FrameKind::Context(context) => {
+
+                if lines.is_empty() {
+                    lines.push(context.to_string());
+                } else {
+                    lines.push(format!("caused by: {}", context));
+                }
+
+                // the newest attachment is found first, show them in the order they were added
+                lines.extend(attachments.drain(..).rev());
+
+            },
+
+            FrameKind::Attachment(AttachmentKind::Printable(attachment)) => {
+
+                attachments.push(format!("  - {}", attachment));
+
+            },
+
+            FrameKind::Attachment(AttachmentKind::Opaque(_)) => {
+
+                if let Some(location) = frame.downcast_ref::<Location<'static>>() {
+
+                    attachments.push(format!("  at {}", location));
+
+                } else if let Some(backtrace) = frame.downcast_ref::<Backtrace>() {
+
+                    if backtrace.status() == BacktraceStatus::Captured {
+                        attachments.push(format!("  backtrace:\n{}", backtrace));
+                    }
+
+                }
+
+            },
+
+            _ => ()
+
+        }
+
+    }
+    //
+    lines.extend(attachments.drain(..).rev());
+
+    lines.join("\n")
+    //
+}
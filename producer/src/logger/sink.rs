@@ -0,0 +1,75 @@
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Log sinks
+//
+// A sink is where the formatted log entries end up (terminal, file, ...)
+//
+use std::io::Write;
+
+use super::{Level,Log,Timestamp};
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Sink trait
+//
+/// Destination of the formatted log entries
+pub trait Sink: Send {
+    //
+    /// Write a log entry
+    ///
+    /// # Arguments
+    ///
+    /// * 'log' - the formatted log entry
+    ///
+    fn write(&mut self, log: &Log);
+    //
+    /// Make sure that every log entry written reached its destination
+    fn flush(&mut self) {}
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Standard output
+//
+/// Print the log entries to the standard output, the Error and Fatal ones to the standard error
+#[derive(Default)]
+pub struct StdoutSink { timestamp: Timestamp }
+//
+impl StdoutSink {
+    //
+    /// Create a sink that show the time of the log entries with the given format
+    ///
+    /// # Arguments
+    ///
+    /// * 'timestamp' - how the time of the log entries is shown
+    ///
+    pub fn new(timestamp: Timestamp) -> Self { Self { timestamp } }
+    //
+}
+//
+impl Sink for StdoutSink {
+
+    fn write(&mut self, log: &Log) {
+
+        match log.record().meta.level {
+
+            Level::FATAL | Level::ERROR => eprintln!("{}", log.format(&self.timestamp)),
+            _ => println!("{}", log.format(&self.timestamp))
+
+        }
+
+    }
+
+    fn flush(&mut self) {
+
+        if let Err(e) = std::io::stdout().flush() {
+
+            eprintln!("unable to flush the standard output because: {}", e);
+
+        }
+
+    }
+
+}
@@ -0,0 +1,200 @@
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Timestamp
+//
+// The time shown before each log entry, it can be the time elapsed since the program
+// started, the wall clock in RFC 3339 or both.
+//
+use std::time::Duration;
+
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+
+use super::Record;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::{fmt_elapsed,fmt_wall_clock,Clock,Precision};
+
+    #[test]
+    fn elapsed_is_padded() {
+
+        let dur = Duration::from_millis(9 * 3_600_000 + 9 * 60_000 + 9_000 + 9);
+
+        assert_eq!(fmt_elapsed(dur, Precision::Millis), "09:09:09.009");
+        assert_eq!(fmt_elapsed(dur, Precision::Micros), "09:09:09.009000");
+
+    }
+
+    #[test]
+    fn elapsed_use_real_milliseconds() {
+
+        let dur = Duration::from_micros(61_234_567);
+
+        assert_eq!(fmt_elapsed(dur, Precision::Millis), "00:01:01.234");
+        assert_eq!(fmt_elapsed(dur, Precision::Micros), "00:01:01.234567");
+
+    }
+
+    #[test]
+    fn elapsed_over_a_day_keep_the_hours() {
+
+        assert_eq!(fmt_elapsed(Duration::from_secs(100 * 3600), Precision::Millis), "100:00:00.000");
+
+    }
+
+    #[test]
+    fn utc_wall_clock_is_rfc3339() {
+
+        let time = Utc.with_ymd_and_hms(2026, 10, 19, 8, 30, 5).unwrap() + chrono::Duration::microseconds(42);
+
+        assert_eq!(fmt_wall_clock(time, Clock::Utc, Precision::Millis), "2026-10-19T08:30:05.000Z");
+        assert_eq!(fmt_wall_clock(time, Clock::Utc, Precision::Micros), "2026-10-19T08:30:05.000042Z");
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Format
+//
+/// Which time is shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeMode {
+
+    /// time since the program started
+    Elapsed,
+    /// wall clock of the given time zone
+    WallClock(Clock),
+    /// wall clock followed by the time since the program started
+    Both(Clock),
+
+}
+//
+/// Time zone of the wall clock
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Clock { Local, Utc }
+//
+/// Smallest unit shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precision { Millis, Micros }
+//
+//
+/// How the time of a log entry is formatted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timestamp {
+
+    pub mode:       TimeMode,
+    pub precision:  Precision,
+
+}
+//
+impl Default for Timestamp {
+
+    fn default() -> Self { Self { mode: TimeMode::Elapsed, precision: Precision::Millis } }
+
+}
+//
+impl Timestamp {
+    //
+    /// Create a timestamp format
+    ///
+    /// # Arguments
+    ///
+    /// * 'mode'      - which time is shown
+    /// * 'precision' - smallest unit shown
+    ///
+    pub fn new(mode: TimeMode, precision: Precision) -> Self { Self { mode, precision } }
+    //
+    //
+    /// Format the time of a log entry
+    ///
+    /// # Arguments
+    ///
+    /// * 'record' - the log entry
+    ///
+    pub fn format(&self, record: &Record) -> String {
+        //
+        match self.mode {
+
+            TimeMode::Elapsed => format!("[{}]", fmt_elapsed(record.elapsed, self.precision)),
+
+            TimeMode::WallClock(clock) => format!(
+                "[{}]",
+                fmt_wall_clock(record.time, clock, self.precision)
+            ),
+
+            TimeMode::Both(clock) => format!(
+                "[{}] [{}]",
+                fmt_wall_clock(record.time, clock, self.precision),
+                fmt_elapsed(record.elapsed, self.precision)
+            )
+
+        }
+        //
+    }
+    //
+    //
+}
+//
+//
+/// Format a duration as hh:mm:ss.mmm or hh:mm:ss.uuuuuu
+///
+/// # Arguments
+///
+/// * 'dur'       - duration since the program started
+/// * 'precision' - smallest unit shown
+///
+fn fmt_elapsed(dur: Duration, precision: Precision) -> String {
+    //
+    let secs = dur.as_secs();
+
+    let hour = secs / 3600;
+    let min = (secs % 3600) / 60;
+    let sec = secs % 60;
+    //
+    match precision {
+
+        Precision::Millis => format!("{:02}:{:02}:{:02}.{:03}", hour, min, sec, dur.subsec_millis()),
+        Precision::Micros => format!("{:02}:{:02}:{:02}.{:06}", hour, min, sec, dur.subsec_micros())
+
+    }
+    //
+}
+//
+//
+/// Format a wall clock time in RFC 3339
+///
+/// # Arguments
+///
+/// * 'time'      - when the log entry has been emitted
+/// * 'clock'     - time zone shown
+/// * 'precision' - smallest unit shown
+///
+fn fmt_wall_clock(time: DateTime<Utc>, clock: Clock, precision: Precision) -> String {
+    //
+    let seconds = match precision {
+
+        Precision::Millis => SecondsFormat::Millis,
+        Precision::Micros => SecondsFormat::Micros
+
+    };
+    //
+    match clock {
+
+        Clock::Utc   => time.to_rfc3339_opts(seconds, true),
+        Clock::Local => time.with_timezone(&Local).to_rfc3339_opts(seconds, false)
+
+    }
+    //
+}
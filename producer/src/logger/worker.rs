@@ -0,0 +1,242 @@
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Asynchronous logging
+//
+// When the async mode is started, the log macros only send the record through a bounded
+// channel and a background thread formats it and writes it to the sinks. This way a tokio
+// task that logs never waits for the LOG_SYSTEM mutex or the terminal.
+//
+use std::sync::{Mutex, PoisonError, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use error_stack::{IntoReport, Result, ResultExt};
+
+use super::{Level, Metadata, Record};
+use crate::EGeneral;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Global
+//
+lazy_static::lazy_static! {
+    //
+    // sender of the running worker, None when the logs are written synchronously
+    static ref DISPATCH: RwLock<Option<SyncSender<Message>>> = RwLock::new(None);
+    //
+    static ref WORKER: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+    //
+}
+//
+// how many records have been dropped because the channel was full
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+//
+const WORKER_NAME: &str = "log-worker";
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Worker
+//
+/// What can be sent to the worker
+enum Message {
+
+    Record(Record),
+    // the worker answer on the given sender once every previous record is written
+    Flush(SyncSender<()>)
+
+}
+//
+//
+/// Start the worker thread, do nothing if it is already running
+///
+/// # Arguments
+///
+/// * 'capacity' - how many records can wait in the channel before they are dropped
+///
+pub(crate) fn start(capacity: usize) -> Result<(),EGeneral> {
+    //
+    let mut dispatch = DISPATCH.write().unwrap_or_else(PoisonError::into_inner);
+    //
+    if dispatch.is_some() {
+        return Ok(());
+    }
+    //
+    let (sender, receiver) = mpsc::sync_channel(capacity);
+
+    let handle = thread::Builder::new()
+        .name(WORKER_NAME.to_string())
+        .spawn(move || run(receiver))
+        .into_report()
+        .change_context(EGeneral::LogSys)
+        .attach_printable("Can't start the log worker thread")?;
+    //
+    *dispatch = Some(sender);
+
+    *WORKER.lock().unwrap_or_else(PoisonError::into_inner) = Some(handle);
+
+    Ok(())
+    //
+}
+//
+//
+/// Stop the worker thread after it wrote every pending record
+pub(crate) fn stop() {
+    //
+    // dropping the only sender close the channel, the worker end after the last record
+    DISPATCH.write().unwrap_or_else(PoisonError::into_inner).take();
+    //
+    let handle = WORKER.lock().unwrap_or_else(PoisonError::into_inner).take();
+
+    if let Some(handle) = handle {
+
+        if handle.join().is_err() {
+
+            eprintln!("the log worker thread has panicked, some logs may have been lost");
+
+        }
+
+    }
+    //
+}
+//
+//
+/// Send a record to the worker
+///
+/// Return the record if there is no worker to receive it so it can be written synchronously
+///
+/// # Arguments
+///
+/// * 'record' - the log entry to be written
+///
+pub(crate) fn send(record: Record) -> Option<Record> {
+    //
+    let dispatch = DISPATCH.read().unwrap_or_else(PoisonError::into_inner);
+
+    let sender = match dispatch.as_ref() {
+
+        Some(sender) => sender,
+        None => return Some(record)
+
+    };
+    //
+    match sender.try_send(Message::Record(record)) {
+
+        Ok(()) => None,
+
+        // Fatal and Error logs are never dropped, wait for a place in the channel
+        Err(TrySendError::Full(Message::Record(record))) if record.meta.level <= Level::ERROR => {
+
+            match sender.send(Message::Record(record)) {
+
+                Ok(()) => None,
+                Err(e) => into_record(e.0)
+
+            }
+
+        },
+
+        Err(TrySendError::Full(_)) => {
+
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+            None
+
+        },
+
+        Err(TrySendError::Disconnected(message)) => into_record(message)
+
+    }
+    //
+}
+//
+//
+/// Wait until the worker wrote every record sent before this call
+///
+/// # Arguments
+///
+/// * 'timeout' - how long to wait for the worker, forever if None
+///
+pub(crate) fn flush(timeout: Option<Duration>) {
+    //
+    // the worker can't wait for itself
+    if thread::current().name() == Some(WORKER_NAME) {
+        return;
+    }
+    //
+    let (ack_sender, ack_receiver) = mpsc::sync_channel(1);
+    //
+    let sent = match DISPATCH.read().unwrap_or_else(PoisonError::into_inner).as_ref() {
+
+        Some(sender) => sender.send(Message::Flush(ack_sender)).is_ok(),
+        None => false
+
+    };
+    //
+    if sent {
+
+        let _ = match timeout {
+
+            Some(timeout) => ack_receiver.recv_timeout(timeout).is_ok(),
+            None => ack_receiver.recv().is_ok()
+
+        };
+
+    }
+    //
+}
+//
+//
+/// Loop of the worker thread, end when the channel is closed
+fn run(receiver: Receiver<Message>) {
+    //
+    for message in receiver {
+
+        match message {
+
+            Message::Record(record) => {
+
+                let dropped = DROPPED.swap(0, Ordering::Relaxed);
+
+                if dropped > 0 {
+
+                    super::write_record(Record::new(
+                        Metadata { level: Level::WARN, target: module_path!(), file: file!(), line: line!(), category: None },
+                        format!("{} log entries have been dropped because the log channel was full", dropped)
+                    ));
+
+                }
+
+                super::write_record(record);
+
+            },
+
+            Message::Flush(ack) => {
+
+                super::flush_sinks();
+
+                let _ = ack.send(());
+
+            }
+
+        }
+
+    }
+    //
+    super::flush_sinks();
+    //
+}
+//
+//
+/// Get back the record from a message that couldn't be sent
+fn into_record(message: Message) -> Option<Record> {
+
+    match message {
+
+        Message::Record(record) => Some(record),
+        Message::Flush(_) => None
+
+    }
+
+}
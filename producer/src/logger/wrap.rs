@@ -0,0 +1,202 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Line wrapping
+//
+// Long log messages are split on word boundaries so every line fit in the column left after
+// the header. The width is computed in terminal columns, not in bytes or chars, and a word
+// longer than a line is only cut between two graphemes.
+//
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use super::{width_of,wrap};
+
+    #[test]
+    fn short_message_is_untouched() {
+
+        assert_eq!(wrap("short message", 20), vec!["short message"]);
+
+    }
+
+    #[test]
+    fn wrap_on_words() {
+
+        let lines = wrap("the quick brown fox jumps over the lazy dog", 10);
+
+        assert_eq!(lines, vec!["the quick", "brown fox", "jumps over", "the lazy", "dog"]);
+
+    }
+
+    #[test]
+    fn keep_existing_new_lines() {
+
+        assert_eq!(wrap("first\nsecond line", 8), vec!["first", "second", "line"]);
+
+    }
+
+    #[test]
+    fn spaces_before_a_long_word_are_dropped() {
+
+        assert_eq!(wrap("one\n    abcdefgh", 4), vec!["one", "abcd", "efgh"]);
+        assert_eq!(wrap("ab    cdefghij", 4), vec!["ab", "cdef", "ghij"]);
+
+    }
+
+    #[test]
+    fn long_word_is_cut_between_graphemes() {
+
+        // 'e' followed by a combining acute accent must stay together
+        let word = "e\u{301}".repeat(7);
+
+        let lines = wrap(&word, 3);
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|l| width_of(l) <= 3));
+        assert_eq!(lines.concat(), word);
+
+    }
+
+    #[test]
+    fn wide_characters_use_two_columns() {
+
+        let lines = wrap("日本語のセリフ です", 6);
+
+        assert!(lines.len() >= 3);
+        assert!(lines.iter().all(|l| width_of(l) <= 6));
+        assert_eq!(lines.concat().replace(' ', ""), "日本語のセリフです");
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Wrap
+//
+/// Split a message in lines that fit in the given number of columns
+///
+/// # Arguments
+///
+/// * 'message' - the message to split, it can already contain new lines
+/// * 'width'   - the maximum number of terminal columns of a line
+///
+pub(crate) fn wrap(message: &str, width: usize) -> Vec<String> {
+    //
+    let width = width.max(1);
+    let mut lines: Vec<String> = Vec::new();
+    //
+    for paragraph in message.lines() {
+
+        let mut line = String::new();
+        let mut line_width = 0;
+
+        for word in paragraph.split_word_bounds() {
+
+            let word_width = width_of(word);
+
+            if line_width + word_width <= width {
+
+                line.push_str(word);
+                line_width += word_width;
+                continue;
+
+            }
+
+            // the spaces at the end of a line are not shown
+            if word.trim().is_empty() {
+
+                lines.push(std::mem::take(&mut line).trim_end().to_string());
+                line_width = 0;
+                continue;
+
+            }
+
+            if !line.trim().is_empty() {
+
+                lines.push(std::mem::take(&mut line).trim_end().to_string());
+
+            }
+
+            // the word starts a new line, the spaces left take no column on it
+            line.clear();
+            line_width = 0;
+
+            // a word bigger than a line is cut between two graphemes
+            for grapheme in word.graphemes(true) {
+
+                let grapheme_width = width_of(grapheme);
+
+                if line_width + grapheme_width > width && !line.is_empty() {
+
+                    lines.push(std::mem::take(&mut line));
+                    line_width = 0;
+
+                }
+
+                line.push_str(grapheme);
+                line_width += grapheme_width;
+
+            }
+
+        }
+
+        if !line.trim().is_empty() || paragraph.trim().is_empty() {
+
+            lines.push(line.trim_end().to_string());
+
+        }
+
+    }
+    //
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    //
+    lines
+    //
+}
+//
+//
+/// Number of terminal columns used by a string
+pub(crate) fn width_of(text: &str) -> usize { UnicodeWidthStr::width(text) }
+//
+//
+/// Number of columns of the terminal attached to the standard output if there is one
+pub(crate) fn terminal_width() -> Option<usize> {
+
+    terminal_size::terminal_size().map(|(terminal_size::Width(w), _)| w as usize)
+
+}
+//
+//
+/// Cut a string to a maximum number of bytes without splitting a character
+///
+/// # Arguments
+///
+/// * 'text'  - the string to cut
+/// * 'bytes' - the maximum length in bytes
+///
+pub(crate) fn truncate_bytes(text: &mut String, bytes: usize) {
+
+    if text.len() <= bytes {
+        return;
+    }
+
+    let mut end = bytes;
+
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    text.truncate(end);
+
+}
@@ -1,15 +1,20 @@
 #![allow(dead_code)]
 
 
-use std::fmt::{Display, Formatter};
-use reqwest::Client;
-use futures::TryFutureExt;
+use std::future::Future;
+
+use error_stack::{IntoReport, ResultExt};
+use reqwest::{Client, Response};
 use json::{object, JsonValue};
 
-use super::logger::{CWARN,CERROR};
+use crate::{cwarn,cerror,EGeneral};
+use crate::template::TemplateSet;
 use serde::Deserialize;
 use serde_json::Value;
 
+#[cfg(test)]
+mod mock;
+
 /*
 
 curl https://api.openai.com/v1/completions \
@@ -30,8 +35,10 @@ curl https://api.openai.com/v1/completions \
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::logger::{capture_logs,Level,LogCapture};
 
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn tcurl_req() {
 
         //curl_req();
@@ -41,18 +48,14 @@ mod test {
 
     }
 
-    #[test]
-    fn invalid_temp_value() {
-
-        use super::super::logger::init;
+    /// a valid request that each test modify
+    fn request() -> PromptRequestInfo {
 
-        init();
-
-        let mut info = PromptRequestInfo {
+        PromptRequestInfo {
 
             model: ModelType::MostAccurate,
             prompt: "Say this is a test".to_string(),
-            temperature: Some(3.0),
+            temperature: Some(1.0),
             top_p: None,
             stop_token: None,
             presence_penalty: None,
@@ -62,10 +65,209 @@ mod test {
             nb_response: 1,
             suffix: None,
             logit_bias: None,
-        };
+        }
+
+    }
+
+    #[test]
+    fn model_from_name_or_alias() {
+
+        assert_eq!("text-curie-001".parse::<ModelType>().unwrap(), ModelType::Accurate);
+        assert_eq!("fastest".parse::<ModelType>().unwrap(), ModelType::Fastest);
+        assert!("gpt-10".parse::<ModelType>().is_err());
+
+    }
+
+    #[test]
+    fn completion_uses_the_context_left_by_default() {
+
+        let mut info = request();
+
+        assert_eq!(json::parse(&info.body()).unwrap()["max_tokens"], 7);
+
+        info.max_word = None;
+
+        assert_eq!(json::parse(&info.body()).unwrap()["max_tokens"], 4097 - 5);
+
+        info.model = ModelType::Fastest;
+        info.prompt = "word ".repeat(3000);
+
+        assert_eq!(json::parse(&info.body()).unwrap()["max_tokens"], 1);
+
+    }
+
+    #[test]
+    fn tokens_are_estimated() {
+
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("Say this is a test"), 5);
+        assert_eq!(estimate_tokens("a b c d e f"), 6);
+
+    }
+
+    #[test]
+    fn prompt_from_template() {
+
+        let mut templates = TemplateSet::new();
+        templates.add("test", "Say {{ word }}").unwrap();
+
+        let info = PromptRequestInfo::from_template(&templates, "test", &serde_json::json!({"word": "this"}), ModelType::Fastest).unwrap();
+
+        assert_eq!(info.prompt, "Say this");
+        assert!(PromptRequestInfo::from_template(&templates, "test", &serde_json::json!({}), ModelType::Fastest).is_err());
+
+    }
+
+    #[test]
+    fn embeddings_keep_the_order_of_the_inputs() {
+
+        let body = json::parse(&embedding_body(&["a".to_string(), "b".to_string()])).unwrap();
+
+        assert_eq!(body["model"], EMBEDDING_MODEL);
+        assert_eq!(body["input"][1], "b");
+
+        let text = r#"{"data": [{"index": 1, "embedding": [0.5]}, {"index": 0, "embedding": [1.0, 2.0]}], "model": "m"}"#;
+        let response = || serde_json::from_str::<EmbeddingResponse>(text).unwrap();
+
+        assert_eq!(response().vectors(2).unwrap(), [vec![1.0, 2.0], vec![0.5]]);
+        assert!(response().vectors(3).is_err());
+
+    }
+
+    #[test]
+    fn connection_talks_to_the_mock_server() {
+
+        let server = mock::MockServer::start();
+        let connection = Connection::with_base(&server.base);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let text = runtime.block_on(connection.complete(request())).unwrap();
+
+        assert_eq!(text, "mock completion of 'Say this is a test'");
+
+        let texts = vec!["one".to_string(), "two words".to_string()];
+
+        assert_eq!(runtime.block_on(connection.embed(texts)).unwrap(), [vec![3.0, 1.0], vec![9.0, 2.0]]);
+
+        // the api refuse the empty texts, its explanation is kept
+        let error = runtime.block_on(connection.embed(vec![String::new()])).unwrap_err();
+
+        assert!(format!("{:?}", error).contains("the texts can't be empty"));
+
+    }
+
+    #[test]
+    fn invalid_temp_value() {
+
+        let mut info = request();
+        info.temperature = Some(3.0);
+
+        let (body, logs) = capture_logs(|| info.body());
+
+        assert!(logs.iter().any(|r| r.meta.level == Level::ERROR && r.message.contains("'temperature'")));
+        assert_eq!(json::parse(&body).unwrap()["temperature"], 1.0);
+
+    }
+
+    #[test]
+    fn valid_request_dont_log() {
+
+        let capture = LogCapture::start();
+
+        request().body();
+
+        assert!(capture.records().is_empty());
+
+    }
+
+    #[test]
+    fn temperature_and_top_p_keep_temperature() {
+
+        let mut info = request();
+        info.top_p = Some(0.5);
+
+        let capture = LogCapture::start();
+
+        let body = json::parse(&info.body()).unwrap();
+
+        capture.assert_contains(Level::WARN, "Cannot passed a temperature and top_p parameter");
+        assert!(info.top_p.is_none());
+        assert!(!body.has_key("top_p"));
+
+    }
+
+    #[test]
+    fn invalid_top_p_value() {
+
+        let mut info = request();
+        info.temperature = None;
+        info.top_p = Some(1.5);
+
+        let capture = LogCapture::start();
+
+        let body = json::parse(&info.body()).unwrap();
+
+        capture.assert_contains(Level::ERROR, "'top_p' must be a value between 0 and 1");
+        assert_eq!(body["top_p"], 1.0);
+
+    }
+
+    #[test]
+    fn too_many_stop_tokens() {
+
+        let mut info = request();
+        info.stop_token = Some((0..6).map(|i| i.to_string()).collect());
+
+        let capture = LogCapture::start();
+
+        let body = json::parse(&info.body()).unwrap();
+
+        capture.assert_contains(Level::ERROR, "up to 4 token");
+        assert_eq!(body["stop"].len(), 4);
+
+    }
+
+    #[test]
+    fn empty_stop_tokens() {
+
+        let mut info = request();
+        info.stop_token = Some(Vec::new());
+
+        let capture = LogCapture::start();
+
+        let body = json::parse(&info.body()).unwrap();
+
+        capture.assert_contains(Level::WARN, "empty vec");
+        assert!(!body.has_key("stop"));
+
+    }
+
+    #[test]
+    fn invalid_penalty_value() {
+
+        let mut info = request();
+        info.presence_penalty = Some(-3.0);
+
+        let capture = LogCapture::start();
 
         info.body();
 
+        capture.assert_contains(Level::ERROR, "'penalty' parameter value must be between -2 and 2");
+
+    }
+
+    #[test]
+    fn logit_bias_must_be_an_object() {
+
+        let mut info = request();
+        info.logit_bias = Some(JsonValue::from(3));
+
+        let capture = LogCapture::start();
+
+        info.body();
+
+        capture.assert_contains(Level::ERROR, "'logit_bias' can only be a json object");
+
     }
 
 }
@@ -73,6 +275,7 @@ mod test {
 //
 // ------------------------------------------------------------------------------------------------
 //
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ModelType {
 
     MostAccurate,
@@ -84,7 +287,11 @@ pub enum ModelType {
 //
 impl ModelType {
 
-    fn to_str(&self) -> &str {
+    /// every model, from the most accurate to the fastest
+    pub const ALL: [ModelType; 4] = [Self::MostAccurate, Self::Accurate, Self::FastAndAccurate, Self::Fastest];
+
+    /// name of the model in the api
+    pub fn to_str(&self) -> &str {
 
         match self {
 
@@ -98,6 +305,85 @@ impl ModelType {
 
     }
 
+    /// short name used in the configuration and on the command line
+    pub fn alias(&self) -> &str {
+
+        match self {
+
+            Self::MostAccurate => "most-accurate",
+            Self::Accurate => "accurate",
+            Self::FastAndAccurate => "fast-and-accurate",
+            Self::Fastest => "fastest"
+
+        }
+
+    }
+
+    /// price in dollars of 1000 tokens, prompt and completion are billed the same
+    pub fn price_per_1k_tokens(&self) -> f64 {
+
+        match self {
+
+            Self::MostAccurate => 0.02,
+            Self::Accurate => 0.002,
+            Self::FastAndAccurate => 0.0005,
+            Self::Fastest => 0.0004
+
+        }
+
+    }
+
+    /// price in dollars of the given number of tokens
+    pub fn cost(&self, tokens: usize) -> f64 { tokens as f64 * self.price_per_1k_tokens() / 1000.0 }
+
+    /// number of tokens the prompt and the completion can use together
+    pub fn context_tokens(&self) -> usize {
+
+        match self {
+
+            Self::MostAccurate => 4097,
+            Self::Accurate | Self::FastAndAccurate | Self::Fastest => 2049
+
+        }
+
+    }
+
+
+}
+//
+impl std::str::FromStr for ModelType {
+
+    type Err = error_stack::Report<EGeneral>;
+
+    /// Find a model from its api name or its alias
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+
+        Self::ALL.into_iter()
+            .find(|m| m.to_str() == name || m.alias() == name)
+            .ok_or_else(|| EGeneral::Model.as_report().attach_printable(format!(
+                "unknown model '{}', expected one of {}",
+                name,
+                Self::ALL.iter().map(|m| m.alias()).collect::<Vec<&str>>().join(", ")
+            )))
+
+    }
+
+}
+//
+//
+/// Estimate the number of tokens of a text without the tokenizer of the model
+///
+/// A token is about 4 characters of english text, a word is never less than a token
+///
+/// # Arguments
+///
+/// * 'text' - the prompt or the completion
+///
+pub fn estimate_tokens(text: &str) -> usize {
+
+    let words = text.split_whitespace().count();
+
+    words.max(text.chars().count().div_ceil(4))
 
 }
 //
@@ -109,13 +395,22 @@ struct PromptResponse {
     object:     String,
     created:    i64,
     model:      String,
+    #[serde(rename = "choices")]
     choice:     Value,
     usage:      Value
 
+}
+//
+impl PromptResponse {
+
+    /// text of the first choice
+    fn text(&self) -> Option<String> { self.choice[0]["text"].as_str().map(|t| t.to_string()) }
+
 }
 //
 //
-struct PromptRequestInfo {
+#[derive(Clone, Debug)]
+pub struct PromptRequestInfo {
 
     pub prompt:             String,
     pub model:              ModelType,
@@ -133,10 +428,56 @@ struct PromptRequestInfo {
 //
 impl PromptRequestInfo {
 
+    /// Request with the default parameters and the prompt rendered from a template
+    ///
+    /// # Arguments
+    ///
+    /// * 'templates' - the set containing the template
+    /// * 'name'      - the template of the prompt
+    /// * 'context'   - the values of the variables, the series bible and the episode state
+    /// * 'model'     - the model that will complete the prompt
+    ///
+    pub fn from_template(templates: &TemplateSet, name: &str, context: &Value, model: ModelType) -> error_stack::Result<Self, EGeneral> {
+
+        Ok(Self::new(templates.render(name, context)?, model))
+
+    }
+
+    /// Request with the default parameters
+    ///
+    /// # Arguments
+    ///
+    /// * 'prompt' - the text to complete
+    /// * 'model'  - the model that will complete the prompt
+    ///
+    pub fn new(prompt: String, model: ModelType) -> Self {
+
+        Self {
+            prompt,
+            model,
+            nb_response:        1,
+            max_word:           None,
+            suffix:             None,
+            temperature:        Some(1.0),
+            top_p:              None,
+            stop_token:         None,
+            presence_penalty:   None,
+            frequency_penalty:  None,
+            logit_bias:         None,
+        }
+
+    }
+
 
-    fn body(&mut self) -> String {
+    /// Body of the request, without a maximum number of tokens the completion can use every
+    /// token of the context of the model left by the prompt
+    pub(crate) fn body(&mut self) -> String {
 
-        let max_tokens = self.max_word.unwrap_or(16);
+        let max_tokens = self.max_word.map(usize::from).unwrap_or_else(|| {
+
+            self.model.context_tokens().saturating_sub(estimate_tokens(&self.prompt)).max(1)
+
+        });
 
         let mut body = object!{
 
@@ -152,7 +493,7 @@ impl PromptRequestInfo {
         if let Some(val) = &self.suffix {
             body.insert("suffix",val.to_string()).map_err(|e|
                 {
-                    CERROR(&format!("unable to add the parameter 'suffix' because of {e}"));
+                    cerror!(category: "api", "unable to add the parameter 'suffix' because of {e}");
 
                 }
 
@@ -165,12 +506,12 @@ impl PromptRequestInfo {
 
             if val.is_empty() {
 
-                CWARN("You pass an empty vec so nothing will be add to the request");
+                cwarn!(category: "api", "You pass an empty vec so nothing will be add to the request");
 
             } else {
                 body.insert("stop",valid_stop_token(val)).map_err(|e|
                     {
-                        CERROR(&format!("unable to add the parameter 'stop' because of {e}"));
+                        cerror!(category: "api", "unable to add the parameter 'stop' because of {e}");
                     }
                 ).unwrap();
 
@@ -181,51 +522,49 @@ impl PromptRequestInfo {
 
 
 
-        if self.temperature.is_some() && self.top_p.is_some() {
+        if let (Some(temperature), Some(_)) = (self.temperature, self.top_p) {
 
-            CWARN("Cannot passed a temperature and top_p parameter");
-            CWARN("We will use the temperature parameter value");
+            cwarn!(category: "api", "Cannot passed a temperature and top_p parameter");
+            cwarn!(category: "api", "We will use the temperature parameter value");
 
             self.top_p = None;
 
-            body.insert("temperature",valid_temp_parameter(self.temperature.unwrap()))
+            body.insert("temperature",valid_temp_parameter(temperature))
                 .map_err(|e| {
 
-                    CERROR(&format!("unable to add the parameter 'temperature' because of {e}"));
+                    cerror!(category: "api", "unable to add the parameter 'temperature' because of {e}");
 
 
                 }).unwrap();
 
-        } else if self.temperature.is_none() && self.top_p.is_some() {
+        } else if let (None, Some(top_p)) = (self.temperature, self.top_p) {
 
-            body.insert("top_p",valid_top_p_parameter(self.top_p.unwrap()))
+            body.insert("top_p",valid_top_p_parameter(top_p))
                 .map_err(|e| {
 
-                    let mess = format!("unable to add the parameter 'top_p' because of {e}");
-
-                    CERROR(&mess);
+                    cerror!(category: "api", "unable to add the parameter 'top_p' because of {e}");
 
                     }
                 ).unwrap();
 
-        } else if self.temperature.is_some() && self.top_p.is_none() {
+        } else if let (Some(temperature), None) = (self.temperature, self.top_p) {
 
-            body.insert("temperature",valid_temp_parameter(self.temperature.unwrap()))
+            body.insert("temperature",valid_temp_parameter(temperature))
                 .map_err(|e| {
 
-                    CERROR(&format!("unable to add the parameter 'temperature' because of {e}"));
+                    cerror!(category: "api", "unable to add the parameter 'temperature' because of {e}");
 
                 }).unwrap();
 
         } else {
 
-            CWARN("Should passed at least one of the temperature and top_p parameters");
-            CWARN("We will use the temperature parameter value");
+            cwarn!(category: "api", "Should passed at least one of the temperature and top_p parameters");
+            cwarn!(category: "api", "We will use the temperature parameter value");
 
             body.insert("temperature",1.0)
                 .map_err(|e| {
 
-                    CERROR(&format!("unable to add the parameter 'temperature' because of {e}"));
+                    cerror!(category: "api", "unable to add the parameter 'temperature' because of {e}");
 
                     }
                 ).unwrap();
@@ -238,7 +577,7 @@ impl PromptRequestInfo {
             body.insert("presence_penalty",validate_penalty(val)).map_err(|e|
                 {
 
-                    CERROR(&format!("Unable to add parameter 'presence_penalty' because of {e}"));
+                    cerror!(category: "api", "Unable to add parameter 'presence_penalty' because of {e}");
 
                 }
 
@@ -251,7 +590,7 @@ impl PromptRequestInfo {
 
             body.insert("frequency_penalty",validate_penalty(val))
                 .map_err(|e|
-                CERROR(&format!("Unable to add parameter 'frequency_penalty' because of {e}"))
+                cerror!(category: "api", "Unable to add parameter 'frequency_penalty' because of {e}")
             ).unwrap();
 
 
@@ -265,7 +604,7 @@ impl PromptRequestInfo {
 
                     body.insert("logit_bias",obj.pretty(2))
                         .map_err(|e|
-                            CERROR(&format!("Unable to add parameter 'logit_bias' because of {e}"))
+                            cerror!(category: "api", "Unable to add parameter 'logit_bias' because of {e}")
 
                     ).unwrap();
 
@@ -273,7 +612,7 @@ impl PromptRequestInfo {
 
                 _ => {
 
-                    CERROR("the parameter 'logit_bias' can only be a json object");
+                    cerror!(category: "api", "the parameter 'logit_bias' can only be a json object");
 
                 }
 
@@ -301,8 +640,8 @@ fn valid_temp_parameter(temperature:f32) -> f32 {
 
     if !(0.0..=2.0).contains(&temperature) {
 
-        CERROR("The parameters 'temperature' must be a value between 0 and 2");
-        CWARN("The default temperature value of '1' will be pass");
+        cerror!(category: "api", "The parameters 'temperature' must be a value between 0 and 2");
+        cwarn!(category: "api", "The default temperature value of '1' will be pass");
 
         return 1.0;
 
@@ -317,8 +656,8 @@ fn valid_top_p_parameter(top_p:f32) -> f32 {
 
     if !(0.0..=1.0).contains(&top_p) {
 
-        CERROR("The parameters 'top_p' must be a value between 0 and 1");
-        CWARN("The default 'top_p' value of '1' will be pass");
+        cerror!(category: "api", "The parameters 'top_p' must be a value between 0 and 1");
+        cwarn!(category: "api", "The default 'top_p' value of '1' will be pass");
 
         return 1.0;
 
@@ -331,8 +670,8 @@ fn valid_stop_token(list:&[String]) -> &[String] {
 
     if list.len() > 4 {
 
-        CERROR("The 'stop' parameter can containt up to 4 token");
-        CWARN("the first four token will be used");
+        cerror!(category: "api", "The 'stop' parameter can containt up to 4 token");
+        cwarn!(category: "api", "the first four token will be used");
 
         return &list[..4];
 
@@ -347,8 +686,8 @@ fn validate_penalty(penalty:f32) -> f32 {
 
     if !(-2.0..=2.0).contains(&penalty) {
 
-        CERROR("The 'penalty' parameter value must be between -2 and 2");
-        CWARN("The default value of 0 will be send");
+        cerror!(category: "api", "The 'penalty' parameter value must be between -2 and 2");
+        cwarn!(category: "api", "The default value of 0 will be send");
 
         return 1.0;
 
@@ -363,36 +702,195 @@ fn validate_penalty(penalty:f32) -> f32 {
 // ------------------------------------------------------------------------------------------------
 // Connection
 //
-struct Connection {
+/// Something that complete the prompts, the api or a fake one in the tests
+pub trait CompletionClient: Sync {
 
-    client: Client
+    /// Send a request and give back the text of the first choice
+    ///
+    /// # Arguments
+    ///
+    /// * 'request' - the prompt and its parameters
+    ///
+    fn complete(&self, request: PromptRequestInfo) -> impl Future<Output = error_stack::Result<String, EGeneral>> + Send;
+
+}
+//
+//
+/// Something that turns texts into vectors, the api or a fake one in the tests
+pub trait EmbeddingClient: Sync {
+
+    /// Send texts and give back one vector per text, in the same order
+    ///
+    /// # Arguments
+    ///
+    /// * 'texts' - the texts to embed
+    ///
+    fn embed(&self, texts: Vec<String>) -> impl Future<Output = error_stack::Result<Vec<Vec<f32>>, EGeneral>> + Send;
+
+}
+//
+//
+/// name of the model of the embeddings
+pub const EMBEDDING_MODEL: &str = "text-embedding-ada-002";
+//
+//
+#[derive(Deserialize,Debug)]
+struct Embedding {
+
+    index:      usize,
+    embedding:  Vec<f32>
+
+}
+//
+//
+#[derive(Deserialize,Debug)]
+struct EmbeddingResponse { data: Vec<Embedding> }
+//
+impl EmbeddingResponse {
+
+    /// vectors in the order of the inputs, there must be one per input
+    fn vectors(mut self, inputs: usize) -> error_stack::Result<Vec<Vec<f32>>, EGeneral> {
+
+        if self.data.len() != inputs {
+
+            return Err(EGeneral::Api.as_report()
+                .attach_printable(format!("{} embeddings received for {} texts", self.data.len(), inputs)));
+
+        }
+
+        self.data.sort_by_key(|e| e.index);
+
+        Ok(self.data.into_iter().map(|e| e.embedding).collect())
+
+    }
+
+}
+//
+//
+fn embedding_body(texts: &[String]) -> String {
+
+    object!{
+
+        model:  EMBEDDING_MODEL,
+        input:  texts,
+
+    }.to_string()
+
+}
+//
+//
+/// address of the OpenAI api
+pub const API_URL: &str = "https://api.openai.com/v1";
+//
+//
+/// Client of the completion api
+pub struct Connection {
+
+    client: Client,
+    // address of the api, the endpoints are added to it
+    base:   String
 
 }
 //
 impl Connection {
 
-    fn init() -> Self { Self { client: Client::new() } }
+    pub fn init() -> Self { Self::with_base(API_URL) }
 
-    async fn send_prompt(&self,body:String) -> Result<(), Box<dyn std::error::Error>> {
+    /// Client of an api answering like OpenAI at another address, like a mock server
+    ///
+    /// # Arguments
+    ///
+    /// * 'base' - the address of the api, like 'http://127.0.0.1:8080/v1'
+    ///
+    pub fn with_base(base: &str) -> Self { Self { client: Client::new(), base: base.trim_end_matches('/').to_string() } }
+
+    async fn send_prompt(&self,body:String) -> error_stack::Result<PromptResponse, EGeneral> {
 
         let response = self.client
-            .post("https://api.openai.com/v1/completions")
+            .post(format!("{}/completions", self.base))
             .header("Content-Type", "application/json")
             .header("Authorization", format!("Bearer {}", env!("OPENAI_KEY")))
             .body(body)
             .send()
-            .await?
+            .await
+            .into_report()
+            .change_context(EGeneral::Api)?;
+
+        let response = checked(response).await?
             .json::<PromptResponse>()
-            .await?;
+            .await
+            .into_report()
+            .change_context(EGeneral::Api)?;
+
+        Ok(response)
+
+
+    }
+
+    async fn send_embeddings(&self,body:String) -> error_stack::Result<EmbeddingResponse, EGeneral> {
+
+        let response = self.client
+            .post(format!("{}/embeddings", self.base))
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", env!("OPENAI_KEY")))
+            .body(body)
+            .send()
+            .await
+            .into_report()
+            .change_context(EGeneral::Api)?;
 
+        checked(response).await?
+            .json::<EmbeddingResponse>()
+            .await
+            .into_report()
+            .change_context(EGeneral::Api)
 
+    }
+
+
+}
+//
+//
+/// Give back the response if the api succeeded, the error has the body explaining why otherwise
+async fn checked(response: Response) -> error_stack::Result<Response, EGeneral> {
 
-   
-        Ok(())
+    let Err(error) = response.error_for_status_ref() else { return Ok(response) };
 
+    let body = response.text().await.unwrap_or_default();
+
+    Err(error_stack::Report::new(error)
+        .change_context(EGeneral::Api)
+        .attach_printable(format!("response of the api: {}", body.trim())))
+
+}
+//
+impl CompletionClient for Connection {
+
+    async fn complete(&self, mut request: PromptRequestInfo) -> error_stack::Result<String, EGeneral> {
+
+        let response = self.send_prompt(request.body()).await?;
+
+        response.text().ok_or_else(|| {
+
+            EGeneral::Api.as_report().attach_printable(format!("the response '{}' has no text", response.id))
+
+        })
 
     }
 
+}
+//
+impl EmbeddingClient for Connection {
+
+    async fn embed(&self, texts: Vec<String>) -> error_stack::Result<Vec<Vec<f32>>, EGeneral> {
+
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.send_embeddings(embedding_body(&texts)).await?.vectors(texts.len())
+
+    }
 
 }
     
@@ -0,0 +1,144 @@
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Mock server
+//
+// A local server answering like the OpenAI api, so the Connection can be tested without a key
+// or a network. It serves the completions and the embeddings, one request per connection.
+//
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use serde_json::{json, Value};
+//
+//
+/// The address of a running mock server, the server lives until the end of the tests
+pub(crate) struct MockServer { pub(crate) base: String }
+//
+impl MockServer {
+    //
+    /// Start a server on a free port of the local host
+    pub(crate) fn start() -> Self {
+        //
+        let listener = TcpListener::bind("127.0.0.1:0").expect("a local port is free");
+        let base = format!("http://{}/v1", listener.local_addr().expect("the listener has an address"));
+        //
+        std::thread::spawn(move || {
+
+            for stream in listener.incoming().flatten() {
+
+                // a client giving up in the middle of a request doesn't stop the server
+                let _ = serve(stream);
+
+            }
+
+        });
+        //
+        Self { base }
+        //
+    }
+    //
+}
+//
+//
+/// Read one request and write its answer
+fn serve(stream: TcpStream) -> std::io::Result<()> {
+    //
+    let mut reader = BufReader::new(stream);
+    let mut head = String::new();
+
+    reader.read_line(&mut head)?;
+
+    let path = head.split_whitespace().nth(1).unwrap_or_default().to_string();
+    //
+    // the headers, only the length of the body is needed
+    let mut length = 0;
+
+    loop {
+
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+
+        if line.trim().is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+
+        }
+
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    //
+    let request: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+
+    let (status, answer) = answer(&path, &request);
+    let answer = answer.to_string();
+    //
+    write!(
+        reader.get_mut(),
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, answer.len(), answer
+    )
+    //
+}
+//
+//
+/// Status and body answered to a request
+///
+/// # Arguments
+///
+/// * 'path'    - the endpoint asked
+/// * 'request' - the json body of the request
+///
+fn answer(path: &str, request: &Value) -> (&'static str, Value) {
+    //
+    let error = |message: &str| json!({ "error": { "message": message, "type": "invalid_request_error" } });
+    //
+    match path {
+
+        "/v1/completions" => (
+            "200 OK",
+            json!({
+                "id":       "cmpl-mock",
+                "object":   "text_completion",
+                "created":  0,
+                "model":    request["model"],
+                "choices":  [{ "text": format!("mock completion of '{}'", request["prompt"].as_str().unwrap_or_default()), "index": 0 }],
+                "usage":    { "total_tokens": 0 }
+            })
+        ),
+
+        "/v1/embeddings" => {
+
+            let inputs: Vec<&str> = request["input"].as_array().into_iter().flatten().filter_map(|i| i.as_str()).collect();
+
+            if inputs.is_empty() || inputs.iter().any(|i| i.is_empty()) {
+                return ("400 Bad Request", error("'$.input' is invalid, the texts can't be empty"));
+            }
+
+            // the vector of a text is its length and its number of words, given in reverse
+            // order like the api is allowed to
+            let data: Vec<Value> = inputs.iter().enumerate().rev()
+                .map(|(index, text)| json!({
+                    "object":       "embedding",
+                    "index":        index,
+                    "embedding":    [text.len() as f32, text.split_whitespace().count() as f32]
+                }))
+                .collect();
+
+            ("200 OK", json!({ "object": "list", "data": data, "model": request["model"] }))
+
+        },
+
+        _ => ("404 Not Found", error(&format!("unknown endpoint '{}'", path)))
+
+    }
+    //
+}
@@ -0,0 +1,538 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Playback
+//
+// An episode played in the terminal: the scene headings, then each line typed along its time in
+// the timeline, the speakers in the color of their character. The playback only produces the
+// text to write, the caller gives it the elapsed time and the keys of the viewer, so a stored
+// episode and one still being written are played the same way.
+//
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use colored::{Color, Colorize};
+
+use crate::episode::Episode;
+use crate::script::ScriptLine;
+use crate::series::Series;
+use crate::timeline::{Pacing, Timeline};
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use std::time::Duration;
+
+    use crate::episode::{Act, Beat, Episode, Scene};
+    use crate::script::ScriptLine;
+    use crate::series::{Character, Series};
+    use crate::timeline::Pacing;
+    use super::{controls, Control, Playback};
+
+    fn ms(ms: u64) -> Duration { Duration::from_millis(ms) }
+
+    /// the text without its colors
+    fn plain(text: String) -> String {
+
+        let mut plain = String::new();
+        let mut escape = false;
+
+        for c in text.chars() {
+
+            match (escape, c) {
+
+                (false, '\x1b') => escape = true,
+                (false, c) => plain.push(c),
+                (true, 'm') => escape = false,
+                (true, _) => {}
+
+            }
+
+        }
+
+        plain
+
+    }
+
+    fn series() -> Series {
+
+        let mut series = Series::new("The Night Shift", "Two robots run a diner on the moon");
+        series.characters = vec![Character::new("Bolt"), Character::new("Nova")];
+        series
+
+    }
+
+    fn episode() -> Episode {
+
+        let scene = |heading: &str, lines: Vec<ScriptLine>| Scene {
+            heading:    heading.to_string(),
+            beats:      vec![Beat { lines, ..Beat::default() }],
+            ..Scene::default()
+        };
+
+        // at 1.5s for 1s, at 2.8s for 1.5s, then at 6.1s for 1s
+        Episode {
+            acts: vec![Act {
+                scenes: vec![
+                    scene("INT. DINER - NIGHT", vec![
+                        ScriptLine::Dialogue { speaker: "Bolt".to_string(), parenthetical: None, text: "Hello moon".to_string() },
+                        ScriptLine::Direction { text: "Nova rolls in".to_string() },
+                    ]),
+                    scene("EXT. CRATER - DAY", vec![
+                        ScriptLine::Dialogue { speaker: "Nova".to_string(), parenthetical: Some("whispering".to_string()), text: "Bye.".to_string() },
+                    ]),
+                ],
+                ..Act::default()
+            }],
+            ..Episode::default()
+        }
+
+    }
+
+    #[test]
+    fn episode_is_typed_along_its_timeline() {
+
+        let mut playback = Playback::new(&series(), &episode(), Pacing::default());
+
+        assert_eq!(plain(playback.advance(ms(1000))), "");
+        assert_eq!(plain(playback.advance(ms(1000))), "\nINT. DINER - NIGHT\n\nBOLT: Hello");
+        assert!(!playback.is_done());
+
+        assert_eq!(
+            plain(playback.advance(ms(10000))),
+            " moon\n  Nova rolls in\n\nEXT. CRATER - DAY\n\nNOVA (whispering): Bye.\n"
+        );
+        assert!(playback.is_done());
+
+        // every character has its own color
+        let colors = &playback.colors;
+        assert_ne!(colors["BOLT"], colors["NOVA"]);
+        assert_eq!(playback.color("Bolt"), colors["BOLT"]);
+        assert!(super::PALETTE.contains(&playback.color("Gus")));
+
+    }
+
+    #[test]
+    fn viewer_can_pause_skip_and_rewind() {
+
+        assert_eq!(controls(b" nq\x1b[D\x1b[Cbx"), [
+            Control::Pause, Control::Skip, Control::Quit, Control::Rewind, Control::Skip, Control::Rewind
+        ]);
+        assert_eq!(controls(b"\x1b"), [Control::Quit]);
+        assert_eq!(controls(b"\x03"), [Control::Quit]);
+
+        let mut playback = Playback::new(&series(), &episode(), Pacing::default());
+
+        assert!(plain(playback.advance(ms(2000))).ends_with("BOLT: Hello"));
+
+        // nothing moves while paused
+        playback.control(Control::Pause);
+        assert_eq!(playback.advance(ms(5000)), "");
+        playback.control(Control::Pause);
+
+        // the rest of the line is shown at once and the direction starts
+        playback.control(Control::Skip);
+        assert_eq!(plain(playback.advance(ms(0))), " moon\n  ");
+
+        // back to the previous line, just started
+        assert_eq!(playback.control(Control::Rewind), "\n");
+        assert_eq!(plain(playback.advance(ms(1000))), "BOLT: Hello moon\n");
+
+        // far enough in a line, it starts again
+        playback.advance(ms(1500));
+        assert_eq!(playback.control(Control::Rewind), "\n");
+        assert_eq!(plain(playback.advance(ms(0))), "  ");
+
+    }
+
+    #[test]
+    fn live_episode_is_played_as_it_arrives() {
+
+        let mut playback = Playback::live(&series(), Pacing::default());
+
+        assert_eq!(playback.advance(ms(10000)), "");
+        assert!(playback.waiting());
+
+        // only the first line is written
+        let mut partial = episode();
+        partial.acts[0].scenes[0].beats[0].lines.truncate(1);
+        partial.acts[0].scenes[1].beats.clear();
+
+        playback.update(&partial);
+
+        assert_eq!(plain(playback.advance(ms(2000))), "\nINT. DINER - NIGHT\n\nBOLT: Hello");
+        assert_eq!(plain(playback.advance(ms(10000))), " moon\n");
+        assert!(playback.waiting());
+
+        // the direction comes after the usual gap, not the time spent waiting
+        playback.update(&episode());
+
+        assert_eq!(plain(playback.advance(ms(200))), "");
+        assert_eq!(plain(playback.advance(ms(100))), "  ");
+
+        playback.finish();
+
+        assert!(plain(playback.advance(ms(10000))).ends_with("NOVA (whispering): Bye.\n"));
+        assert!(playback.is_done() && !playback.waiting());
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Controls
+//
+/// What the viewer can ask during the playback
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+
+    /// stop or resume the playback
+    Pause,
+    /// show the rest of the line and go to the next one
+    Skip,
+    /// play the line again, or the previous one when it just started
+    Rewind,
+    Quit,
+
+}
+//
+//
+/// The controls of the keys read from the terminal
+///
+/// Space pauses, 'n' or right skips, 'b' or left rewinds, 'q', escape or ctrl-c quits, the
+/// other keys are ignored
+pub fn controls(keys: &[u8]) -> Vec<Control> {
+    //
+    let mut controls = Vec::new();
+    let mut i = 0;
+
+    while i < keys.len() {
+
+        let control = match &keys[i..] {
+
+            [0x1b, b'[', b'C', ..] => { i += 2; Some(Control::Skip) },
+            [0x1b, b'[', b'D', ..] => { i += 2; Some(Control::Rewind) },
+            [0x1b, b'[', _, ..] => { i += 2; None },
+            [b' ', ..] => Some(Control::Pause),
+            [b'n', ..] => Some(Control::Skip),
+            [b'b', ..] => Some(Control::Rewind),
+            // ctrl-c is read as a key while the terminal doesn't send the signals
+            [b'q', ..] | [0x1b, ..] | [0x03, ..] => Some(Control::Quit),
+            _ => None
+
+        };
+
+        controls.extend(control);
+        i += 1;
+
+    }
+    //
+    controls
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Playback
+//
+/// colors of the speakers, in the order of the characters of the bible
+const PALETTE: [Color; 8] = [
+    Color::BrightCyan, Color::BrightYellow, Color::BrightMagenta, Color::BrightGreen,
+    Color::BrightBlue, Color::BrightRed, Color::Cyan, Color::Yellow,
+];
+//
+// a rewind this early in a line goes to the previous one
+const REWIND_GRACE: Duration = Duration::from_millis(1000);
+//
+//
+/// An episode being played
+pub struct Playback {
+
+    /// color of each speaker, by uppercase name
+    pub colors:     BTreeMap<String, Color>,
+    pacing:         Pacing,
+    episode:        Episode,
+    timeline:       Timeline,
+    /// position in the episode
+    clock:          Duration,
+    paused:         bool,
+    /// no more lines will come
+    complete:       bool,
+    /// index in the timeline of the line being typed, or of the next one
+    line:           usize,
+    /// the speaker of the line is written
+    started:        bool,
+    /// characters of the line already written
+    revealed:       usize,
+    /// act and scene of the last heading written
+    scene:          Option<(usize, usize)>,
+
+}
+//
+impl Playback {
+    //
+    /// Play a whole episode
+    ///
+    /// # Arguments
+    ///
+    /// * 'series'  - the bible, for the colors of the characters
+    /// * 'episode' - the episode to play
+    /// * 'pacing'  - the length of the lines
+    ///
+    pub fn new(series: &Series, episode: &Episode, pacing: Pacing) -> Self {
+
+        let mut playback = Self::live(series, pacing);
+
+        playback.update(episode);
+        playback.finish();
+
+        playback
+
+    }
+    //
+    //
+    /// Play an episode while it is written, it is given by `update` and `finish` tells when
+    /// it is done
+    pub fn live(series: &Series, pacing: Pacing) -> Self {
+
+        let colors = series.characters.iter()
+            .enumerate()
+            .map(|(i, c)| (c.name.to_uppercase(), PALETTE[i % PALETTE.len()]))
+            .collect();
+
+        Self {
+            colors,
+            pacing,
+            episode:    Episode::default(),
+            timeline:   Timeline::default(),
+            clock:      Duration::ZERO,
+            paused:     false,
+            complete:   false,
+            line:       0,
+            started:    false,
+            revealed:   0,
+            scene:      None,
+        }
+
+    }
+    //
+    //
+    /// Take the episode as it is now, the lines already played keep their time
+    pub fn update(&mut self, episode: &Episode) {
+
+        self.timeline = Timeline::build(episode, &self.pacing, &Default::default());
+        self.episode = episode.clone();
+
+    }
+    //
+    //
+    /// The episode is complete, the playback ends with its last line
+    pub fn finish(&mut self) { self.complete = true; }
+    //
+    //
+    /// Every line is played
+    pub fn is_done(&self) -> bool { self.complete && self.line >= self.timeline.lines.len() }
+    //
+    //
+    /// Every line written so far is played, the next ones are not there yet
+    pub fn waiting(&self) -> bool { !self.complete && self.line >= self.timeline.lines.len() }
+    //
+    //
+    /// Color of a speaker, a speaker out of the bible gets one from its name
+    pub fn color(&self, speaker: &str) -> Color {
+
+        let name = speaker.to_uppercase();
+
+        self.colors.get(&name)
+            .copied()
+            .unwrap_or_else(|| PALETTE[name.bytes().map(|b| b as usize).sum::<usize>() % PALETTE.len()])
+
+    }
+    //
+    //
+    /// Apply a control of the viewer, gives the text to write before going on
+    pub fn control(&mut self, control: Control) -> String {
+        //
+        let lines = &self.timeline.lines;
+
+        match control {
+
+            Control::Pause => self.paused = !self.paused,
+
+            Control::Skip if self.line < lines.len() => {
+
+                self.clock = match self.started {
+
+                    true => lines.get(self.line + 1).map_or(lines[self.line].end, |l| l.start).max(self.clock),
+                    false => lines[self.line].start.max(self.clock)
+
+                };
+
+            },
+
+            Control::Rewind if self.line > 0 || self.started => {
+
+                let restart = self.started
+                    && self.line < lines.len()
+                    && self.clock.saturating_sub(lines[self.line].start) > REWIND_GRACE;
+
+                let line = if restart { self.line } else { self.line.saturating_sub(1) };
+                let partial = std::mem::replace(&mut self.started, false);
+
+                self.line = line;
+                self.revealed = 0;
+                self.clock = lines[line].start;
+
+                if partial {
+                    return "\n".to_string();
+                }
+
+            },
+
+            _ => {}
+
+        }
+        //
+        String::new()
+        //
+    }
+    //
+    //
+    /// Move the playback forward, gives the text to write
+    ///
+    /// # Arguments
+    ///
+    /// * 'elapsed' - time since the previous call
+    ///
+    pub fn advance(&mut self, elapsed: Duration) -> String {
+        //
+        if self.paused {
+            return String::new();
+        }
+
+        self.clock += elapsed;
+        //
+        let mut output = String::new();
+
+        while let Some(timed) = self.timeline.lines.get(self.line) {
+
+            if self.clock < timed.start {
+                break;
+            }
+
+            if !self.started {
+
+                let scene = (timed.key.act, timed.key.scene);
+
+                if self.scene != Some(scene) {
+
+                    let heading = &self.episode.acts[scene.0].scenes[scene.1].heading;
+
+                    if !heading.is_empty() {
+                        output += &format!("\n{}\n\n", heading.to_uppercase().bold().underline());
+                    }
+
+                    self.scene = Some(scene);
+
+                }
+
+                output += &self.prefix(&timed.line);
+                self.started = true;
+
+            }
+
+            // the characters of the text are typed evenly along the line
+            let text: Vec<char> = text(&timed.line).chars().collect();
+            let length = timed.end.saturating_sub(timed.start);
+
+            let typed = match self.clock >= timed.end || length.is_zero() {
+
+                true => text.len(),
+                false => (text.len() as f64 * (self.clock - timed.start).as_secs_f64() / length.as_secs_f64()) as usize
+
+            };
+
+            if typed > self.revealed {
+
+                let chunk: String = text[self.revealed..typed].iter().collect();
+
+                output += &match timed.line {
+
+                    ScriptLine::Direction { .. } => chunk.dimmed().italic().to_string(),
+                    ScriptLine::Heading { .. } | ScriptLine::Transition { .. } => chunk.bold().to_string(),
+                    _ => chunk
+
+                };
+
+                self.revealed = typed;
+
+            }
+
+            if self.clock < timed.end {
+                break;
+            }
+
+            output.push('\n');
+
+            self.line += 1;
+            self.started = false;
+            self.revealed = 0;
+
+        }
+        //
+        // a live episode waits for its next line at the end of the last one
+        if self.waiting() {
+            self.clock = self.clock.min(self.timeline.duration());
+        }
+        //
+        output
+        //
+    }
+    //
+    //
+    /// What is written before the text of a line, the speaker of a reply
+    fn prefix(&self, line: &ScriptLine) -> String {
+
+        match line {
+
+            ScriptLine::Dialogue { speaker, parenthetical, .. } => {
+
+                let speaker = speaker.to_uppercase().color(self.color(speaker)).bold();
+
+                match parenthetical {
+
+                    Some(p) => format!("{} {}: ", speaker, format!("({})", p).dimmed()),
+                    None => format!("{}: ", speaker)
+
+                }
+
+            },
+
+            ScriptLine::Direction { .. } => "  ".to_string(),
+
+            _ => String::new()
+
+        }
+
+    }
+    //
+}
+//
+//
+/// The text typed for a line
+fn text(line: &ScriptLine) -> &str {
+
+    match line {
+
+        ScriptLine::Heading { text } | ScriptLine::Direction { text } | ScriptLine::Transition { text } | ScriptLine::Note { text } => text,
+        ScriptLine::Dialogue { text, .. } => text
+
+    }
+
+}
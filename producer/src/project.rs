@@ -0,0 +1,880 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Project
+//
+// The directory of a series holds its bible, the working copy of each episode and, kept by the
+// project, every version of the episodes with its review status and every generation sent to
+// the api with its parameters and its cost. A manifest gives the version of this layout, the
+// directories made by an older version are migrated when they are opened.
+//
+//  bible.toml
+//  project.json                the manifest
+//  episodes/001.json           the current version of each episode
+//  versions/001/v1.json        every version of each episode
+//  generations.jsonl           one generation per line
+//
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use error_stack::{IntoReport, Result, ResultExt};
+use serde::{Deserialize, Serialize};
+
+use crate::episode::Episode;
+use crate::{cinfo, estimate_tokens, CompletionClient, EGeneral, PromptRequestInfo};
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use std::path::PathBuf;
+
+    use error_stack::Result;
+    use futures::executor::block_on;
+
+    use crate::episode::{Act, Episode, Scene};
+    use crate::{CompletionClient, EGeneral, ModelType, PromptRequestInfo};
+    use super::{diff, Change, Project, Recorder, ReviewStatus, SCHEMA};
+
+    struct EchoClient;
+
+    impl CompletionClient for EchoClient {
+
+        async fn complete(&self, request: PromptRequestInfo) -> Result<String, EGeneral> {
+
+            match request.prompt.as_str() {
+
+                "fail" => Err(EGeneral::Api.as_report()),
+                prompt => Ok(format!("{} back", prompt))
+
+            }
+
+        }
+
+    }
+
+    fn dir(name: &str) -> PathBuf {
+
+        let dir = std::env::temp_dir().join(format!("producer-project-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+
+    }
+
+    fn episode(summary: &str) -> Episode {
+
+        Episode {
+            number: 2,
+            logline: "The diner runs out of coffee".to_string(),
+            acts: vec![Act {
+                summary: "The shortage".to_string(),
+                scenes: vec![Scene { heading: "Diner".to_string(), summary: summary.to_string(), ..Scene::default() }]
+            }]
+        }
+
+    }
+
+    #[test]
+    fn versions_are_kept_and_rolled_back() {
+
+        let dir = dir("versions");
+        let mut project = Project::open(&dir).unwrap();
+
+        assert_eq!(project.commit_episode(&episode("No coffee"), "generated").unwrap(), 1);
+        assert_eq!(project.commit_episode(&episode("No coffee"), "same").unwrap(), 1);
+        assert_eq!(project.commit_episode(&episode("No tea"), "scene 1 regenerated").unwrap(), 2);
+
+        project.set_status(2, 1, ReviewStatus::Accepted).unwrap();
+        assert!(project.set_status(2, 5, ReviewStatus::Accepted).is_err());
+
+        let restored = project.rollback(2, 1).unwrap();
+
+        assert_eq!(restored.acts[0].scenes[0].summary, "No coffee");
+        assert_eq!(Episode::load(&project.episode_path(2)).unwrap(), restored);
+
+        // a new version comes after every existing one, the manifest is saved
+        assert_eq!(project.commit_episode(&episode("No milk"), "again").unwrap(), 3);
+
+        let project = Project::open(&dir).unwrap();
+        let record = &project.manifest.episodes[&2];
+
+        assert_eq!(record.current, 3);
+        assert_eq!(record.versions.iter().map(|v| v.status).collect::<Vec<_>>(), [ReviewStatus::Accepted, ReviewStatus::Draft, ReviewStatus::Draft]);
+        assert_eq!(project.load_version(2, 2).unwrap().acts[0].scenes[0].summary, "No tea");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+    }
+
+    #[test]
+    fn old_directories_are_migrated() {
+
+        let dir = dir("migration");
+        std::fs::create_dir_all(dir.join("episodes")).unwrap();
+        episode("No coffee").save(&dir.join("episodes").join("002.json")).unwrap();
+        // an export written next to the episode by an older version
+        std::fs::write(dir.join("episodes").join("002.srt"), "1\n00:00:00,000 --> 00:00:01,000\nNo\n").unwrap();
+
+        let project = Project::open(&dir).unwrap();
+
+        assert_eq!(project.manifest.schema, SCHEMA);
+        assert_eq!(project.manifest.episodes[&2].current, 1);
+        assert!(project.load_version(2, 2).is_err());
+        assert_eq!(project.load_version(2, 1).unwrap(), episode("No coffee"));
+
+        // a directory of a newer version is not touched
+        std::fs::write(dir.join("project.json"), r#"{"schema": 99}"#).unwrap();
+
+        assert!(Project::open(&dir).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+    }
+
+    #[test]
+    fn generations_are_recorded_with_their_cost() {
+
+        let dir = dir("generations");
+        let project = Project::open(&dir).unwrap();
+        let recorder = Recorder::new(&EchoClient);
+
+        let mut request = PromptRequestInfo::new("Say this is a test".to_string(), ModelType::MostAccurate);
+        request.stop_token = Some(vec!["\n".to_string()]);
+
+        block_on(recorder.complete(request)).unwrap();
+        assert!(block_on(recorder.complete(PromptRequestInfo::new("fail".to_string(), ModelType::Fastest))).is_err());
+
+        project.record(Some(2), recorder.take()).unwrap();
+        project.record(None, Vec::new()).unwrap();
+
+        let generations = project.generations().unwrap();
+
+        assert_eq!(generations.len(), 2);
+        assert_eq!(generations[0].episode, Some(2));
+        assert_eq!(generations[0].request.model, "text-davinci-003");
+        assert_eq!(generations[0].request.stop, Some(vec!["\n".to_string()]));
+        assert_eq!(generations[0].response.as_deref(), Some("Say this is a test back"));
+        assert_eq!(generations[0].tokens, 11);
+        assert!(generations[1].error.is_some());
+        assert!((project.cost().unwrap() - 0.00022).abs() < 1e-6);
+        assert!(recorder.take().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+    }
+
+    #[test]
+    fn versions_are_compared_by_line() {
+
+        let changes = diff(&episode("No coffee"), &episode("No tea"));
+
+        assert!(changes.contains(&Change::Removed("## Diner: No coffee".to_string())));
+        assert!(changes.contains(&Change::Added("## Diner: No tea".to_string())));
+        assert_eq!(changes.iter().filter(|c| matches!(c, Change::Same(_))).count(), 2);
+
+        let text = super::Diff { changes }.to_string();
+
+        assert_eq!(text.lines().filter(|l| l.starts_with(['-', '+'])).count(), 2);
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Manifest
+//
+/// version of the layout written by this version of the producer
+pub const SCHEMA: u32 = 1;
+//
+/// file of the manifest in the directory of a series
+pub const MANIFEST_FILE: &str = "project.json";
+//
+/// directory of the current version of the episodes
+pub const EPISODES_DIR: &str = "episodes";
+//
+/// directory of every version of the episodes
+pub const VERSIONS_DIR: &str = "versions";
+//
+/// file of the generations
+pub const GENERATIONS_FILE: &str = "generations.jsonl";
+//
+//
+/// Where a version of an episode is in its review
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewStatus {
+
+    #[default]
+    Draft,
+    Accepted,
+    Rejected,
+
+}
+//
+impl ReviewStatus {
+
+    /// name used in the listings
+    pub fn to_str(&self) -> &str {
+
+        match self {
+
+            Self::Draft => "draft",
+            Self::Accepted => "accepted",
+            Self::Rejected => "rejected"
+
+        }
+
+    }
+
+}
+//
+//
+/// A saved version of an episode
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Version {
+
+    pub version:    u32,
+    /// date in RFC 3339
+    pub created:    String,
+    /// why the version was made, like 'generated' or 'act 1 scene 2 regenerated'
+    pub note:       String,
+    pub status:     ReviewStatus,
+
+}
+//
+//
+/// The versions of an episode
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct EpisodeRecord {
+
+    /// the version in the working copy
+    pub current:    u32,
+    pub versions:   Vec<Version>,
+
+}
+//
+//
+/// What the project holds
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Manifest {
+
+    /// version of the layout, 0 for the directories made before the projects
+    pub schema:     u32,
+    pub episodes:   BTreeMap<u32, EpisodeRecord>,
+
+}
+//
+//
+// a migration brings a project from the version of its index to the next one
+type Migration = fn(&mut Project) -> Result<(), EGeneral>;
+//
+const MIGRATIONS: [Migration; SCHEMA as usize] = [
+    //
+    // 0 -> 1: the episodes written before the projects become their first version
+    |project| {
+
+        let dir = project.dir.join(EPISODES_DIR);
+
+        let Ok(files) = std::fs::read_dir(&dir) else { return Ok(()) };
+
+        let mut numbers: Vec<u32> = files
+            .filter_map(|f| f.ok())
+            .map(|f| f.path())
+            .filter(|path| path.extension().is_some_and(|e| e == "json"))
+            .filter_map(|path| path.file_stem()?.to_str()?.parse().ok())
+            .collect();
+
+        numbers.sort();
+
+        for number in numbers {
+
+            let episode = Episode::load(&project.episode_path(number)).change_context(EGeneral::Project)?;
+
+            project.commit_episode(&episode, "written before the project")?;
+
+        }
+
+        Ok(())
+
+    },
+    //
+];
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Project
+//
+/// The files of a series
+pub struct Project {
+
+    dir:            PathBuf,
+    pub manifest:   Manifest,
+
+}
+//
+impl Project {
+    //
+    /// Open the directory of a series, migrating it if it was made by an older version
+    ///
+    /// # Arguments
+    ///
+    /// * 'dir' - the directory of the series, created if needed
+    ///
+    pub fn open(dir: &Path) -> Result<Self, EGeneral> {
+        //
+        let path = dir.join(MANIFEST_FILE);
+
+        let manifest: Manifest = match path.exists() {
+
+            true => serde_json::from_str(&read(&path)?)
+                .into_report()
+                .change_context(EGeneral::Project)
+                .attach_printable_lazy(|| format!("in '{}'", path.display()))?,
+
+            false => Manifest::default()
+
+        };
+        //
+        if manifest.schema > SCHEMA {
+
+            return Err(EGeneral::Project.as_report().attach_printable(format!(
+                "'{}' was made by a newer version, its schema is {} and only {} is known",
+                dir.display(), manifest.schema, SCHEMA
+            )));
+
+        }
+        //
+        let mut project = Self { dir: dir.to_path_buf(), manifest };
+
+        while project.manifest.schema < SCHEMA {
+
+            let from = project.manifest.schema;
+
+            MIGRATIONS[from as usize](&mut project)
+                .attach_printable_lazy(|| format!("while migrating '{}' from the schema {}", dir.display(), from))?;
+
+            project.manifest.schema = from + 1;
+            project.save()?;
+
+            cinfo!("'{}' migrated to the schema {}", dir.display(), from + 1);
+
+        }
+        //
+        Ok(project)
+        //
+    }
+    //
+    //
+    /// Directory of the series
+    pub fn dir(&self) -> &Path { &self.dir }
+    //
+    //
+    /// File of the current version of an episode
+    pub fn episode_path(&self, number: u32) -> PathBuf { self.dir.join(EPISODES_DIR).join(format!("{:03}.json", number)) }
+    //
+    //
+    /// File of a version of an episode
+    pub fn version_path(&self, number: u32, version: u32) -> PathBuf {
+
+        self.dir.join(VERSIONS_DIR).join(format!("{:03}", number)).join(format!("v{}.json", version))
+
+    }
+    //
+    //
+    /// Write the manifest
+    fn save(&self) -> Result<(), EGeneral> {
+
+        create_dir(&self.dir)?;
+
+        let content = serde_json::to_string_pretty(&self.manifest).into_report().change_context(EGeneral::Project)?;
+
+        write(&self.dir.join(MANIFEST_FILE), &content)
+
+    }
+    //
+    //
+    /// The versions of an episode, none if it was never saved
+    pub fn versions(&self, number: u32) -> &[Version] {
+
+        self.manifest.episodes.get(&number).map_or(&[], |r| r.versions.as_slice())
+
+    }
+    //
+    //
+    /// Save an episode as its current version, a new version is made only if it changed
+    ///
+    /// # Arguments
+    ///
+    /// * 'episode' - the episode, its number gives its place
+    /// * 'note'    - why the version is made
+    ///
+    pub fn commit_episode(&mut self, episode: &Episode, note: &str) -> Result<u32, EGeneral> {
+        //
+        let number = episode.number;
+        let record = self.manifest.episodes.get(&number).cloned().unwrap_or_default();
+        //
+        if record.current > 0 && self.load_version(number, record.current)? == *episode {
+
+            self.write_current(episode)?;
+            return Ok(record.current);
+
+        }
+        //
+        let version = record.versions.iter().map(|v| v.version).max().unwrap_or(0) + 1;
+        let path = self.version_path(number, version);
+
+        create_dir(path.parent().unwrap_or(&self.dir))?;
+        episode.save(&path).change_context(EGeneral::Project)?;
+
+        self.write_current(episode)?;
+        //
+        let record = self.manifest.episodes.entry(number).or_default();
+
+        record.current = version;
+        record.versions.push(Version { version, created: now(), note: note.to_string(), status: ReviewStatus::Draft });
+
+        self.save()?;
+        //
+        Ok(version)
+        //
+    }
+    //
+    //
+    /// Load a version of an episode
+    pub fn load_version(&self, number: u32, version: u32) -> Result<Episode, EGeneral> {
+
+        self.version(number, version)?;
+
+        Episode::load(&self.version_path(number, version)).change_context(EGeneral::Project)
+
+    }
+    //
+    //
+    /// Make a version of an episode its current one again
+    ///
+    /// # Arguments
+    ///
+    /// * 'number'  - the number of the episode
+    /// * 'version' - the version to restore
+    ///
+    pub fn rollback(&mut self, number: u32, version: u32) -> Result<Episode, EGeneral> {
+        //
+        let episode = self.load_version(number, version)?;
+
+        self.write_current(&episode)?;
+
+        if let Some(record) = self.manifest.episodes.get_mut(&number) {
+            record.current = version;
+        }
+
+        self.save()?;
+        //
+        Ok(episode)
+        //
+    }
+    //
+    //
+    /// Change the review status of a version
+    pub fn set_status(&mut self, number: u32, version: u32, status: ReviewStatus) -> Result<(), EGeneral> {
+
+        self.version(number, version)?;
+
+        let record = self.manifest.episodes.get_mut(&number).expect("the version exists");
+
+        record.versions.iter_mut().filter(|v| v.version == version).for_each(|v| v.status = status);
+
+        self.save()
+
+    }
+    //
+    //
+    /// Check that a version exists
+    fn version(&self, number: u32, version: u32) -> Result<&Version, EGeneral> {
+
+        self.versions(number).iter().find(|v| v.version == version).ok_or_else(|| {
+
+            EGeneral::Project.as_report().attach_printable(format!("episode {} has no version {}", number, version))
+
+        })
+
+    }
+    //
+    //
+    /// Write the working copy of an episode
+    fn write_current(&self, episode: &Episode) -> Result<(), EGeneral> {
+
+        let path = self.episode_path(episode.number);
+
+        create_dir(path.parent().unwrap_or(&self.dir))?;
+
+        episode.save(&path).change_context(EGeneral::Project)
+
+    }
+    //
+    //
+    /// Append generations to the file of the project
+    ///
+    /// # Arguments
+    ///
+    /// * 'episode'     - the episode they were made for, if any
+    /// * 'generations' - the generations of a recorder
+    ///
+    pub fn record(&self, episode: Option<u32>, generations: Vec<Generation>) -> Result<(), EGeneral> {
+        //
+        if generations.is_empty() {
+            return Ok(());
+        }
+        //
+        create_dir(&self.dir)?;
+
+        let path = self.dir.join(GENERATIONS_FILE);
+
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&path)
+            .into_report()
+            .change_context(EGeneral::Project)
+            .attach_printable_lazy(|| format!("unable to open '{}'", path.display()))?;
+        //
+        for generation in generations {
+
+            let line = serde_json::to_string(&Generation { episode, ..generation }).into_report().change_context(EGeneral::Project)?;
+
+            writeln!(file, "{}", line)
+                .into_report()
+                .change_context(EGeneral::Project)
+                .attach_printable_lazy(|| format!("unable to write '{}'", path.display()))?;
+
+        }
+        //
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Every generation recorded
+    pub fn generations(&self) -> Result<Vec<Generation>, EGeneral> {
+
+        let path = self.dir.join(GENERATIONS_FILE);
+
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        read(&path)?.lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| serde_json::from_str(l).into_report().change_context(EGeneral::Project).attach_printable_lazy(|| format!("in '{}'", path.display())))
+            .collect()
+
+    }
+    //
+    //
+    /// Price in dollars of every generation recorded
+    pub fn cost(&self) -> Result<f64, EGeneral> { Ok(self.generations()?.iter().map(|g| g.cost).sum()) }
+    //
+}
+//
+//
+fn read(path: &Path) -> Result<String, EGeneral> {
+
+    std::fs::read_to_string(path)
+        .into_report()
+        .change_context(EGeneral::Project)
+        .attach_printable_lazy(|| format!("unable to read '{}'", path.display()))
+
+}
+//
+//
+fn write(path: &Path, content: &str) -> Result<(), EGeneral> {
+
+    std::fs::write(path, content)
+        .into_report()
+        .change_context(EGeneral::Project)
+        .attach_printable_lazy(|| format!("unable to write '{}'", path.display()))
+
+}
+//
+//
+fn create_dir(dir: &Path) -> Result<(), EGeneral> {
+
+    std::fs::create_dir_all(dir)
+        .into_report()
+        .change_context(EGeneral::Project)
+        .attach_printable_lazy(|| format!("unable to create '{}'", dir.display()))
+
+}
+//
+//
+/// The date of a version or a generation, in RFC 3339 to the second
+fn now() -> String { chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false) }
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Generations
+//
+/// The parameters of a request as they were sent
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct RequestRecord {
+
+    pub model:              String,
+    pub prompt:             String,
+    pub max_tokens:         Option<u16>,
+    pub n:                  u16,
+    pub suffix:             Option<String>,
+    pub temperature:        Option<f32>,
+    pub top_p:              Option<f32>,
+    pub stop:               Option<Vec<String>>,
+    pub presence_penalty:   Option<f32>,
+    pub frequency_penalty:  Option<f32>,
+    /// the json object as text
+    pub logit_bias:         Option<String>,
+
+}
+//
+impl From<&PromptRequestInfo> for RequestRecord {
+
+    fn from(request: &PromptRequestInfo) -> Self {
+
+        Self {
+            model:              request.model.to_str().to_string(),
+            prompt:             request.prompt.clone(),
+            max_tokens:         request.max_word,
+            n:                  request.nb_response,
+            suffix:             request.suffix.clone(),
+            temperature:        request.temperature,
+            top_p:              request.top_p,
+            stop:               request.stop_token.clone(),
+            presence_penalty:   request.presence_penalty,
+            frequency_penalty:  request.frequency_penalty,
+            logit_bias:         request.logit_bias.as_ref().map(|b| b.dump()),
+        }
+
+    }
+
+}
+//
+//
+/// A request sent to the api and what came back
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Generation {
+
+    /// date in RFC 3339
+    pub time:       String,
+    pub episode:    Option<u32>,
+    pub request:    RequestRecord,
+    pub response:   Option<String>,
+    pub error:      Option<String>,
+    /// estimated tokens of the prompt and the response
+    pub tokens:     usize,
+    /// estimated price in dollars
+    pub cost:       f64,
+
+}
+//
+//
+/// A client that keeps every generation it sends, until they are given to the project
+pub struct Recorder<'a, C: CompletionClient> {
+
+    client:         &'a C,
+    generations:    Mutex<Vec<Generation>>,
+
+}
+//
+impl<'a, C: CompletionClient> Recorder<'a, C> {
+    //
+    /// Record the generations of a client
+    pub fn new(client: &'a C) -> Self { Self { client, generations: Mutex::new(Vec::new()) } }
+    //
+    //
+    /// The generations recorded since the last call
+    pub fn take(&self) -> Vec<Generation> { std::mem::take(&mut *self.generations.lock().expect("the lock is never poisoned")) }
+    //
+}
+//
+impl<C: CompletionClient> CompletionClient for Recorder<'_, C> {
+
+    async fn complete(&self, request: PromptRequestInfo) -> Result<String, EGeneral> {
+        //
+        let record = RequestRecord::from(&request);
+        let model = request.model;
+        let time = now();
+
+        let result = self.client.complete(request).await;
+        //
+        let response = result.as_ref().ok().cloned();
+        let tokens = estimate_tokens(&record.prompt) + response.as_deref().map_or(0, estimate_tokens);
+
+        let generation = Generation {
+            time,
+            episode:    None,
+            error:      result.as_ref().err().map(|e| format!("{:?}", e)),
+            response,
+            tokens,
+            cost:       model.cost(tokens),
+            request:    record,
+        };
+
+        self.generations.lock().expect("the lock is never poisoned").push(generation);
+        //
+        result
+        //
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Diff
+//
+/// lines kept around the changes in a diff
+const CONTEXT: usize = 2;
+//
+//
+/// A line of a diff
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+
+    Same(String),
+    Removed(String),
+    Added(String),
+
+}
+//
+//
+/// The changes between two versions of an episode
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Diff { pub changes: Vec<Change> }
+//
+impl Display for Diff {
+
+    /// The changed lines with a few lines around them, like a unified diff
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        //
+        let changed: Vec<usize> = (0..self.changes.len()).filter(|i| !matches!(self.changes[*i], Change::Same(_))).collect();
+        let near = |i: usize| changed.iter().any(|c| c.abs_diff(i) <= CONTEXT);
+
+        let mut skipped = false;
+        //
+        for (i, change) in self.changes.iter().enumerate() {
+
+            if !near(i) {
+
+                skipped = true;
+                continue;
+
+            }
+
+            if skipped {
+                writeln!(f, "...")?;
+            }
+
+            skipped = false;
+
+            match change {
+
+                Change::Same(line) => writeln!(f, "  {}", line)?,
+                Change::Removed(line) => writeln!(f, "- {}", line)?,
+                Change::Added(line) => writeln!(f, "+ {}", line)?,
+
+            }
+
+        }
+        //
+        Ok(())
+        //
+    }
+
+}
+//
+//
+/// The text of an episode compared between its versions
+fn lines(episode: &Episode) -> Vec<String> {
+    //
+    let mut lines = vec![format!("Episode {}: {}", episode.number, episode.logline)];
+
+    for (a, act) in episode.acts.iter().enumerate() {
+
+        lines.push(format!("# Act {}: {}", a + 1, act.summary));
+
+        for scene in &act.scenes {
+
+            lines.push(format!("## {}: {}", scene.heading, scene.summary));
+
+            for beat in &scene.beats {
+
+                lines.push(format!("### {}", beat.description));
+                lines.extend(beat.lines.iter().map(|l| l.to_string()));
+
+            }
+
+        }
+
+    }
+    //
+    lines
+    //
+}
+//
+//
+/// Compare two versions of an episode line by line
+pub fn diff(old: &Episode, new: &Episode) -> Vec<Change> {
+    //
+    let (old, new) = (lines(old), lines(new));
+    //
+    // longest common subsequence of the lines after each position
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+
+    for i in (0..old.len()).rev() {
+
+        for j in (0..new.len()).rev() {
+
+            common[i][j] = match old[i] == new[j] {
+
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1])
+
+            };
+
+        }
+
+    }
+    //
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < old.len() || j < new.len() {
+
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+
+            changes.push(Change::Same(old[i].clone()));
+            i += 1;
+            j += 1;
+
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+
+            changes.push(Change::Removed(old[i].clone()));
+            i += 1;
+
+        } else {
+
+            changes.push(Change::Added(new[j].clone()));
+            j += 1;
+
+        }
+
+    }
+    //
+    changes
+    //
+}
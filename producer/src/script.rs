@@ -0,0 +1,668 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Script
+//
+// Read the free text of a completion as typed script lines. The models rarely follow the
+// format exactly, so the parser accepts markdown, names on their own line, missing colons and
+// parentheticals anywhere around the name. When too much of the text can't be read, the
+// client is asked to write it again with the problem explained.
+//
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+
+use error_stack::{Result, ResultExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::series::{same_name, Series};
+use crate::template::TemplateSet;
+use crate::{cwarn, CompletionClient, EGeneral, PromptRequestInfo};
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use std::sync::Mutex;
+
+    use error_stack::Result;
+    use futures::executor::block_on;
+
+    use crate::episode::default_templates;
+    use crate::{CompletionClient, EGeneral, ModelType, PromptRequestInfo};
+    use super::{ScriptLine, ScriptParser};
+
+    fn parser() -> ScriptParser {
+
+        ScriptParser::new(vec!["Bolt".to_string(), "Nova Prime".to_string()])
+
+    }
+
+    fn dialogue(speaker: &str, parenthetical: Option<&str>, text: &str) -> ScriptLine {
+
+        ScriptLine::Dialogue {
+            speaker:        speaker.to_string(),
+            parenthetical:  parenthetical.map(|p| p.to_string()),
+            text:           text.to_string()
+        }
+
+    }
+
+    #[test]
+    fn sloppy_text_is_read() {
+
+        let script = parser().read("\
+            INT. DINER - NIGHT\n\
+            \n\
+            **BOLT:** Not my circuit.\n\
+            *Nova rolls in*\n\
+            - nova prime (whispering): We are out of coffee.\n\
+            BOLT - Then we close.\n\
+            NOVA PRIME\n\
+            (beat)\n\
+            Never.\n\
+            Gus: I'll drive.\n\
+            CUT TO:\n\
+            [[too long]]\n\
+        ");
+
+        assert_eq!(script.lines, [
+            ScriptLine::Heading { text: "INT. DINER - NIGHT".to_string() },
+            dialogue("Bolt", None, "Not my circuit."),
+            ScriptLine::Direction { text: "Nova rolls in".to_string() },
+            dialogue("Nova Prime", Some("whispering"), "We are out of coffee."),
+            dialogue("Bolt", None, "Then we close."),
+            dialogue("Nova Prime", Some("beat"), "Never."),
+            dialogue("Gus", None, "I'll drive."),
+            ScriptLine::Transition { text: "CUT TO:".to_string() },
+            ScriptLine::Note { text: "too long".to_string() },
+        ]);
+
+        assert!(script.unknown_speakers.contains("Gus"));
+        assert!(script.unparsed.is_empty());
+        assert!(script.problem().is_none());
+
+    }
+
+    #[test]
+    fn accented_names_are_matched_in_any_case() {
+
+        let parser = ScriptParser::new(vec!["Zoé".to_string(), "Émile".to_string()]);
+
+        let script = parser.read("ZOÉ: Bonjour.\nÉMILE - Salut.\nzoé (laughing) Encore.");
+
+        assert_eq!(script.lines, [
+            dialogue("Zoé", None, "Bonjour."),
+            dialogue("Émile", None, "Salut."),
+            dialogue("Zoé", Some("laughing"), "Encore."),
+        ]);
+
+        assert!(script.unknown_speakers.is_empty());
+
+    }
+
+    #[test]
+    fn prose_is_a_problem() {
+
+        let parser = parser();
+
+        let script = parser.read("Here is the dialogue you asked:\nBolt is angry and leaves.\nBOLT: Fine.\nThe end of the scene is sad.");
+
+        assert_eq!(script.unparsed.len(), 3);
+        assert_eq!(script.unparsed[0].line, 1);
+        assert!(script.problem().unwrap().contains("3 of 4 lines"));
+
+        assert!(parser.read("Just some prose.").problem().unwrap().contains("no dialogue"));
+        assert!(format!("{:?}", parser.parse("").unwrap_err()).contains("no dialogue"));
+
+    }
+
+    /// give the answers in order
+    struct ReplayClient { answers: Mutex<Vec<&'static str>>, prompts: Mutex<Vec<String>> }
+
+    impl ReplayClient {
+
+        fn new(answers: &[&'static str]) -> Self {
+
+            Self { answers: Mutex::new(answers.iter().rev().copied().collect()), prompts: Mutex::new(Vec::new()) }
+
+        }
+
+    }
+
+    impl CompletionClient for ReplayClient {
+
+        async fn complete(&self, request: PromptRequestInfo) -> Result<String, EGeneral> {
+
+            self.prompts.lock().unwrap().push(request.prompt);
+
+            Ok(self.answers.lock().unwrap().pop().unwrap_or("nothing").to_string())
+
+        }
+
+    }
+
+    #[test]
+    fn invalid_completion_is_corrected() {
+
+        let client = ReplayClient::new(&["Sure! Bolt says hello.", "BOLT: Hello."]);
+        let request = PromptRequestInfo::new("Dialogue:".to_string(), ModelType::Fastest);
+
+        let (text, script) = block_on(parser().complete(&client, &default_templates(), request.clone())).unwrap();
+
+        assert_eq!(text, "BOLT: Hello.");
+        assert_eq!(script.lines, [dialogue("Bolt", None, "Hello.")]);
+
+        let prompts = client.prompts.lock().unwrap();
+
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].starts_with("Dialogue: Sure! Bolt says hello."));
+        assert!(prompts[1].contains("no dialogue line"));
+
+        let client = ReplayClient::new(&[]);
+        let error = block_on(parser().with_corrections(1).complete(&client, &default_templates(), request)).unwrap_err();
+
+        assert!(format!("{:?}", error).contains("still not a script after 1 correction"));
+        assert_eq!(client.prompts.lock().unwrap().len(), 2);
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Script lines
+//
+/// A line of a script
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScriptLine {
+
+    /// location and time like 'INT. DINER - NIGHT'
+    Heading { text: String },
+
+    /// what happens on stage
+    Direction { text: String },
+
+    /// a reply, the parenthetical tells how it is said
+    Dialogue { speaker: String, parenthetical: Option<String>, text: String },
+
+    /// change of scene like 'CUT TO:'
+    Transition { text: String },
+
+    /// a note of the writers, not part of the show
+    Note { text: String },
+
+}
+//
+impl Display for ScriptLine {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+
+        match self {
+
+            Self::Heading { text } => write!(f, "{}", text),
+            Self::Direction { text } => write!(f, "[{}]", text),
+            Self::Dialogue { speaker, parenthetical: Some(p), text } => write!(f, "{} ({}): {}", speaker.to_uppercase(), p, text),
+            Self::Dialogue { speaker, parenthetical: None, text } => write!(f, "{}: {}", speaker.to_uppercase(), text),
+            Self::Transition { text } => write!(f, "{}", text),
+            Self::Note { text } => write!(f, "[[{}]]", text)
+
+        }
+
+    }
+
+}
+//
+//
+/// A line of the completion that is not a script line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unparsed {
+
+    /// number of the line, starting at 1
+    pub line:   usize,
+    pub text:   String,
+
+}
+//
+//
+/// The lines read from a completion and what was wrong with it
+#[derive(Clone, Debug, Default)]
+pub struct ParsedScript {
+
+    pub lines:              Vec<ScriptLine>,
+    /// speakers that are not characters of the series, as written in the completion
+    pub unknown_speakers:   BTreeSet<String>,
+    pub unparsed:           Vec<Unparsed>,
+    /// number of non empty lines of the completion
+    pub total:              usize,
+
+}
+//
+impl ParsedScript {
+    //
+    /// Tell why the completion can't be used, none if it can
+    ///
+    /// A completion is rejected when it has no dialogue at all or when more than a third of its
+    /// lines are not script lines
+    pub fn problem(&self) -> Option<String> {
+        //
+        if !self.lines.iter().any(|l| matches!(l, ScriptLine::Dialogue { .. })) {
+            return Some("there is no dialogue line".to_string());
+        }
+        //
+        if self.unparsed.len() * 3 > self.total {
+
+            let first = self.unparsed.iter()
+                .take(3)
+                .map(|u| format!("'{}'", u.text))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            return Some(format!("{} of {} lines are not script lines, like {}", self.unparsed.len(), self.total, first));
+
+        }
+        //
+        None
+        //
+    }
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Parser
+//
+/// default number of corrections asked before giving up
+pub const DEFAULT_CORRECTIONS: usize = 2;
+//
+/// longest name accepted for an unknown speaker
+const MAX_NAME_LENGTH: usize = 32;
+const MAX_NAME_WORDS: usize = 4;
+//
+//
+/// Read the completions as script lines
+pub struct ScriptParser {
+
+    characters:     Vec<String>,
+    corrections:    usize,
+
+}
+//
+impl ScriptParser {
+    //
+    /// Create a parser knowing the names of the characters
+    pub fn new(mut characters: Vec<String>) -> Self {
+
+        // the longest names first so 'Nova Prime' is found before 'Nova'
+        characters.sort_by_key(|c| std::cmp::Reverse(c.len()));
+
+        Self { characters, corrections: DEFAULT_CORRECTIONS }
+
+    }
+    //
+    /// Create a parser knowing the characters of a series
+    pub fn for_series(series: &Series) -> Self {
+
+        Self::new(series.characters.iter().map(|c| c.name.clone()).collect())
+
+    }
+    //
+    /// Change the number of corrections asked before giving up
+    pub fn with_corrections(mut self, corrections: usize) -> Self {
+
+        self.corrections = corrections;
+        self
+
+    }
+    //
+    //
+    /// Read a completion, fail if it can't be used
+    pub fn parse(&self, text: &str) -> Result<ParsedScript, EGeneral> {
+
+        let script = self.read(text);
+
+        match script.problem() {
+
+            Some(problem) => Err(EGeneral::Script.as_report().attach_printable(problem)),
+            None => Ok(script)
+
+        }
+
+    }
+    //
+    //
+    /// Read every line that looks like a script line, the other ones are kept in 'unparsed'
+    pub fn read(&self, text: &str) -> ParsedScript {
+        //
+        let mut script = ParsedScript::default();
+        // a name alone on its line, its reply is on the next lines
+        let mut pending: Option<(String, Option<String>, usize)> = None;
+        //
+        for (n, raw) in text.lines().enumerate() {
+
+            let line = clean_line(raw);
+            let line = line.as_str();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            script.total += 1;
+
+            if let Some(note) = line.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+
+                script.lines.push(ScriptLine::Note { text: note.trim().to_string() });
+
+            } else if is_heading(line) {
+
+                script.lines.push(ScriptLine::Heading { text: line.to_string() });
+
+            } else if let Some(inner) = enclosed(line) {
+
+                match &mut pending {
+
+                    Some((_, parenthetical @ None, _)) if line.starts_with('(') => *parenthetical = Some(inner.to_string()),
+                    _ => script.lines.push(ScriptLine::Direction { text: inner.to_string() })
+
+                }
+
+            } else if is_transition(line) {
+
+                script.lines.push(ScriptLine::Transition { text: line.to_string() });
+
+            } else if let Some((name, parenthetical, text)) = self.split_dialogue(line) {
+
+                self.push_dialogue(&mut script, name, parenthetical, text);
+                pending = None;
+
+            } else if let Some((name, parenthetical)) = self.speaker_alone(line) {
+
+                pending = Some((name.to_string(), parenthetical.map(|p| p.to_string()), n + 1));
+
+            } else if let Some((name, parenthetical, _)) = pending.take() {
+
+                self.push_dialogue(&mut script, &name, parenthetical.as_deref(), line);
+
+            } else {
+
+                script.unparsed.push(Unparsed { line: n + 1, text: raw.trim().to_string() });
+
+            }
+
+        }
+        //
+        if let Some((name, _, line)) = pending {
+            script.unparsed.push(Unparsed { line, text: name });
+        }
+        //
+        script
+        //
+    }
+    //
+    //
+    /// Send a request and read its completion, ask for a correction while it can't be used
+    ///
+    /// # Arguments
+    ///
+    /// * 'client'    - what completes the prompts
+    /// * 'templates' - where the 'correction' template is found
+    /// * 'request'   - the prompt asking for a script
+    ///
+    pub async fn complete<C: CompletionClient>(
+        &self,
+        client: &C,
+        templates: &TemplateSet,
+        request: PromptRequestInfo
+
+    ) -> Result<(String, ParsedScript), EGeneral> {
+        //
+        let mut text = client.complete(request.clone()).await.change_context(EGeneral::Script)?;
+        //
+        for attempt in 0.. {
+
+            let script = self.read(&text);
+
+            let Some(problem) = script.problem() else {
+
+                if !script.unknown_speakers.is_empty() {
+                    cwarn!(category: "script", "unknown speakers in the script: {:?}", script.unknown_speakers);
+                }
+
+                return Ok((text, script));
+
+            };
+
+            if attempt == self.corrections {
+
+                return Err(EGeneral::Script.as_report()
+                    .attach_printable(problem)
+                    .attach_printable(format!(
+                        "still not a script after {} correction{}", attempt, if attempt == 1 { "" } else { "s" }
+                    )));
+
+            }
+
+            cwarn!(category: "script", "the completion is not a script ({}), asking for a correction", problem);
+
+            let context = json!({ "prompt": request.prompt, "completion": text.trim(), "problem": problem });
+
+            let mut correction = request.clone();
+            correction.prompt = templates.render("correction", &context).change_context(EGeneral::Script)?;
+
+            text = client.complete(correction).await.change_context(EGeneral::Script)?;
+
+        }
+        //
+        unreachable!("the loop only ends by returning")
+        //
+    }
+    //
+    //
+    /// Add a reply, the speaker gets the name of the character when it is known
+    fn push_dialogue(&self, script: &mut ParsedScript, name: &str, parenthetical: Option<&str>, text: &str) {
+
+        let speaker = match self.character(name) {
+
+            Some(character) => character.to_string(),
+
+            None => {
+
+                script.unknown_speakers.insert(name.to_string());
+                name.to_string()
+
+            }
+
+        };
+
+        script.lines.push(ScriptLine::Dialogue {
+            speaker,
+            parenthetical:  parenthetical.map(|p| p.to_string()),
+            text:           text.to_string()
+        });
+
+    }
+    //
+    //
+    /// Name of the character with this name, whatever its case
+    fn character(&self, name: &str) -> Option<&str> {
+
+        self.characters.iter().find(|c| same_name(c, name)).map(|c| c.as_str())
+
+    }
+    //
+    //
+    /// Split a reply into the speaker, the parenthetical and the text
+    ///
+    /// The name ends with a colon, or with a dash or a space when it is the name of a character
+    fn split_dialogue<'l>(&self, line: &'l str) -> Option<(&'l str, Option<&'l str>, &'l str)> {
+        //
+        if let Some((head, text)) = line.split_once(':') {
+
+            let (name, before) = split_parenthetical(head.trim());
+            let (after, text) = leading_parenthetical(text.trim());
+
+            if (is_name(name) || self.character(name).is_some()) && !text.is_empty() {
+                return Some((name, before.or(after), text));
+            }
+
+        }
+        //
+        // missing colon
+        for character in &self.characters {
+
+            // the case of a letter can change its length in bytes, the characters are counted
+            let end = line.char_indices().nth(character.chars().count()).map_or(line.len(), |(i, _)| i);
+            let name = &line[..end];
+
+            if !same_name(name, character) || !line[name.len()..].starts_with([' ', '(']) {
+                continue;
+            }
+
+            let (parenthetical, rest) = leading_parenthetical(line[name.len()..].trim());
+
+            let dashed = rest.strip_prefix(['-', '—', '–']).map(|r| r.trim());
+            let shouted = name.chars().all(|c| !c.is_lowercase());
+
+            let text = match dashed {
+
+                Some(text) => text,
+                None if shouted || parenthetical.is_some() => rest,
+                None => continue
+
+            };
+
+            if !text.is_empty() {
+                return Some((name, parenthetical, text));
+            }
+
+        }
+        //
+        None
+        //
+    }
+    //
+    //
+    /// A name alone on its line, in capitals or the name of a character
+    fn speaker_alone<'l>(&self, line: &'l str) -> Option<(&'l str, Option<&'l str>)> {
+
+        let (name, parenthetical) = split_parenthetical(line);
+
+        let shouted = name.chars().any(|c| c.is_alphabetic()) && !name.chars().any(|c| c.is_lowercase());
+
+        match is_name(name) && (shouted || self.character(name).is_some()) {
+
+            true => Some((name, parenthetical)),
+            false => None
+
+        }
+
+    }
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Lines
+//
+/// Remove the markdown around a line, bold names like '**BOLT:**' lose their stars
+fn clean_line(line: &str) -> String {
+    //
+    let line = line.trim().trim_start_matches(['#', '>']).trim_start();
+
+    let line = match line.strip_prefix(['-', '*', '•']) {
+
+        Some(rest) if rest.starts_with(' ') => rest.trim_start(),
+        _ => line
+
+    };
+    //
+    line.replace("**", "").replace("__", "").trim().to_string()
+    //
+}
+//
+//
+/// Check if a line is a scene heading, it starts with 'INT', 'EXT', 'EST' or 'I/E' and a dot or a space
+pub(crate) fn is_heading(line: &str) -> bool {
+
+    let upper = line.get(..9).unwrap_or(line).to_uppercase();
+
+    ["INT./EXT", "INT/EXT", "I/E", "INT", "EXT", "EST"].iter().any(|p| {
+        upper.strip_prefix(p).is_some_and(|rest| rest.starts_with(['.', ' ']))
+    })
+
+}
+//
+//
+/// Check if a line is a transition like 'CUT TO:'
+pub(crate) fn is_transition(line: &str) -> bool {
+
+    line.ends_with("TO:") && !line.chars().any(|c| c.is_lowercase())
+
+}
+//
+//
+/// Text inside brackets, parentheses or a single emphasis
+fn enclosed(line: &str) -> Option<&str> {
+
+    let pairs = [('[', ']'), ('(', ')'), ('*', '*'), ('_', '_')];
+
+    pairs.iter()
+        .find(|(open, close)| line.len() > 2 && line.starts_with(*open) && line.ends_with(*close))
+        .map(|_| line[1..line.len() - 1].trim())
+        .filter(|inner| !inner.contains(['[', ']']) || inner.starts_with('('))
+
+}
+//
+//
+/// Split 'BOLT (whispering)' into the name and the parenthetical
+fn split_parenthetical(head: &str) -> (&str, Option<&str>) {
+
+    match (head.find(['(', '[']), head.ends_with([')', ']'])) {
+
+        (Some(start), true) if start > 0 => (head[..start].trim(), Some(head[start + 1..head.len() - 1].trim())),
+        _ => (head, None)
+
+    }
+
+}
+//
+//
+/// Split '(whispering) text' into the parenthetical and the text
+fn leading_parenthetical(text: &str) -> (Option<&str>, &str) {
+
+    let close = match text.chars().next() {
+
+        Some('(') => ')',
+        Some('[') => ']',
+        _ => return (None, text)
+
+    };
+
+    match text.find(close) {
+
+        Some(end) if !text[end + 1..].trim().is_empty() => (Some(text[1..end].trim()), text[end + 1..].trim()),
+        _ => (None, text)
+
+    }
+
+}
+//
+//
+/// Check if a text can be the name of a speaker
+fn is_name(name: &str) -> bool {
+    //
+    let words: Vec<&str> = name.split_whitespace().collect();
+
+    !words.is_empty()
+        && words.len() <= MAX_NAME_WORDS
+        && name.len() <= MAX_NAME_LENGTH
+        && name.starts_with(|c: char| c.is_alphabetic())
+        && words.iter().all(|w| w.starts_with(|c: char| c.is_uppercase() || c.is_ascii_digit()))
+        && name.chars().all(|c| c.is_alphanumeric() || matches!(c, ' ' | '.' | '\'' | '-'))
+    //
+}
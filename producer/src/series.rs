@@ -0,0 +1,397 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Series bible
+//
+// Everything the writers need to know about the show: what it is about, where it happens, its
+// tone and its characters. Every prompt is built from it so the episodes stay consistent.
+// The bible is stored as TOML, YAML or JSON depending on the extension of the file.
+//
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use serde::{Deserialize, Serialize};
+
+use crate::EGeneral;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use super::{Character, Series, SeriesFormat};
+
+    fn series() -> Series {
+
+        let mut series = Series::new("The Night Shift", "Two robots run a diner on the moon");
+        series.setting = "A diner in a crater".to_string();
+        series.tone = "Deadpan".to_string();
+        series.running_gags.push("The coffee machine is always broken".to_string());
+        series.banned_topics.push("politics".to_string());
+
+        let mut bolt = Character::new("Bolt");
+        bolt.personality = "Grumpy cook".to_string();
+        bolt.catchphrases.push("Not my circuit.".to_string());
+        bolt.relationships.insert("Nova".to_string(), "reluctant friend".to_string());
+
+        series.characters.push(bolt);
+        series.characters.push(Character::new("Nova"));
+
+        series
+
+    }
+
+    #[test]
+    fn round_trip_in_every_format() {
+
+        for format in [SeriesFormat::Toml, SeriesFormat::Yaml, SeriesFormat::Json] {
+
+            let text = series().to_string(format).unwrap();
+
+            assert_eq!(Series::parse(&text, format).unwrap(), series(), "{:?}", format);
+
+        }
+
+    }
+
+    #[test]
+    fn minimal_toml_bible() {
+
+        let series = Series::parse(r#"
+            title = "Pilot"
+            premise = "A show about nothing"
+
+            [[characters]]
+            name = "Ann"
+            catchphrases = ["Well, well."]
+        "#, SeriesFormat::Toml).unwrap();
+
+        assert_eq!(series.character("ann").unwrap().catchphrases, ["Well, well."]);
+        assert!(series.running_gags.is_empty());
+
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+
+        let mut series = series();
+        series.premise = " ".to_string();
+        series.characters.push(Character::new("bolt"));
+        series.characters[1].relationships.insert("Ghost".to_string(), "haunted by".to_string());
+        series.running_gags.push(String::new());
+
+        let text = format!("{:?}", series.validate().unwrap_err());
+
+        assert!(text.contains("the premise is empty"));
+        assert!(text.contains("the character 'bolt' is defined more than once"));
+        assert!(text.contains("'Nova' has a relationship with 'Ghost'"));
+        assert!(text.contains("running gag 2 is empty"));
+
+    }
+
+    #[test]
+    fn format_from_extension() {
+
+        assert_eq!(SeriesFormat::from_path("a/bible.yml".as_ref()).unwrap(), SeriesFormat::Yaml);
+        assert_eq!(SeriesFormat::from_path("bible.JSON".as_ref()).unwrap(), SeriesFormat::Json);
+        assert!(SeriesFormat::from_path("bible.txt".as_ref()).is_err());
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Format
+//
+/// File formats of the bible
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeriesFormat {
+
+    Toml,
+    Yaml,
+    Json,
+
+}
+//
+impl SeriesFormat {
+    //
+    /// Find the format from the extension of the file
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - the bible file
+    ///
+    pub fn from_path(path: &Path) -> Result<Self, EGeneral> {
+
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        match extension.as_deref() {
+
+            Some("toml") => Ok(Self::Toml),
+            Some("yaml") | Some("yml") => Ok(Self::Yaml),
+            Some("json") => Ok(Self::Json),
+
+            _ => Err(
+                EGeneral::Series
+                    .as_report()
+                    .attach_printable(format!("'{}' is not a .toml, .yaml or .json file", path.display()))
+            )
+
+        }
+
+    }
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Character
+//
+/// A recurring character of the show
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Character {
+
+    pub name:           String,
+    pub personality:    String,
+    /// how the character speaks, also used to pick a voice for the audio
+    pub voice:          String,
+    pub catchphrases:   Vec<String>,
+    /// name of another character and what they are to this one
+    pub relationships:  BTreeMap<String, String>,
+
+}
+//
+impl Character {
+    //
+    /// Create a character with only a name
+    pub fn new(name: &str) -> Self { Self { name: name.to_string(), ..Self::default() } }
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Series
+//
+/// The bible of a series
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Series {
+
+    pub title:          String,
+    pub premise:        String,
+    pub setting:        String,
+    pub tone:           String,
+    pub characters:     Vec<Character>,
+    pub running_gags:   Vec<String>,
+    /// subjects the writers must never bring up
+    pub banned_topics:  Vec<String>,
+
+}
+//
+impl Series {
+    //
+    /// Create a series without any character
+    ///
+    /// # Arguments
+    ///
+    /// * 'title'   - name of the show
+    /// * 'premise' - what the show is about
+    ///
+    pub fn new(title: &str, premise: &str) -> Self {
+
+        Self { title: title.to_string(), premise: premise.to_string(), ..Self::default() }
+
+    }
+    //
+    //
+    /// Read and validate a bible, the format depends on the extension of the file
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - the bible file
+    ///
+    pub fn load(path: &Path) -> Result<Self, EGeneral> {
+        //
+        let format = SeriesFormat::from_path(path)?;
+
+        let content = std::fs::read_to_string(path)
+            .into_report()
+            .change_context(EGeneral::Series)
+            .attach_printable_lazy(|| format!("unable to read '{}'", path.display()))?;
+        //
+        let series = Self::parse(&content, format)
+            .attach_printable_lazy(|| format!("in '{}'", path.display()))?;
+
+        series.validate().attach_printable_lazy(|| format!("in '{}'", path.display()))?;
+
+        Ok(series)
+        //
+    }
+    //
+    //
+    /// Validate and write the bible, the format depends on the extension of the file
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - the bible file, replaced if it exists
+    ///
+    pub fn save(&self, path: &Path) -> Result<(), EGeneral> {
+
+        self.validate()?;
+
+        let content = self.to_string(SeriesFormat::from_path(path)?)?;
+
+        std::fs::write(path, content)
+            .into_report()
+            .change_context(EGeneral::Series)
+            .attach_printable_lazy(|| format!("unable to write '{}'", path.display()))
+
+    }
+    //
+    //
+    /// Parse a bible without validating it
+    ///
+    /// # Arguments
+    ///
+    /// * 'content' - text of the bible
+    /// * 'format'  - format of the text
+    ///
+    pub fn parse(content: &str, format: SeriesFormat) -> Result<Self, EGeneral> {
+
+        match format {
+
+            SeriesFormat::Toml => toml::from_str(content).into_report().change_context(EGeneral::Series),
+            SeriesFormat::Yaml => serde_yaml::from_str(content).into_report().change_context(EGeneral::Series),
+            SeriesFormat::Json => serde_json::from_str(content).into_report().change_context(EGeneral::Series)
+
+        }
+
+    }
+    //
+    //
+    /// Write the bible as text
+    ///
+    /// # Arguments
+    ///
+    /// * 'format' - format of the text
+    ///
+    pub fn to_string(&self, format: SeriesFormat) -> Result<String, EGeneral> {
+
+        match format {
+
+            SeriesFormat::Toml => toml::to_string_pretty(self).into_report().change_context(EGeneral::Series),
+            SeriesFormat::Yaml => serde_yaml::to_string(self).into_report().change_context(EGeneral::Series),
+            SeriesFormat::Json => serde_json::to_string_pretty(self).into_report().change_context(EGeneral::Series)
+
+        }
+
+    }
+    //
+    //
+    /// Find a character from its name, the case is ignored
+    pub fn character(&self, name: &str) -> Option<&Character> {
+
+        self.characters.iter().find(|c| same_name(&c.name, name.trim()))
+
+    }
+    //
+    //
+    /// Check that the bible can be used to write an episode, every problem found is attached
+    /// to the report
+    pub fn validate(&self) -> Result<(), EGeneral> {
+        //
+        let mut problems: Vec<String> = Vec::new();
+        //
+        if self.title.trim().is_empty() {
+            problems.push("the title is empty".to_string());
+        }
+
+        if self.premise.trim().is_empty() {
+            problems.push("the premise is empty".to_string());
+        }
+        //
+        for (i, character) in self.characters.iter().enumerate() {
+
+            let name = character.name.trim();
+
+            if name.is_empty() {
+
+                problems.push(format!("character {} has no name", i + 1));
+                continue;
+
+            }
+
+            if self.characters[..i].iter().any(|c| same_name(c.name.trim(), name)) {
+                problems.push(format!("the character '{}' is defined more than once", name));
+            }
+
+            if character.catchphrases.iter().any(|c| c.trim().is_empty()) {
+                problems.push(format!("'{}' has an empty catchphrase", name));
+            }
+
+            for other in character.relationships.keys() {
+
+                if same_name(other.trim(), name) {
+                    problems.push(format!("'{}' has a relationship with itself", name));
+                } else if self.character(other).is_none() {
+                    problems.push(format!("'{}' has a relationship with '{}' who is not a character", name, other));
+                }
+
+            }
+
+        }
+        //
+        empty_entries(&self.running_gags, "running gag", &mut problems);
+        empty_entries(&self.banned_topics, "banned topic", &mut problems);
+        //
+        match problems.is_empty() {
+
+            true => Ok(()),
+
+            false => Err(problems.into_iter().fold(
+                Report::new(EGeneral::Series),
+                |report, problem| report.attach_printable(problem)
+            ))
+
+        }
+        //
+    }
+    //
+}
+//
+//
+/// Report the empty entries of a list
+///
+/// # Arguments
+///
+/// * 'entries'  - the list to check
+/// * 'what'     - name of an entry in the problem
+/// * 'problems' - where the problems are added
+///
+fn empty_entries(entries: &[String], what: &str, problems: &mut Vec<String>) {
+
+    for (i, entry) in entries.iter().enumerate() {
+
+        if entry.trim().is_empty() {
+            problems.push(format!("{} {} is empty", what, i + 1));
+        }
+
+    }
+
+}
+//
+//
+/// Check that two names are the same whatever their case, accented letters included
+pub(crate) fn same_name(a: &str, b: &str) -> bool {
+
+    a.chars().flat_map(char::to_lowercase).eq(b.chars().flat_map(char::to_lowercase))
+
+}
@@ -0,0 +1,457 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Subtitle
+//
+// Subtitles of the dialogue of a timeline, written as SRT or WebVTT. A reply too long for a
+// cue is split at the word boundaries and its time shared between the parts. A cue shown too
+// briefly to be read is held longer when the next one leaves the room for it.
+//
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::cwarn;
+use crate::script::ScriptLine;
+use crate::timeline::Timeline;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use crate::timeline::{Pacing, Timeline};
+    use crate::timeline::test::episode;
+    use super::{subtitles, to_srt, to_vtt, wrap, Position, SubtitleOptions};
+
+    fn ms(ms: u64) -> Duration { Duration::from_millis(ms) }
+
+    #[test]
+    fn long_replies_are_wrapped_and_split() {
+
+        assert_eq!(wrap("one two three four five", 9), ["one two", "three", "four five"]);
+        assert_eq!(wrap("unbreakable", 4), ["unbreakable"]);
+
+        let options = SubtitleOptions { max_line_length: 12, ..SubtitleOptions::default() };
+        let timeline = Timeline::build(&episode(), &Pacing::default(), &HashMap::new());
+
+        let cues = subtitles(&timeline, &options);
+
+        // 'BOLT: one two three four five six seven eight' is too long for two lines of 12
+        assert_eq!(cues[0].lines, ["BOLT: one", "two"]);
+        assert_eq!(cues[1].lines, ["three four", "five"]);
+        assert_eq!(cues[2].lines, ["six seven", "eight"]);
+        assert_eq!(cues[0].start, timeline.lines[0].start);
+        assert_eq!(cues[2].end, timeline.lines[0].end);
+        assert!(cues[0].end == cues[1].start && cues[1].end == cues[2].start);
+
+    }
+
+    #[test]
+    fn short_cues_are_held_for_reading() {
+
+        let options = SubtitleOptions { max_chars_per_second: 0.5, ..SubtitleOptions::default() };
+        let timeline = Timeline::build(&episode(), &Pacing::default(), &HashMap::new());
+
+        let cues = subtitles(&timeline, &options);
+        let hi = cues.iter().find(|c| c.text.contains("Hi.")).unwrap();
+        let bye = cues.iter().find(|c| c.text.contains("Bye.")).unwrap();
+
+        // 'Hi.' needs 6s but the next reply starts sooner
+        assert_eq!(hi.end, bye.start - options.min_gap);
+        assert!(hi.end - hi.start > ms(1000));
+
+    }
+
+    #[test]
+    fn srt_and_vtt_output() {
+
+        let timeline = Timeline::build(&episode(), &Pacing::default(), &HashMap::new());
+        let cues = subtitles(&timeline, &SubtitleOptions::default());
+
+        assert_eq!(cues[0].position, Position::Left);
+        assert_eq!(cues[1].position, Position::Right);
+        assert_eq!(cues[2].position, Position::Center);
+
+        let srt = to_srt(&cues);
+
+        assert!(srt.starts_with("1\n00:00:01,500 --> 00:00:04,500\n{\\an1}BOLT: one two three four five six seven\neight\n\n2\n"));
+        assert!(srt.ends_with("\nBOLT: Bye.\n"));
+
+        let vtt = to_vtt(&cues);
+
+        assert!(vtt.starts_with("WEBVTT\n\n1\n00:00:01.500 --> 00:00:04.500 align:left position:10%\n<v Bolt>one two three four five six seven\neight\n"));
+        assert!(vtt.contains("align:right position:90%\n<v Nova>Hi.\n"));
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Subtitles
+//
+/// Where a cue is shown, the speakers of a dialogue are on each side of the screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Position {
+
+    Left,
+    Center,
+    Right,
+
+}
+//
+//
+/// Limits of the subtitles
+#[derive(Clone, Debug)]
+pub struct SubtitleOptions {
+
+    /// characters per line
+    pub max_line_length:        usize,
+    pub max_lines:              usize,
+    /// fastest reading speed, a quicker cue is held longer when possible
+    pub max_chars_per_second:   f64,
+    /// time between two cues when a cue is held
+    pub min_gap:                Duration,
+    /// start the replies with the name of the speaker in SRT, WebVTT uses voice tags
+    pub speaker_labels:         bool,
+
+}
+//
+impl Default for SubtitleOptions {
+
+    fn default() -> Self {
+
+        Self {
+            max_line_length:        42,
+            max_lines:              2,
+            max_chars_per_second:   17.0,
+            min_gap:                Duration::from_millis(80),
+            speaker_labels:         true,
+        }
+
+    }
+
+}
+//
+//
+/// A subtitle shown on screen
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subtitle {
+
+    pub start:      Duration,
+    pub end:        Duration,
+    pub speaker:    String,
+    /// the text without the label
+    pub text:       String,
+    /// the lines shown in SRT, with the label
+    pub lines:      Vec<String>,
+    pub position:   Position,
+
+}
+//
+//
+/// Build the subtitles of every reply of a timeline
+///
+/// # Arguments
+///
+/// * 'timeline' - when the replies are played
+/// * 'options'  - the limits of the subtitles
+///
+pub fn subtitles(timeline: &Timeline, options: &SubtitleOptions) -> Vec<Subtitle> {
+    //
+    let positions = positions(timeline);
+    let mut cues: Vec<Subtitle> = Vec::new();
+    //
+    for timed in &timeline.lines {
+
+        let ScriptLine::Dialogue { speaker, text, .. } = &timed.line else { continue };
+
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        let labelled = match options.speaker_labels {
+
+            true => format!("{}: {}", speaker.to_uppercase(), text),
+            false => text.clone()
+
+        };
+
+        let label = labelled.len() - text.len();
+        let chunks = split(&labelled, options);
+        //
+        // the time of the reply is shared by the number of characters of each part
+        let total: usize = chunks.iter().flatten().map(|l| l.len()).sum();
+        let length = timed.end - timed.start;
+        let mut done = 0;
+
+        for (i, chunk) in chunks.iter().enumerate() {
+
+            let chars: usize = chunk.iter().map(|l| l.len()).sum();
+
+            let start = timed.start + length.mul_f64(done as f64 / total.max(1) as f64);
+            done += chars;
+            let end = timed.start + length.mul_f64(done as f64 / total.max(1) as f64);
+
+            let mut text = chunk.join("\n");
+
+            if i == 0 {
+                text.drain(..label);
+            }
+
+            cues.push(Subtitle {
+                start,
+                end,
+                speaker:    speaker.clone(),
+                text,
+                lines:      chunk.to_vec(),
+                position:   positions.get(&(timed.key.act, timed.key.scene, speaker.as_str())).copied().unwrap_or(Position::Center)
+            });
+
+        }
+
+    }
+    //
+    hold_for_reading(&mut cues, options);
+    //
+    cues
+    //
+}
+//
+//
+/// Place the speakers of each scene, the first one on the left and the second one on the right,
+/// a speaker alone is in the center
+fn positions(timeline: &Timeline) -> HashMap<(usize, usize, &str), Position> {
+    //
+    let mut speakers: Vec<((usize, usize), Vec<&str>)> = Vec::new();
+    //
+    for timed in &timeline.lines {
+
+        let ScriptLine::Dialogue { speaker, .. } = &timed.line else { continue };
+
+        let scene = (timed.key.act, timed.key.scene);
+
+        if speakers.last().map(|(s, _)| *s) != Some(scene) {
+            speakers.push((scene, Vec::new()));
+        }
+
+        let names = &mut speakers.last_mut().unwrap().1;
+
+        if !names.contains(&speaker.as_str()) {
+            names.push(speaker);
+        }
+
+    }
+    //
+    let mut positions = HashMap::new();
+
+    for ((act, scene), names) in speakers {
+
+        for (i, name) in names.iter().enumerate() {
+
+            let position = match (names.len(), i) {
+
+                (1, _) => Position::Center,
+                (_, 0) => Position::Left,
+                (_, 1) => Position::Right,
+                _ => Position::Center
+
+            };
+
+            positions.insert((act, scene, *name), position);
+
+        }
+
+    }
+    //
+    positions
+    //
+}
+//
+//
+/// Hold the cues shown too briefly, without reaching the next one
+fn hold_for_reading(cues: &mut [Subtitle], options: &SubtitleOptions) {
+    //
+    let mut too_fast = 0;
+    //
+    for i in 0..cues.len() {
+
+        let chars = cues[i].text.chars().filter(|c| *c != '\n').count() as f64;
+        let needed = cues[i].start + Duration::from_secs_f64(chars / options.max_chars_per_second);
+
+        if needed <= cues[i].end {
+            continue;
+        }
+
+        let limit = cues.get(i + 1).map_or(needed, |next| next.start.saturating_sub(options.min_gap));
+
+        cues[i].end = cues[i].end.max(needed.min(limit));
+
+        if cues[i].end < needed {
+            too_fast += 1;
+        }
+
+    }
+    //
+    if too_fast > 0 {
+        cwarn!(category: "script", "{} subtitles are shown faster than {} characters per second", too_fast, options.max_chars_per_second);
+    }
+    //
+}
+//
+//
+/// Split a reply into the lines of each cue, the cues get about the same number of characters
+/// so the last one is not left with a single word
+fn split(text: &str, options: &SubtitleOptions) -> Vec<Vec<String>> {
+    //
+    let lines = wrap(text, options.max_line_length);
+    let count = lines.len().div_ceil(options.max_lines.max(1));
+
+    if count <= 1 {
+        return vec![lines];
+    }
+    //
+    let total = text.len();
+    let mut groups: Vec<String> = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut done = 0;
+    let mut size = 0;
+    //
+    for word in text.split_whitespace() {
+
+        let boundary = total * (groups.len() + 1) / count;
+
+        if !current.is_empty() && groups.len() + 1 < count && done + size + word.len() / 2 > boundary {
+
+            groups.push(current.join(" "));
+            current.clear();
+            done += size;
+            size = 0;
+
+        }
+
+        current.push(word);
+        size += word.len() + 1;
+
+    }
+
+    groups.push(current.join(" "));
+    //
+    groups.iter().map(|g| wrap(g, options.max_line_length)).collect()
+    //
+}
+//
+//
+/// Split a text into lines no longer than a length, a word longer than the length is alone on
+/// its line
+pub fn wrap(text: &str, length: usize) -> Vec<String> {
+    //
+    let mut lines: Vec<String> = Vec::new();
+    //
+    for word in text.split_whitespace() {
+
+        match lines.last_mut() {
+
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= length => {
+
+                line.push(' ');
+                line.push_str(word);
+
+            },
+
+            _ => lines.push(word.to_string())
+
+        }
+
+    }
+    //
+    lines
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Formats
+//
+/// extensions of the subtitle files
+pub const SRT_EXTENSION: &str = "srt";
+pub const VTT_EXTENSION: &str = "vtt";
+//
+//
+/// Write the subtitles as SRT, the positions are '{\an}' tags
+pub fn to_srt(cues: &[Subtitle]) -> String {
+    //
+    let mut srt = String::new();
+    //
+    for (i, cue) in cues.iter().enumerate() {
+
+        if i > 0 {
+            srt.push('\n');
+        }
+
+        let tag = match cue.position {
+
+            Position::Left => "{\\an1}",
+            Position::Center => "",
+            Position::Right => "{\\an3}"
+
+        };
+
+        let _ = writeln!(srt, "{}\n{} --> {}", i + 1, timestamp(cue.start, ','), timestamp(cue.end, ','));
+        let _ = writeln!(srt, "{}{}", tag, cue.lines.join("\n"));
+
+    }
+    //
+    srt
+    //
+}
+//
+//
+/// Write the subtitles as WebVTT, the speakers are voice tags and the positions cue settings
+pub fn to_vtt(cues: &[Subtitle]) -> String {
+    //
+    let mut vtt = String::from("WEBVTT\n");
+    //
+    for (i, cue) in cues.iter().enumerate() {
+
+        let settings = match cue.position {
+
+            Position::Left => " align:left position:10%",
+            Position::Center => "",
+            Position::Right => " align:right position:90%"
+
+        };
+
+        let _ = writeln!(vtt, "\n{}\n{} --> {}{}", i + 1, timestamp(cue.start, '.'), timestamp(cue.end, '.'), settings);
+        let _ = writeln!(vtt, "<v {}>{}", escape(&cue.speaker), escape(&cue.text));
+
+    }
+    //
+    vtt
+    //
+}
+//
+//
+/// Time like '00:01:02,500', the separator of the milliseconds is ',' in SRT and '.' in WebVTT
+fn timestamp(time: Duration, separator: char) -> String {
+
+    let ms = time.as_millis();
+
+    format!("{:02}:{:02}:{:02}{}{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, separator, ms % 1000)
+
+}
+//
+//
+/// Escape the characters with a meaning in WebVTT
+fn escape(text: &str) -> String {
+
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+
+}
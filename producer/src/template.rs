@@ -0,0 +1,640 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Prompt templates
+//
+// The prompts are written as templates rendered against the series bible and the state of the
+// episode. A template can use:
+//
+// * '{{ series.title }}'                                  a variable, an unknown one is an error
+// * '{{#if series.tone}} .. {{else}} .. {{/if}}'          a condition, false if the value is empty
+// * '{{#if series.tone?}} .. {{/if}}'                      also false if the value is missing
+// * '{{#each series.characters as c}} .. {{/each}}'       a loop, with '@index', '@number',
+//                                                         '@first' and '@last'
+// * '{{> characters}}'                                    another template of the same set
+// * '{{#truncate 300}} .. {{/truncate}}'                  a section cut to a number of tokens
+// * '{{#role system}} .. {{/role}}'                       a chat message
+// * '{{! a comment }}'
+//
+mod parser;
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use error_stack::{IntoReport, Result, ResultExt};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::openai_call::estimate_tokens;
+use crate::EGeneral;
+use parser::{error, Node};
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use serde_json::json;
+
+    use crate::series::{Character, Series};
+    use super::{truncate_tokens, ChatMessage, TemplateSet, TEMPLATE_EXTENSION};
+
+    fn context() -> serde_json::Value {
+
+        let mut series = Series::new("The Night Shift", "Two robots run a diner on the moon");
+
+        let mut bolt = Character::new("Bolt");
+        bolt.catchphrases = vec!["Not my circuit.".to_string(), "Order up.".to_string()];
+
+        series.characters = vec![bolt, Character::new("Nova")];
+
+        json!({ "series": series, "episode": { "number": 3, "summary": "" } })
+
+    }
+
+    #[test]
+    fn variables_conditions_and_loops() {
+
+        let mut set = TemplateSet::new();
+
+        set.add("cast", "\
+            {{#each series.characters as c}}
+            {{ @number }}. {{ c.name }}{{#if c.catchphrases}} ({{ c.catchphrases }}){{/if}}{{#if @last}}.{{else}},{{/if}}
+            {{/each}}
+        ").unwrap();
+
+        set.add("prompt", "\
+            Write episode {{ episode.number }} of '{{ series.title }}'.
+            {{#if episode.summary}}
+            Previously: {{ episode.summary }}
+            {{else}}
+            This is the first episode.
+            {{/if}}
+            {{> cast}}
+        ").unwrap();
+
+        let text = set.render("prompt", &context()).unwrap();
+        let lines: Vec<&str> = text.lines().map(|l| l.trim()).collect();
+
+        assert_eq!(lines, [
+            "Write episode 3 of 'The Night Shift'.",
+            "This is the first episode.",
+            "1. Bolt (Not my circuit., Order up.),",
+            "2. Nova.",
+            ""
+        ]);
+
+    }
+
+    #[test]
+    fn missing_variable_is_an_error() {
+
+        let mut set = TemplateSet::new();
+
+        set.add("prompt", "Title: {{ series.title }}\nTone: {{ series.mood }}").unwrap();
+        set.add("partial", "{{> unknown}}").unwrap();
+        set.add("loop", "{{#each series.title as c}}{{/each}}").unwrap();
+        set.add("condition", "{{#if series.title}}\n{{#if series.mood}}sad{{/if}}\n{{/if}}").unwrap();
+        set.add("optional", "{{#if series.mood?}}sad{{else}}fine{{/if}}").unwrap();
+
+        let error = |name: &str| format!("{:?}", set.render(name, &context()).unwrap_err());
+
+        assert!(error("prompt").contains("template 'prompt' line 2: missing variable 'series.mood'"));
+        assert!(error("partial").contains("unknown template 'unknown'"));
+        assert!(error("loop").contains("'series.title' is not a list"));
+        assert!(error("condition").contains("template 'condition' line 2: missing variable 'series.mood'"));
+        assert_eq!(set.render("optional", &context()).unwrap(), "fine");
+        assert!(error("nothing").contains("unknown template 'nothing'"));
+
+    }
+
+    #[test]
+    fn chat_messages_from_role_blocks() {
+
+        let mut set = TemplateSet::new();
+
+        set.add("chat", "\
+            {{#role system}}
+            You write '{{ series.title }}'.
+            {{/role}}
+            {{#role user}}
+            Premise: {{ series.premise }}
+            {{/role}}
+        ").unwrap();
+
+        let messages = set.render_messages("chat", &context()).unwrap();
+
+        assert_eq!(messages, [
+            ChatMessage { role: "system".to_string(), content: "You write 'The Night Shift'.".to_string() },
+            ChatMessage { role: "user".to_string(), content: "Premise: Two robots run a diner on the moon".to_string() },
+        ]);
+
+    }
+
+    #[test]
+    fn section_is_truncated_to_its_tokens() {
+
+        let mut set = TemplateSet::new();
+
+        set.add("prompt", "Start {{#truncate 5}}{{ text }}{{/truncate}} end").unwrap();
+
+        let text = set.render("prompt", &json!({ "text": "one two three four five six seven eight" })).unwrap();
+
+        assert_eq!(text, "Start one two three … end");
+        assert_eq!(truncate_tokens("short", 10), "short");
+
+    }
+
+    #[test]
+    fn templates_are_loaded_from_a_directory() {
+
+        let dir = std::env::temp_dir().join(format!("producer-templates-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        std::fs::write(dir.join(format!("title.{}", TEMPLATE_EXTENSION)), "{{ series.title }}").unwrap();
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let set = TemplateSet::load_dir(&dir).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(set.render("title", &context()).unwrap(), "The Night Shift");
+        assert!(set.render("notes", &context()).is_err());
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Template
+//
+/// extension of the template files
+pub const TEMPLATE_EXTENSION: &str = "prompt";
+//
+// a template including itself is stopped after this many includes
+const MAX_INCLUDE_DEPTH: usize = 16;
+//
+// added at the end of a truncated section
+const TRUNCATION_MARK: &str = "…";
+//
+//
+/// A message of a chat prompt
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChatMessage {
+
+    pub role:       String,
+    pub content:    String,
+
+}
+//
+//
+/// Templates that can include each other by name
+#[derive(Default)]
+pub struct TemplateSet { templates: HashMap<String, Vec<Node>> }
+//
+impl TemplateSet {
+    //
+    /// Create an empty set
+    pub fn new() -> Self { Self::default() }
+    //
+    //
+    /// Load every '.prompt' file of a directory, the name of a template is its file name
+    /// without the extension
+    ///
+    /// # Arguments
+    ///
+    /// * 'dir' - directory of the templates
+    ///
+    pub fn load_dir(dir: &Path) -> Result<Self, EGeneral> {
+        //
+        let mut set = Self::new();
+        //
+        let entries = std::fs::read_dir(dir)
+            .into_report()
+            .change_context(EGeneral::Template)
+            .attach_printable_lazy(|| format!("unable to read '{}'", dir.display()))?;
+        //
+        for entry in entries {
+
+            let path = entry.into_report().change_context(EGeneral::Template)?.path();
+
+            if path.extension().and_then(|e| e.to_str()) != Some(TEMPLATE_EXTENSION) {
+                continue;
+            }
+
+            let Some(name) = path.file_stem().and_then(|n| n.to_str()) else { continue };
+
+            let source = std::fs::read_to_string(&path)
+                .into_report()
+                .change_context(EGeneral::Template)
+                .attach_printable_lazy(|| format!("unable to read '{}'", path.display()))?;
+
+            set.add(name, &source)?;
+
+        }
+        //
+        Ok(set)
+        //
+    }
+    //
+    //
+    /// Parse a template and add it to the set, replacing the one with the same name
+    ///
+    /// # Arguments
+    ///
+    /// * 'name'   - name used to render or include the template
+    /// * 'source' - text of the template
+    ///
+    pub fn add(&mut self, name: &str, source: &str) -> Result<(), EGeneral> {
+
+        self.templates.insert(name.to_string(), parser::parse(name, source)?);
+
+        Ok(())
+
+    }
+    //
+    //
+    /// Check if the set has a template
+    pub fn contains(&self, name: &str) -> bool { self.templates.contains_key(name) }
+    //
+    //
+    /// Add every template of another set, replacing the ones with the same name
+    pub fn merge(&mut self, other: TemplateSet) { self.templates.extend(other.templates); }
+    //
+    //
+    /// Render a template as a single prompt, the role blocks are written as plain text
+    ///
+    /// # Arguments
+    ///
+    /// * 'name'    - the template to render
+    /// * 'context' - the values of the variables, usually '{"series": .., "episode": ..}'
+    ///
+    pub fn render(&self, name: &str, context: &Value) -> Result<String, EGeneral> {
+
+        let mut renderer = Renderer::new(self, context, false);
+
+        renderer.template(name, 0)?;
+
+        Ok(renderer.out)
+
+    }
+    //
+    //
+    /// Render a template as chat messages, one per role block, the text outside of them is
+    /// sent as a user message
+    ///
+    /// # Arguments
+    ///
+    /// * 'name'    - the template to render
+    /// * 'context' - the values of the variables
+    ///
+    pub fn render_messages(&self, name: &str, context: &Value) -> Result<Vec<ChatMessage>, EGeneral> {
+
+        let mut renderer = Renderer::new(self, context, true);
+
+        renderer.template(name, 0)?;
+        renderer.flush_user_message();
+
+        Ok(renderer.messages)
+
+    }
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Renderer
+//
+/// State of a rendering
+struct Renderer<'a> {
+
+    set:        &'a TemplateSet,
+    root:       &'a Value,
+    // the variables of the loops, the innermost last
+    scopes:     Vec<Map<String, Value>>,
+    // name of the template being rendered, for the errors
+    names:      Vec<String>,
+    out:        String,
+    // none when rendering a single prompt
+    chat:       bool,
+    messages:   Vec<ChatMessage>,
+    in_role:    bool,
+
+}
+//
+impl<'a> Renderer<'a> {
+    //
+    fn new(set: &'a TemplateSet, root: &'a Value, chat: bool) -> Self {
+
+        Self { set, root, scopes: Vec::new(), names: Vec::new(), out: String::new(), chat, messages: Vec::new(), in_role: false }
+
+    }
+    //
+    //
+    /// Name of the template being rendered
+    fn name(&self) -> &str { self.names.last().map(|n| n.as_str()).unwrap_or("") }
+    //
+    //
+    /// Render a template of the set
+    fn template(&mut self, name: &str, depth: usize) -> Result<(), EGeneral> {
+        //
+        let nodes = self.set.templates.get(name).ok_or_else(|| {
+
+            EGeneral::Template.as_report().attach_printable(format!("unknown template '{}'", name))
+
+        })?;
+        //
+        if depth > MAX_INCLUDE_DEPTH {
+
+            return Err(EGeneral::Template.as_report()
+                .attach_printable(format!("the template '{}' is included more than {} times in itself", name, MAX_INCLUDE_DEPTH)));
+
+        }
+        //
+        self.names.push(name.to_string());
+
+        self.nodes(nodes, depth)?;
+
+        self.names.pop();
+
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Render a list of nodes
+    fn nodes(&mut self, nodes: &[Node], depth: usize) -> Result<(), EGeneral> {
+        //
+        for node in nodes {
+
+            match node {
+
+                Node::Text(text) => self.out.push_str(text),
+
+                Node::Var { path, line } => {
+
+                    let value = self.lookup(path).ok_or_else(|| error(self.name(), *line, &format!("missing variable '{}'", path)))?;
+
+                    let text = display(&value).ok_or_else(|| error(self.name(), *line, &format!("'{}' can't be written as text", path)))?;
+
+                    self.out.push_str(&text);
+
+                },
+
+                Node::If { path, optional, then, otherwise, line } => {
+
+                    let value = match self.lookup(path) {
+
+                        Some(value) => is_true(&value),
+                        None if *optional => false,
+                        None => return Err(error(self.name(), *line, &format!("missing variable '{}'", path)))
+
+                    };
+
+                    match value {
+
+                        true => self.nodes(then, depth)?,
+                        false => self.nodes(otherwise, depth)?
+
+                    }
+
+                },
+
+                Node::Each { path, alias, body, line } => self.each(path, alias, body, *line, depth)?,
+
+                Node::Partial { name, line } => {
+
+                    self.template(name, depth + 1)
+                        .attach_printable_lazy(|| format!("included by '{}' line {}", self.name(), line))?;
+
+                },
+
+                Node::Truncate { tokens, body } => {
+
+                    let text = self.capture(body, depth)?;
+
+                    self.out.push_str(&truncate_tokens(&text, *tokens));
+
+                },
+
+                Node::Role { role, body, line } => self.role(role, body, *line, depth)?,
+
+            }
+
+        }
+        //
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Render the body of a loop for each item of a list
+    fn each(&mut self, path: &str, alias: &str, body: &[Node], line: usize, depth: usize) -> Result<(), EGeneral> {
+        //
+        let list = match self.lookup(path) {
+
+            Some(Value::Array(list)) => list,
+            Some(Value::Null) => Vec::new(),
+            Some(_) => return Err(error(self.name(), line, &format!("'{}' is not a list", path))),
+            None => return Err(error(self.name(), line, &format!("missing variable '{}'", path)))
+
+        };
+        //
+        let count = list.len();
+
+        for (i, item) in list.into_iter().enumerate() {
+
+            let mut scope = Map::new();
+
+            scope.insert(alias.to_string(), item);
+            scope.insert("@index".to_string(), Value::from(i));
+            scope.insert("@number".to_string(), Value::from(i + 1));
+            scope.insert("@first".to_string(), Value::from(i == 0));
+            scope.insert("@last".to_string(), Value::from(i + 1 == count));
+
+            self.scopes.push(scope);
+
+            let result = self.nodes(body, depth);
+
+            self.scopes.pop();
+
+            result?;
+
+        }
+        //
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Render a chat message, or only its content when rendering a single prompt
+    fn role(&mut self, role: &str, body: &[Node], line: usize, depth: usize) -> Result<(), EGeneral> {
+        //
+        if !self.chat {
+            return self.nodes(body, depth);
+        }
+        //
+        if self.in_role {
+            return Err(error(self.name(), line, &format!("'#role {}' is inside another role", role)));
+        }
+        //
+        self.flush_user_message();
+
+        self.in_role = true;
+        let content = self.capture(body, depth);
+        self.in_role = false;
+
+        self.messages.push(ChatMessage { role: role.to_string(), content: trim_lines(&content?) });
+
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Send the text written outside of the role blocks as a user message
+    fn flush_user_message(&mut self) {
+
+        let content = trim_lines(&std::mem::take(&mut self.out));
+
+        if !content.is_empty() {
+            self.messages.push(ChatMessage { role: "user".to_string(), content });
+        }
+
+    }
+    //
+    //
+    /// Render nodes apart from the output
+    fn capture(&mut self, nodes: &[Node], depth: usize) -> Result<String, EGeneral> {
+
+        let out = std::mem::take(&mut self.out);
+
+        let result = self.nodes(nodes, depth);
+
+        Ok(std::mem::replace(&mut self.out, out)).and_then(|text| result.map(|_| text))
+
+    }
+    //
+    //
+    /// Find the value of a variable, in the loops first
+    fn lookup(&self, path: &str) -> Option<Value> {
+        //
+        let mut parts = path.split('.');
+        let first = parts.next()?;
+        //
+        let mut value = self.scopes.iter().rev()
+            .find_map(|scope| scope.get(first))
+            .or_else(|| self.root.get(first))?;
+        //
+        for part in parts {
+
+            value = match value {
+
+                Value::Array(list) => list.get(part.parse::<usize>().ok()?)?,
+                Value::Object(map) => map.get(part)?,
+                _ => return None
+
+            };
+
+        }
+        //
+        Some(value.clone())
+        //
+    }
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Values
+//
+/// Check if a value makes a condition true, empty values are false
+fn is_true(value: &Value) -> bool {
+
+    match value {
+
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.trim().is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty()
+
+    }
+
+}
+//
+//
+/// Write a value as text, a list is written as a comma separated list
+fn display(value: &Value) -> Option<String> {
+
+    match value {
+
+        Value::Null => Some(String::new()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::String(s) => Some(s.clone()),
+
+        Value::Array(list) => list.iter()
+            .map(display)
+            .collect::<Option<Vec<String>>>()
+            .map(|items| items.join(", ")),
+
+        Value::Object(_) => None
+
+    }
+
+}
+//
+//
+/// Remove the indentation shared by the lines and the empty lines around a message
+fn trim_lines(text: &str) -> String {
+
+    let indent = text.lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    text.lines()
+        .map(|l| l.get(indent..).unwrap_or(l.trim_start()).trim_end())
+        .collect::<Vec<&str>>()
+        .join("\n")
+        .trim_matches('\n')
+        .to_string()
+
+}
+//
+//
+/// Cut a text on a word so it is no longer than a number of tokens, a mark is added at the end
+///
+/// # Arguments
+///
+/// * 'text'   - the text to cut
+/// * 'tokens' - the estimated number of tokens allowed
+///
+pub fn truncate_tokens(text: &str, tokens: usize) -> String {
+    //
+    if estimate_tokens(text) <= tokens {
+        return text.to_string();
+    }
+    //
+    // the end of every word, the longest prefix that fits is kept
+    let ends: Vec<usize> = text.char_indices()
+        .filter(|(i, c)| !c.is_whitespace() && text[i + c.len_utf8()..].chars().next().is_none_or(|n| n.is_whitespace()))
+        .map(|(i, c)| i + c.len_utf8())
+        .collect();
+
+    let fits = ends.partition_point(|end| estimate_tokens(&text[..*end]) < tokens);
+
+    match fits {
+
+        0 => TRUNCATION_MARK.to_string(),
+        n => format!("{} {}", &text[..ends[n - 1]], TRUNCATION_MARK)
+
+    }
+    //
+}
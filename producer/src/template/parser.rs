@@ -0,0 +1,474 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Template parser
+//
+// Split a template into its text and its '{{ }}' tags, then build the tree of nodes. A block
+// tag alone on its line removes the whole line, so the blocks can be indented in the files
+// without leaving empty lines in the prompts.
+//
+use error_stack::Result;
+
+use crate::EGeneral;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use super::{parse, Node};
+
+    #[test]
+    fn standalone_block_tags_remove_their_line() {
+
+        let nodes = parse("t", "A\n  {{#if x}}\n  B\n  {{/if}}\nC").unwrap();
+
+        assert_eq!(nodes.len(), 3);
+        assert!(matches!(&nodes[0], Node::Text(t) if t == "A\n"));
+        assert!(matches!(&nodes[1], Node::If { then, .. } if matches!(&then[0], Node::Text(t) if t == "  B\n")));
+        assert!(matches!(&nodes[2], Node::Text(t) if t == "C"));
+
+    }
+
+    #[test]
+    fn inline_tags_keep_the_text() {
+
+        let nodes = parse("t", "Hi {{#if x}}{{ name }}{{else}}you{{/if}}!").unwrap();
+
+        assert!(matches!(&nodes[0], Node::Text(t) if t == "Hi "));
+        assert!(matches!(&nodes[1], Node::If { otherwise, .. } if otherwise.len() == 1));
+        assert!(matches!(&nodes[2], Node::Text(t) if t == "!"));
+
+    }
+
+    #[test]
+    fn errors_give_the_line() {
+
+        let error = |source: &str| format!("{:?}", parse("scene", source).unwrap_err());
+
+        assert!(error("a\n{{#if x}}\nb").contains("template 'scene' line 2: '#if' is never closed"));
+        assert!(error("{{ name").contains("line 1: '{{' is never closed"));
+        assert!(error("{{#each list}}{{/each}}").contains("expected '#each <list> as <name>'"));
+        assert!(error("{{/if}}").contains("unexpected '/if'"));
+        assert!(error("{{ bad name }}").contains("invalid variable 'bad name'"));
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Node
+//
+/// A part of a template
+#[derive(Debug)]
+pub(crate) enum Node {
+
+    Text(String),
+
+    /// '{{ path }}'
+    Var { path: String, line: usize },
+
+    /// '{{#if path}} then {{else}} otherwise {{/if}}', 'path?' when the value can be missing
+    If { path: String, optional: bool, then: Vec<Node>, otherwise: Vec<Node>, line: usize },
+
+    /// '{{#each path as alias}} body {{/each}}'
+    Each { path: String, alias: String, body: Vec<Node>, line: usize },
+
+    /// '{{> name}}'
+    Partial { name: String, line: usize },
+
+    /// '{{#truncate tokens}} body {{/truncate}}'
+    Truncate { tokens: usize, body: Vec<Node> },
+
+    /// '{{#role name}} body {{/role}}'
+    Role { role: String, body: Vec<Node>, line: usize },
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Lexer
+//
+/// Text between the tags or the content of a tag
+#[derive(Debug)]
+enum Segment {
+
+    Text(String),
+    Tag { content: String, line: usize },
+
+}
+//
+impl Segment {
+    //
+    /// Check if the tag can remove its line when it is alone on it
+    fn is_block(&self) -> bool {
+
+        match self {
+
+            Self::Tag { content, .. } => {
+                content.starts_with(['#', '/', '>', '!']) || content == "else"
+            },
+
+            Self::Text(_) => false
+
+        }
+
+    }
+    //
+}
+//
+//
+/// Error of a template at a line
+///
+/// # Arguments
+///
+/// * 'name'    - name of the template
+/// * 'line'    - line of the problem, starting at 1
+/// * 'problem' - what is wrong
+///
+pub(crate) fn error(name: &str, line: usize, problem: &str) -> error_stack::Report<EGeneral> {
+
+    EGeneral::Template.as_report().attach_printable(format!("template '{}' line {}: {}", name, line, problem))
+
+}
+//
+//
+/// Split the template into text and tags
+fn lex(name: &str, source: &str) -> Result<Vec<Segment>, EGeneral> {
+    //
+    let mut segments = Vec::new();
+    let mut rest = source;
+    let mut line = 1;
+    //
+    while let Some(start) = rest.find("{{") {
+
+        let text = &rest[..start];
+        line += text.matches('\n').count();
+
+        if !text.is_empty() {
+            segments.push(Segment::Text(text.to_string()));
+        }
+
+        let end = rest[start..].find("}}")
+            .ok_or_else(|| error(name, line, "'{{' is never closed"))?;
+
+        let content = &rest[start + 2..start + end];
+
+        segments.push(Segment::Tag { content: content.trim().to_string(), line });
+
+        line += content.matches('\n').count();
+        rest = &rest[start + end + 2..];
+
+    }
+    //
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest.to_string()));
+    }
+    //
+    Ok(segments)
+    //
+}
+//
+//
+/// Remove the lines containing only a block tag
+fn remove_standalone_lines(segments: &mut [Segment]) {
+    //
+    let text = |segment: Option<&Segment>| match segment {
+
+        Some(Segment::Text(t)) => Some(t.clone()),
+        _ => None
+
+    };
+    //
+    // checked on the original text, a text between two block tags is trimmed by both
+    let standalone: Vec<bool> = (0..segments.len()).map(|i| {
+
+        if !segments[i].is_block() {
+            return false;
+        }
+
+        let before = match i {
+
+            0 => true,
+
+            _ => match text(segments.get(i - 1)) {
+
+                Some(t) => match t.rfind('\n') {
+
+                    Some(n) => t[n + 1..].trim_matches([' ', '\t']).is_empty(),
+                    None => i == 1 && t.trim_matches([' ', '\t']).is_empty()
+
+                },
+
+                None => false
+
+            }
+
+        };
+
+        let after = match segments.get(i + 1) {
+
+            None => true,
+
+            Some(_) => match text(segments.get(i + 1)) {
+
+                Some(t) => match t.find('\n') {
+
+                    Some(n) => t[..n].trim_matches([' ', '\t', '\r']).is_empty(),
+                    None => i + 2 == segments.len() && t.trim_matches([' ', '\t']).is_empty()
+
+                },
+
+                None => false
+
+            }
+
+        };
+
+        before && after
+
+    }).collect();
+    //
+    for (i, _) in standalone.iter().enumerate().filter(|(_, s)| **s) {
+
+        if i > 0 {
+
+            if let Segment::Text(t) = &mut segments[i - 1] {
+                t.truncate(t.trim_end_matches([' ', '\t']).len());
+            }
+
+        }
+
+        if let Some(Segment::Text(t)) = segments.get_mut(i + 1) {
+
+            let trimmed = t.trim_start_matches([' ', '\t', '\r']);
+            let trimmed = trimmed.strip_prefix('\n').unwrap_or(trimmed);
+
+            *t = trimmed.to_string();
+
+        }
+
+    }
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Parser
+//
+/// Parse a template into nodes
+///
+/// # Arguments
+///
+/// * 'name'   - name of the template, used in the errors
+/// * 'source' - text of the template
+///
+pub(crate) fn parse(name: &str, source: &str) -> Result<Vec<Node>, EGeneral> {
+    //
+    let mut segments = lex(name, source)?;
+
+    remove_standalone_lines(&mut segments);
+    //
+    let mut parser = Parser { name, segments: segments.into_iter() };
+
+    match parser.block()? {
+
+        (nodes, None) => Ok(nodes),
+
+        (_, Some((tag, line))) => Err(error(name, line, &format!("unexpected '{}'", tag)))
+
+    }
+    //
+}
+//
+//
+/// A closing tag and its line
+type Closing = Option<(String, usize)>;
+//
+//
+/// Build the nodes from the segments
+struct Parser<'a> {
+
+    name:       &'a str,
+    segments:   std::vec::IntoIter<Segment>,
+
+}
+//
+impl Parser<'_> {
+    //
+    /// Parse until the end of the template or a closing tag, give back the closing tag
+    fn block(&mut self) -> Result<(Vec<Node>, Closing), EGeneral> {
+        //
+        let mut nodes = Vec::new();
+        //
+        while let Some(segment) = self.segments.next() {
+
+            let (content, line) = match segment {
+
+                Segment::Text(text) => {
+
+                    if !text.is_empty() {
+                        nodes.push(Node::Text(text));
+                    }
+
+                    continue;
+
+                },
+
+                Segment::Tag { content, line } => (content, line)
+
+            };
+
+            if content == "else" || content.starts_with('/') {
+                return Ok((nodes, Some((content, line))));
+            }
+
+            if content.starts_with('!') {
+                continue;
+            }
+
+            nodes.push(self.tag(&content, line)?);
+
+        }
+        //
+        Ok((nodes, None))
+        //
+    }
+    //
+    //
+    /// Parse a tag that is not a closing one
+    fn tag(&mut self, content: &str, line: usize) -> Result<Node, EGeneral> {
+        //
+        if let Some(name) = content.strip_prefix('>') {
+
+            return Ok(Node::Partial { name: self.identifier(name.trim(), line)?, line });
+
+        }
+        //
+        let Some(block) = content.strip_prefix('#') else {
+
+            return Ok(Node::Var { path: self.path(content, line)?, line });
+
+        };
+        //
+        let words: Vec<&str> = block.split_whitespace().collect();
+
+        match words.as_slice() {
+
+            ["if", path] => {
+
+                let (path, optional) = match path.strip_suffix('?') {
+
+                    Some(path) => (path, true),
+                    None => (*path, false)
+
+                };
+
+                let path = self.path(path, line)?;
+                let (then, end) = self.body("if", line, true)?;
+
+                let otherwise = match end.as_str() {
+
+                    "else" => self.body("if", line, false)?.0,
+                    _ => Vec::new()
+
+                };
+
+                Ok(Node::If { path, optional, then, otherwise, line })
+
+            },
+
+            ["each", path, "as", alias] => {
+
+                let path = self.path(path, line)?;
+                let alias = self.identifier(alias, line)?;
+
+                Ok(Node::Each { path, alias, body: self.body("each", line, false)?.0, line })
+
+            },
+
+            ["truncate", tokens] => {
+
+                let tokens = tokens.parse::<usize>()
+                    .map_err(|_| error(self.name, line, &format!("'{}' is not a number of tokens", tokens)))?;
+
+                Ok(Node::Truncate { tokens, body: self.body("truncate", line, false)?.0 })
+
+            },
+
+            ["role", role] => {
+
+                let role = self.identifier(role, line)?;
+
+                Ok(Node::Role { role, body: self.body("role", line, false)?.0, line })
+
+            },
+
+            ["each", ..] => Err(error(self.name, line, "expected '#each <list> as <name>'")),
+
+            _ => Err(error(self.name, line, &format!("unknown block '#{}'", block)))
+
+        }
+        //
+    }
+    //
+    //
+    /// Parse the body of a block until its closing tag
+    ///
+    /// # Arguments
+    ///
+    /// * 'block'      - name of the block
+    /// * 'line'       - line where the block starts
+    /// * 'allow_else' - if the body can end with '{{else}}'
+    ///
+    fn body(&mut self, block: &str, line: usize, allow_else: bool) -> Result<(Vec<Node>, String), EGeneral> {
+
+        match self.block()? {
+
+            (nodes, Some((end, _))) if end == format!("/{}", block) || (allow_else && end == "else") => {
+                Ok((nodes, end))
+            },
+
+            (_, Some((end, end_line))) => Err(error(self.name, end_line, &format!("unexpected '{}' inside '#{}'", end, block))),
+
+            (_, None) => Err(error(self.name, line, &format!("'#{}' is never closed", block)))
+
+        }
+
+    }
+    //
+    //
+    /// Check a variable path like 'series.characters.0.name' or '@index'
+    fn path(&self, path: &str, line: usize) -> Result<String, EGeneral> {
+
+        let valid = !path.is_empty()
+            && path.split('.').all(|p| !p.is_empty())
+            && path.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '@' | '-'));
+
+        match valid {
+
+            true => Ok(path.to_string()),
+            false => Err(error(self.name, line, &format!("invalid variable '{}'", path)))
+
+        }
+
+    }
+    //
+    //
+    /// Check a name without dots
+    fn identifier(&self, name: &str, line: usize) -> Result<String, EGeneral> {
+
+        match !name.contains('.') {
+
+            true => self.path(name, line),
+            false => Err(error(self.name, line, &format!("invalid name '{}'", name)))
+
+        }
+
+    }
+    //
+}
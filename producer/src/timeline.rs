@@ -0,0 +1,251 @@
+#![allow(clippy::items_after_test_module)]
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Timeline
+//
+// When each line of an episode starts and ends. The subtitles, the audio mix and the playback
+// all read the same timeline so they stay in sync. A line lasts as long as its audio when it
+// has been recorded, otherwise its length is estimated from a number of words per minute.
+//
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::episode::Episode;
+use crate::script::ScriptLine;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+pub(crate) mod test {
+
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use crate::episode::{Act, Beat, Episode, Scene};
+    use crate::script::ScriptLine;
+    use super::{LineKey, Pacing, Timeline};
+
+    pub(crate) fn episode() -> Episode {
+
+        let dialogue = |speaker: &str, text: &str| ScriptLine::Dialogue {
+            speaker:        speaker.to_string(),
+            parenthetical:  None,
+            text:           text.to_string()
+        };
+
+        let scene = |lines: Vec<ScriptLine>| Scene {
+            beats: vec![Beat { lines, ..Beat::default() }],
+            ..Scene::default()
+        };
+
+        Episode {
+            acts: vec![Act {
+                scenes: vec![
+                    scene(vec![
+                        dialogue("Bolt", "one two three four five six seven eight"),
+                        ScriptLine::Note { text: "not played".to_string() },
+                        ScriptLine::Direction { text: "Nova rolls in".to_string() },
+                        dialogue("Nova", "Hi."),
+                    ]),
+                    scene(vec![dialogue("Bolt", "Bye.")]),
+                ],
+                ..Act::default()
+            }],
+            ..Episode::default()
+        }
+
+    }
+
+    fn ms(ms: u64) -> Duration { Duration::from_millis(ms) }
+
+    #[test]
+    fn lines_are_paced_by_words() {
+
+        let pacing = Pacing { words_per_minute: 120.0, ..Pacing::default() };
+        let timeline = Timeline::build(&episode(), &pacing, &HashMap::new());
+
+        let times: Vec<(u64, u64)> = timeline.lines.iter()
+            .map(|l| (l.start.as_millis() as u64, l.end.as_millis() as u64))
+            .collect();
+
+        // scene change 1500, 8 words at 2 per second, gap 300, direction 1500, 'Hi.' at the minimum
+        assert_eq!(times, [(1500, 5500), (5800, 7300), (7600, 8600), (10400, 11400)]);
+        assert_eq!(timeline.duration(), ms(11400));
+        assert_eq!(timeline.lines[3].key, LineKey { act: 0, scene: 1, beat: 0, line: 0 });
+
+    }
+
+    #[test]
+    fn recorded_audio_gives_the_length() {
+
+        let mut durations = HashMap::new();
+        durations.insert(LineKey { act: 0, scene: 0, beat: 0, line: 0 }, ms(2500));
+
+        let timeline = Timeline::build(&episode(), &Pacing::default(), &durations);
+
+        assert_eq!(timeline.lines[0].end - timeline.lines[0].start, ms(2500));
+        assert_eq!(timeline.at(ms(2000)).map(|l| l.key.line), Some(0));
+        assert_eq!(timeline.at(ms(4100)).map(|l| l.key.line), None);
+        assert_eq!(timeline.at(ms(4500)).map(|l| l.key.line), Some(2));
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Pacing
+//
+/// How long the lines last when their audio is not known
+#[derive(Clone, Debug)]
+pub struct Pacing {
+
+    /// speed of the dialogue
+    pub words_per_minute:   f64,
+    /// shortest reply
+    pub min_line:           Duration,
+    /// silence after each line
+    pub gap:                Duration,
+    /// length of a stage direction
+    pub direction:          Duration,
+    /// pause before each scene, a heading or a transition
+    pub scene_change:       Duration,
+
+}
+//
+impl Default for Pacing {
+
+    fn default() -> Self {
+
+        Self {
+            words_per_minute:   160.0,
+            min_line:           Duration::from_millis(1000),
+            gap:                Duration::from_millis(300),
+            direction:          Duration::from_millis(1500),
+            scene_change:       Duration::from_millis(1500),
+        }
+
+    }
+
+}
+//
+impl Pacing {
+    //
+    /// Estimated length of a reply
+    pub fn speech(&self, text: &str) -> Duration {
+
+        let words = text.split_whitespace().count() as f64;
+
+        Duration::from_secs_f64(words * 60.0 / self.words_per_minute).max(self.min_line)
+
+    }
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Timeline
+//
+/// Where a line is in the episode, the indexes start at 0
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LineKey {
+
+    pub act:    usize,
+    pub scene:  usize,
+    pub beat:   usize,
+    pub line:   usize,
+
+}
+//
+//
+/// A line and when it is played
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimedLine {
+
+    pub key:    LineKey,
+    pub start:  Duration,
+    pub end:    Duration,
+    pub line:   ScriptLine,
+
+}
+//
+//
+/// Every line of an episode that is played, in order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Timeline { pub lines: Vec<TimedLine> }
+//
+impl Timeline {
+    //
+    /// Compute when each line is played, the notes are left out
+    ///
+    /// # Arguments
+    ///
+    /// * 'episode'   - the episode to play
+    /// * 'pacing'    - the length of the lines without audio
+    /// * 'durations' - the length of the recorded lines
+    ///
+    pub fn build(episode: &Episode, pacing: &Pacing, durations: &HashMap<LineKey, Duration>) -> Self {
+        //
+        let mut timeline = Self::default();
+        let mut time = Duration::ZERO;
+        //
+        for (a, act) in episode.acts.iter().enumerate() {
+
+            for (s, scene) in act.scenes.iter().enumerate() {
+
+                time += pacing.scene_change;
+
+                for (b, beat) in scene.beats.iter().enumerate() {
+
+                    for (l, line) in beat.lines.iter().enumerate() {
+
+                        let key = LineKey { act: a, scene: s, beat: b, line: l };
+
+                        let (pause, length) = match line {
+
+                            ScriptLine::Note { .. } => continue,
+
+                            ScriptLine::Heading { .. } | ScriptLine::Transition { .. } => (pacing.scene_change, Duration::ZERO),
+                            ScriptLine::Direction { .. } => (Duration::ZERO, pacing.direction),
+                            ScriptLine::Dialogue { text, .. } => (Duration::ZERO, pacing.speech(text))
+
+                        };
+
+                        let length = durations.get(&key).copied().unwrap_or(length);
+
+                        time += pause;
+
+                        timeline.lines.push(TimedLine { key, start: time, end: time + length, line: line.clone() });
+
+                        time += length + pacing.gap;
+
+                    }
+
+                }
+
+            }
+
+        }
+        //
+        timeline
+        //
+    }
+    //
+    //
+    /// Time when the last line ends
+    pub fn duration(&self) -> Duration { self.lines.last().map_or(Duration::ZERO, |l| l.end) }
+    //
+    //
+    /// The line played at a time, none during the pauses
+    pub fn at(&self, time: Duration) -> Option<&TimedLine> {
+
+        let index = self.lines.partition_point(|l| l.end <= time);
+
+        self.lines.get(index).filter(|l| l.start <= time)
+
+    }
+    //
+}
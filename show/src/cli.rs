@@ -0,0 +1,444 @@
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Command line
+//
+// Every subcommand of the show binary and the flags shared by all of them
+//
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use producer::logger::Level;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use clap::Parser;
+
+    use producer::logger::Level;
+    use super::{Cli, Command, ExportFormat};
+
+    #[test]
+    fn global_flags_after_the_subcommand() {
+
+        let cli = Cli::try_parse_from([
+            "show", "export", "pilot", "1", "--format", "srt", "--dry-run", "-vv", "--model", "fastest"
+        ]).unwrap();
+
+        assert!(cli.global.dry_run);
+        assert_eq!(cli.global.model.as_deref(), Some("fastest"));
+        assert_eq!(cli.global.level(), Some(Level::DEBUG));
+
+        match cli.command {
+
+            Command::Export(args) => {
+
+                assert_eq!(args.series, "pilot");
+                assert_eq!(args.episode, 1);
+                assert_eq!(args.format, ExportFormat::Srt);
+
+            },
+
+            _ => panic!("expected the export subcommand")
+
+        }
+
+    }
+
+    #[test]
+    fn verbosity_map_to_the_log_levels() {
+
+        let level = |args: &[&str]| Cli::try_parse_from(args).unwrap().global.level();
+
+        assert_eq!(level(&["show", "cost"]), None);
+        assert_eq!(level(&["show", "-q", "cost"]), Some(Level::ERROR));
+        assert_eq!(level(&["show", "-v", "cost"]), Some(Level::INFO));
+        assert_eq!(level(&["show", "-vvvv", "cost"]), Some(Level::TRACE));
+        assert!(Cli::try_parse_from(["show", "-q", "-v", "cost"]).is_err());
+
+    }
+
+    #[test]
+    fn play_a_stored_episode_or_a_live_one() {
+
+        let play = |args: &[&str]| match Cli::try_parse_from(args).map(|cli| cli.command) {
+
+            Ok(Command::Play(args)) => Some((args.episode, args.live, args.speed)),
+            _ => None
+
+        };
+
+        assert_eq!(play(&["show", "play", "pilot", "2", "--speed", "1.5"]), Some((Some(2), false, 1.5)));
+        assert_eq!(play(&["show", "play", "pilot", "--live", "--logline", "Coffee crisis"]), Some((None, true, 1.0)));
+        assert_eq!(play(&["show", "play", "pilot"]), None);
+        assert_eq!(play(&["show", "play", "pilot", "2", "--live"]), None);
+        assert_eq!(play(&["show", "play", "pilot", "2", "--logline", "Coffee crisis"]), None);
+        assert_eq!(play(&["show", "play", "pilot", "--logline", "Coffee crisis"]), None);
+
+    }
+
+    #[test]
+    fn speed_must_move_the_playback_forward() {
+
+        let speed = |value: &str| Cli::try_parse_from(["show", "play", "pilot", "2", "--speed", value]).is_ok();
+
+        assert!(speed("0.5"));
+        assert!(!speed("0"));
+        assert!(!speed("-1"));
+        assert!(!speed("nan"));
+        assert!(!speed("inf"));
+        assert!(!speed("fast"));
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Cli
+//
+/// Generate, play and export the episodes of an AI written show
+#[derive(Parser, Debug)]
+#[command(name = "show", version)]
+pub struct Cli {
+
+    #[command(flatten)]
+    pub global:     GlobalArgs,
+
+    #[command(subcommand)]
+    pub command:    Command,
+
+}
+//
+//
+/// Flags accepted by every subcommand
+#[derive(Args, Debug)]
+pub struct GlobalArgs {
+
+    /// Configuration file, 'show.toml' is used if it exists
+    #[arg(short, long, global = true, value_name = "FILE")]
+    pub config:     Option<PathBuf>,
+
+    /// Model used for the generation, overrides the configuration
+    #[arg(short, long, global = true, value_name = "MODEL")]
+    pub model:      Option<String>,
+
+    /// Show what would be generated without calling the api or writing a file
+    #[arg(long, global = true)]
+    pub dry_run:    bool,
+
+    /// More log entries, can be repeated up to -vvv
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose:    u8,
+
+    /// Only show the errors
+    #[arg(short, long, global = true)]
+    pub quiet:      bool,
+
+}
+//
+impl GlobalArgs {
+    //
+    /// Log level asked on the command line, none if no verbosity flag was given
+    pub fn level(&self) -> Option<Level> {
+
+        if self.quiet {
+            return Some(Level::ERROR);
+        }
+
+        match self.verbose {
+
+            0 => None,
+            1 => Some(Level::INFO),
+            2 => Some(Level::DEBUG),
+            _ => Some(Level::TRACE)
+
+        }
+
+    }
+    //
+}
+//
+//
+/// The subcommands
+#[derive(Subcommand, Debug)]
+pub enum Command {
+
+    /// Create a new series
+    NewSeries(NewSeriesArgs),
+
+    /// Write a new episode of a series
+    GenerateEpisode(GenerateEpisodeArgs),
+
+    /// Write again a single scene of an episode
+    RegenerateScene(RegenerateSceneArgs),
+
+    /// Check an episode against the facts of the previous ones and record its own facts
+    Check(CheckArgs),
+
+    /// Play an episode in the terminal
+    Play(PlayArgs),
+
+    /// Export an episode to another format
+    Export(ExportArgs),
+
+    /// Speak an episode, one WAV file per line and one for the whole episode
+    #[cfg(feature = "tts")]
+    Render(RenderArgs),
+
+    /// Read a Fountain script as the next episode of a series
+    Import(ImportArgs),
+
+    /// List the versions of the episodes of a series
+    Versions(VersionsArgs),
+
+    /// Show what changed between two versions of an episode
+    Diff(DiffArgs),
+
+    /// Make an older version of an episode its current one
+    Rollback(RollbackArgs),
+
+    /// Estimate the price of the api calls
+    Cost(CostArgs),
+
+}
+//
+//
+#[derive(Args, Debug)]
+pub struct NewSeriesArgs {
+
+    /// Name of the series, also the name of its directory
+    pub name:       String,
+
+    /// What the series is about
+    #[arg(long)]
+    pub premise:    String,
+
+}
+//
+//
+#[derive(Args, Debug)]
+pub struct GenerateEpisodeArgs {
+
+    /// Name of the series
+    pub series:     String,
+
+    /// One sentence summary of the episode, written by the model if not given
+    #[arg(long)]
+    pub logline:    Option<String>,
+
+    /// Let each character write its own lines, remembering the past episodes
+    #[arg(long)]
+    pub agents:     bool,
+
+}
+//
+//
+#[derive(Args, Debug)]
+pub struct CheckArgs {
+
+    /// Name of the series
+    pub series:     String,
+
+    /// Number of the episode
+    pub episode:    u32,
+
+}
+//
+//
+#[derive(Args, Debug)]
+pub struct RegenerateSceneArgs {
+
+    /// Name of the series
+    pub series:     String,
+
+    /// Number of the episode
+    pub episode:    u32,
+
+    /// Number of the act, starting at 1
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub act:        usize,
+
+    /// Number of the scene in the act, starting at 1
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub scene:      usize,
+
+    /// Let each character write its own lines, remembering the past episodes
+    #[arg(long)]
+    pub agents:     bool,
+
+}
+//
+//
+#[derive(Args, Debug)]
+pub struct PlayArgs {
+
+    /// Name of the series
+    pub series:     String,
+
+    /// Number of the episode, not given with --live
+    #[arg(required_unless_present = "live")]
+    pub episode:    Option<u32>,
+
+    /// Write the next episode and play it while it is written
+    #[arg(long, conflicts_with = "episode")]
+    pub live:       bool,
+
+    /// One sentence summary of the live episode, written by the model if not given
+    #[arg(long, conflicts_with = "episode")]
+    pub logline:    Option<String>,
+
+    /// How fast the episode is played, 2 is twice as fast
+    #[arg(long, default_value_t = 1.0, value_parser = parse_speed)]
+    pub speed:      f64,
+
+}
+//
+//
+/// Read a playback speed, it must move the playback forward
+fn parse_speed(text: &str) -> Result<f64, String> {
+
+    match text.parse::<f64>() {
+
+        Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(speed),
+        Ok(_) => Err("the speed must be a number greater than 0".to_string()),
+        Err(e) => Err(e.to_string())
+
+    }
+
+}
+//
+//
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+
+    /// Name of the series
+    pub series:     String,
+
+    /// Number of the episode
+    pub episode:    u32,
+
+    /// Format of the exported file
+    #[arg(short, long, value_enum, default_value_t = ExportFormat::Fountain)]
+    pub format:     ExportFormat,
+
+    /// Where to write the file, in the exports of the series by default
+    #[arg(short, long, value_name = "FILE")]
+    pub output:     Option<PathBuf>,
+
+}
+//
+//
+#[cfg(feature = "tts")]
+#[derive(Args, Debug)]
+pub struct RenderArgs {
+
+    /// Name of the series
+    pub series:     String,
+
+    /// Number of the episode
+    pub episode:    u32,
+
+    /// Directory of the audio files, 'audio/<episode>' in the series by default
+    #[arg(short, long, value_name = "DIR")]
+    pub output:     Option<PathBuf>,
+
+    /// Speak with espeak-ng instead of the built-in synthesizer
+    #[arg(long)]
+    pub espeak:     bool,
+
+    /// Sound effects and beds, 'sounds' in the series by default
+    #[arg(long, value_name = "DIR")]
+    pub sounds:     Option<PathBuf>,
+
+    /// Write the episode track with 32-bit float samples instead of 16-bit
+    #[arg(long)]
+    pub float:      bool,
+
+}
+//
+//
+#[derive(Args, Debug)]
+pub struct ImportArgs {
+
+    /// Name of the series
+    pub series:     String,
+
+    /// The Fountain script
+    pub file:       PathBuf,
+
+}
+//
+//
+#[derive(Args, Debug)]
+pub struct VersionsArgs {
+
+    /// Name of the series
+    pub series:     String,
+
+    /// Number of the episode, every episode if not given
+    pub episode:    Option<u32>,
+
+}
+//
+//
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+
+    /// Name of the series
+    pub series:     String,
+
+    /// Number of the episode
+    pub episode:    u32,
+
+    /// The older version
+    pub from:       u32,
+
+    /// The newer version, the current one if not given
+    pub to:         Option<u32>,
+
+}
+//
+//
+#[derive(Args, Debug)]
+pub struct RollbackArgs {
+
+    /// Name of the series
+    pub series:     String,
+
+    /// Number of the episode
+    pub episode:    u32,
+
+    /// The version to restore
+    pub version:    u32,
+
+}
+//
+//
+/// Formats an episode can be exported to
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+
+    Fountain,
+    Srt,
+    Vtt,
+
+}
+//
+//
+#[derive(Args, Debug)]
+pub struct CostArgs {
+
+    /// Prompts or scripts whose tokens are counted
+    pub files:      Vec<PathBuf>,
+
+    /// Print the cost of the generations recorded for a series
+    #[arg(long)]
+    pub series:     Option<String>,
+
+}
@@ -0,0 +1,848 @@
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Subcommands
+//
+// Run the subcommand parsed from the command line with the loaded configuration
+//
+use std::future::Future;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use error_stack::{IntoReport, Result, ResultExt};
+
+use producer::agent::{Director, MEMORY_DIR};
+use producer::continuity::{Continuity, FactStore, CONTINUITY_FILE};
+use producer::episode::{default_templates, Episode, EpisodeNode, EpisodeWriter};
+use producer::index::{self, Source, VectorIndex, INDEX_FILE};
+use producer::playback::Playback;
+use producer::project::{self, Project, Recorder, ReviewStatus, EPISODES_DIR};
+use producer::fountain::{self, FOUNTAIN_EXTENSION};
+use producer::series::{Series, SeriesFormat};
+use producer::subtitle::{self, SubtitleOptions, SRT_EXTENSION, VTT_EXTENSION};
+use producer::timeline::{Pacing, Timeline};
+use producer::logger::Level;
+use producer::{cinfo, creport, estimate_tokens, CompletionClient, Connection, ModelType};
+
+use crate::cli::{
+    CheckArgs, Command, CostArgs, DiffArgs, ExportArgs, ExportFormat, GenerateEpisodeArgs, GlobalArgs, ImportArgs,
+    NewSeriesArgs, PlayArgs, RegenerateSceneArgs, RollbackArgs, VersionsArgs
+};
+use crate::config::Config;
+use crate::player;
+use crate::EShow;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Session
+//
+/// file of the bible in the directory of a series
+pub const BIBLE_FILE: &str = "bible.toml";
+//
+/// directory of the exported episodes in the directory of a series
+pub const EXPORTS_DIR: &str = "exports";
+//
+/// directory of the rendered episodes in the directory of a series
+#[cfg(feature = "tts")]
+pub const AUDIO_DIR: &str = "audio";
+//
+/// directory of the sound effects and the beds in the directory of a series
+#[cfg(feature = "tts")]
+pub const SOUNDS_DIR: &str = "sounds";
+//
+// passages of the previous episodes given to the prompts
+const RECALLED: usize = 3;
+//
+/// What every subcommand needs to run
+pub struct Session {
+
+    pub config:     Config,
+    pub model:      ModelType,
+    pub dry_run:    bool,
+    /// runs every request of the command, the pooled connections belong to it
+    runtime:        tokio::runtime::Runtime,
+
+}
+//
+impl Session {
+    //
+    /// Resolve the settings from the configuration and the command line
+    ///
+    /// # Arguments
+    ///
+    /// * 'config' - the loaded configuration
+    /// * 'global' - the flags shared by every subcommand
+    ///
+    pub fn new(config: Config, global: &GlobalArgs) -> Result<Self, EShow> {
+
+        let model = config.model(global.model.as_deref())?;
+
+        let runtime = tokio::runtime::Runtime::new()
+            .into_report()
+            .change_context(EShow::Command)
+            .attach_printable("unable to start the async runtime")?;
+
+        Ok(Self { config, model, dry_run: global.dry_run, runtime })
+
+    }
+    //
+    //
+    /// Run a subcommand
+    ///
+    /// # Arguments
+    ///
+    /// * 'command' - the subcommand and its arguments
+    ///
+    pub fn run(&self, command: Command) -> Result<(), EShow> {
+        //
+        cinfo!(
+            "running {:?} with the model '{}' in '{}'",
+            command, self.model.to_str(), self.config.series_dir.display()
+        );
+
+        if self.dry_run {
+            cinfo!("dry run, nothing will be sent to the api or written");
+        }
+        //
+        match command {
+
+            Command::Cost(args) => self.cost(args),
+
+            Command::NewSeries(args) => self.new_series(args),
+            Command::GenerateEpisode(args) => self.generate_episode(args, None),
+            Command::RegenerateScene(args) => self.regenerate_scene(args),
+            Command::Check(args) => self.check(args),
+            Command::Play(args) => self.play(args),
+            Command::Export(args) => self.export(args),
+            #[cfg(feature = "tts")]
+            Command::Render(args) => self.render(args),
+            Command::Import(args) => self.import(args),
+            Command::Versions(args) => self.versions(args),
+            Command::Diff(args) => self.diff(args),
+            Command::Rollback(args) => self.rollback(args),
+
+        }
+        //
+    }
+    //
+    //
+    /// Directory of a series
+    pub fn series_dir(&self, name: &str) -> PathBuf { self.config.series_dir.join(name) }
+    //
+    //
+    /// File of an episode of a series
+    pub fn episode_path(&self, series: &str, number: u32) -> PathBuf {
+
+        self.series_dir(series).join(EPISODES_DIR).join(format!("{:03}.json", number))
+
+    }
+    //
+    //
+    /// Number of the first episode of a series that is not written yet
+    fn next_episode(&self, series: &str) -> u32 {
+
+        (1..).find(|n| !self.episode_path(series, *n).exists()).unwrap_or(1)
+
+    }
+    //
+    //
+    /// Open the project of a series, migrating its directory if it is older
+    fn project(&self, name: &str) -> Result<Project, EShow> {
+
+        Project::open(&self.series_dir(name))
+            .change_context(EShow::Command)
+            .attach_printable_lazy(|| format!("unable to open the project of the series '{}'", name))
+
+    }
+    //
+    //
+    /// Load the bible of a series
+    fn load_series(&self, name: &str) -> Result<Series, EShow> {
+
+        Series::load(&self.series_dir(name).join(BIBLE_FILE))
+            .change_context(EShow::Command)
+            .attach_printable_lazy(|| format!("unable to load the series '{}'", name))
+
+    }
+    //
+    //
+    /// Create the directory of a series with a bible containing only its premise
+    fn new_series(&self, args: NewSeriesArgs) -> Result<(), EShow> {
+        //
+        let series = Series::new(&args.name, &args.premise);
+
+        series.validate().change_context(EShow::Command)?;
+        //
+        let dir = self.series_dir(&args.name);
+        let path = dir.join(BIBLE_FILE);
+
+        if path.exists() {
+
+            return Err(EShow::Command.as_report()
+                .attach_printable(format!("the series '{}' already exists in '{}'", args.name, dir.display())));
+
+        }
+        //
+        if self.dry_run {
+
+            println!("{}", series.to_string(SeriesFormat::Toml).change_context(EShow::Command)?);
+            return Ok(());
+
+        }
+        //
+        std::fs::create_dir_all(&dir)
+            .into_report()
+            .change_context(EShow::Command)
+            .attach_printable_lazy(|| format!("unable to create '{}'", dir.display()))?;
+
+        series.save(&path).change_context(EShow::Command)?;
+
+        println!("created '{}', add the characters to its bible before writing an episode", path.display());
+
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Write the next episode of a series
+    ///
+    /// # Arguments
+    ///
+    /// * 'args'  - the subcommand arguments
+    /// * 'speed' - the episode is played at this speed while it is written when given
+    ///
+    fn generate_episode(&self, args: GenerateEpisodeArgs, speed: Option<f64>) -> Result<(), EShow> {
+        //
+        let series = self.load_series(&args.series)?;
+
+        let number = self.next_episode(&args.series);
+        //
+        if self.dry_run {
+
+            let context = serde_json::json!({
+                "series": series,
+                "episode": { "number": number, "logline": args.logline.clone().unwrap_or_default() },
+                "acts": producer::episode::DEFAULT_ACTS
+            });
+
+            let prompt = default_templates().render("logline", &context).change_context(EShow::Command)?;
+
+            println!("episode {} would start with the prompt:\n{}", number, prompt);
+            return Ok(());
+
+        }
+        //
+        let mut project = self.project(&args.series)?;
+        let connection = Connection::init();
+        let recorder = Recorder::new(&connection);
+        let recalled = self.recall(&connection, &args.series, args.logline.as_deref().unwrap_or(&series.premise))?;
+        let writer = EpisodeWriter::new(&recorder, &series, self.model)
+            .with_dialogue(!args.agents)
+            .with_facts(self.load_facts(&args.series)?)
+            .with_recalled(recalled);
+        let memory = self.series_dir(&args.series).join(MEMORY_DIR);
+        //
+        let (progress, updates) = std::sync::mpsc::channel();
+
+        let writer = match speed {
+
+            Some(_) => writer.with_progress(progress),
+            None => writer
+
+        };
+        //
+        let episode = std::thread::scope(|scope| {
+
+            let playing = speed.map(|speed| {
+
+                let playback = Playback::live(&series, Pacing::default());
+
+                scope.spawn(move || player::play(playback, Some(updates), speed))
+
+            });
+
+            let episode = self.block_on(async {
+
+                let mut episode = writer.write(number, args.logline).await?;
+
+                if args.agents {
+
+                    let mut director = Director::new(&recorder, &series, self.model).with_memories(&memory)?;
+
+                    director.play_episode(&mut episode).await?;
+                    director.remember(&episode).await?;
+                    director.save_memories(&memory)?;
+
+                }
+
+                Ok(episode)
+
+            });
+
+            // the playback ends once the writer is gone
+            drop(writer);
+
+            let played = playing.map(|p| p.join().unwrap_or_else(|_| Err(EShow::Command.as_report().attach_printable("the playback panicked"))));
+
+            if let Some(Err(report)) = played {
+                creport!(Level::WARN, report);
+            }
+
+            episode
+
+        });
+
+        project.record(Some(number), recorder.take()).change_context(EShow::Command)?;
+
+        let episode = episode?;
+        //
+        let version = project.commit_episode(&episode, "generated").change_context(EShow::Command)?;
+
+        println!("episode {} version {} written in '{}': {}", number, version, project.episode_path(number).display(), episode.logline);
+
+        self.accept(&recorder, &mut project, &series, &args.series, &episode)?;
+        self.index(&connection, &series, &args.series, &episode)
+        //
+    }
+    //
+    //
+    /// Write again the beats and the dialogue of a scene
+    fn regenerate_scene(&self, args: RegenerateSceneArgs) -> Result<(), EShow> {
+        //
+        let series = self.load_series(&args.series)?;
+
+        let mut episode = Episode::load(&self.episode_path(&args.series, args.episode)).change_context(EShow::Command)?;
+
+        let node = EpisodeNode::Scene(args.act - 1, args.scene - 1);
+
+        episode.check(node).change_context(EShow::Command)?;
+        //
+        if self.dry_run {
+
+            let scene = &episode.acts[args.act - 1].scenes[args.scene - 1];
+
+            println!("would write again act {} scene {}: {} | {}", args.act, args.scene, scene.heading, scene.summary);
+            return Ok(());
+
+        }
+        //
+        let mut project = self.project(&args.series)?;
+        let connection = Connection::init();
+        let recorder = Recorder::new(&connection);
+        let scene = &episode.acts[args.act - 1].scenes[args.scene - 1];
+        let recalled = self.recall(&connection, &args.series, &format!("{}: {}", scene.heading, scene.summary))?;
+        let writer = EpisodeWriter::new(&recorder, &series, self.model)
+            .with_dialogue(!args.agents)
+            .with_facts(self.load_facts(&args.series)?)
+            .with_recalled(recalled);
+        let memory = self.series_dir(&args.series).join(MEMORY_DIR);
+
+        let written = self.block_on(async {
+
+            writer.regenerate(&mut episode, node).await?;
+
+            if args.agents {
+
+                let mut director = Director::new(&recorder, &series, self.model).with_memories(&memory)?;
+
+                director.play_scene(&mut episode, args.act - 1, args.scene - 1).await?;
+
+            }
+
+            Ok(())
+
+        });
+
+        project.record(Some(args.episode), recorder.take()).change_context(EShow::Command)?;
+
+        written?;
+        //
+        let note = format!("act {} scene {} regenerated", args.act, args.scene);
+        let version = project.commit_episode(&episode, &note).change_context(EShow::Command)?;
+
+        println!("act {} scene {} of episode {} written again as version {}", args.act, args.scene, args.episode, version);
+        println!("run 'show check {} {}' to record its facts again", args.series, args.episode);
+
+        self.index(&connection, &series, &args.series, &episode)?;
+
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Check an episode against the previous ones and record its facts if it has no contradiction
+    fn check(&self, args: CheckArgs) -> Result<(), EShow> {
+        //
+        let series = self.load_series(&args.series)?;
+
+        let episode = Episode::load(&self.episode_path(&args.series, args.episode)).change_context(EShow::Command)?;
+        //
+        if self.dry_run {
+
+            let facts = self.load_facts(&args.series)?;
+
+            println!("would check episode {} against {} facts", args.episode, facts.facts.len());
+            return Ok(());
+
+        }
+        //
+        let mut project = self.project(&args.series)?;
+        let connection = Connection::init();
+        let recorder = Recorder::new(&connection);
+
+        let accepted = self.accept(&recorder, &mut project, &series, &args.series, &episode);
+
+        project.record(Some(args.episode), recorder.take()).change_context(EShow::Command)?;
+
+        accepted?;
+
+        self.index(&connection, &series, &args.series, &episode)
+        //
+    }
+    //
+    //
+    /// Load the facts of the accepted episodes of a series
+    fn load_facts(&self, name: &str) -> Result<FactStore, EShow> {
+
+        FactStore::load(&self.series_dir(name).join(CONTINUITY_FILE)).change_context(EShow::Command)
+
+    }
+    //
+    //
+    /// Check the continuity of the current version of an episode, its facts are recorded and the
+    /// version accepted only when it has no contradiction, otherwise the version is rejected
+    ///
+    /// # Arguments
+    ///
+    /// * 'client'  - what completes the prompts
+    /// * 'project' - the project of the series
+    /// * 'series'  - the bible of the series
+    /// * 'name'    - the name of the series
+    /// * 'episode' - the episode to accept
+    ///
+    fn accept<C: CompletionClient>(
+        &self,
+        client: &C,
+        project: &mut Project,
+        series: &Series,
+        name: &str,
+        episode: &Episode
+
+    ) -> Result<(), EShow> {
+        //
+        let path = project.dir().join(CONTINUITY_FILE);
+        let mut facts = FactStore::load(&path).change_context(EShow::Command)?;
+        let continuity = Continuity::new(client, series, self.model);
+
+        let issues = self.block_on(continuity.check(&facts, episode))?;
+
+        let version = project.manifest.episodes.get(&episode.number).map_or(0, |r| r.current);
+        //
+        if !issues.is_empty() {
+
+            project.set_status(episode.number, version, ReviewStatus::Rejected).change_context(EShow::Command)?;
+
+            println!("version {} of episode {} is rejected, {} line(s) contradict the previous episodes:", version, episode.number, issues.len());
+
+            for issue in &issues {
+                println!("  {}", issue);
+            }
+
+            println!("regenerate the scenes then run 'show check {} {}'", name, episode.number);
+            return Ok(());
+
+        }
+        //
+        let found = self.block_on(continuity.extract(episode))?;
+        let count = found.len();
+
+        facts.record(episode.number, found);
+        facts.save(&path).change_context(EShow::Command)?;
+
+        project.set_status(episode.number, version, ReviewStatus::Accepted).change_context(EShow::Command)?;
+
+        println!("version {} of episode {} accepted with {} fact(s)", version, episode.number, count);
+
+        Ok(())
+        //
+    }
+    //
+    //
+    /// The passages of the previous episodes closest to a text, none before the first episode
+    ///
+    /// # Arguments
+    ///
+    /// * 'client' - what embeds the text
+    /// * 'name'   - the name of the series
+    /// * 'text'   - what the episode or the scene is about
+    ///
+    fn recall(&self, client: &Connection, name: &str, text: &str) -> Result<Vec<String>, EShow> {
+
+        let index = VectorIndex::load(&self.series_dir(name).join(INDEX_FILE)).change_context(EShow::Command)?;
+
+        let found = self.block_on(index.query(client, text, RECALLED, &[Source::Script]))?;
+
+        Ok(found.into_iter().map(|(_, entry)| entry.text.clone()).collect())
+
+    }
+    //
+    //
+    /// Index the bible, the facts and the scenes of an episode, only what changed is embedded
+    ///
+    /// # Arguments
+    ///
+    /// * 'client'  - what embeds the texts
+    /// * 'series'  - the bible of the series
+    /// * 'name'    - the name of the series
+    /// * 'episode' - the episode written or changed
+    ///
+    fn index(&self, client: &Connection, series: &Series, name: &str, episode: &Episode) -> Result<(), EShow> {
+        //
+        let path = self.series_dir(name).join(INDEX_FILE);
+        let mut vectors = VectorIndex::load(&path).change_context(EShow::Command)?;
+        let facts = self.load_facts(name)?;
+
+        self.block_on(async {
+
+            vectors.replace(client, "bible/", index::bible_documents(series)).await?;
+            vectors.replace(client, "fact/", index::fact_documents(&facts)).await?;
+            vectors.replace(client, &format!("episode/{}/", episode.number), index::episode_documents(episode)).await
+
+        })?;
+
+        vectors.save(&path).change_context(EShow::Command)
+        //
+    }
+    //
+    //
+    /// Play an episode in the terminal, a stored one or the next one while it is written
+    fn play(&self, args: PlayArgs) -> Result<(), EShow> {
+        //
+        if args.live {
+
+            let generate = GenerateEpisodeArgs { series: args.series, logline: args.logline, agents: false };
+
+            return self.generate_episode(generate, Some(args.speed));
+
+        }
+        //
+        let series = self.load_series(&args.series)?;
+        let number = args.episode.unwrap_or_default();
+        let episode = Episode::load(&self.episode_path(&args.series, number)).change_context(EShow::Command)?;
+
+        player::play(Playback::new(&series, &episode, Pacing::default()), None, args.speed)
+        //
+    }
+    //
+    //
+    /// Write an episode in another format
+    fn export(&self, args: ExportArgs) -> Result<(), EShow> {
+        //
+        let series = self.load_series(&args.series)?;
+
+        let path = self.episode_path(&args.series, args.episode);
+        let episode = Episode::load(&path).change_context(EShow::Command)?;
+        //
+        let subtitles = || {
+
+            let timeline = Timeline::build(&episode, &Pacing::default(), &HashMap::new());
+
+            subtitle::subtitles(&timeline, &SubtitleOptions::default())
+
+        };
+
+        let (content, extension) = match args.format {
+
+            ExportFormat::Fountain => (fountain::export(&episode, &series), FOUNTAIN_EXTENSION),
+            ExportFormat::Srt => (subtitle::to_srt(&subtitles()), SRT_EXTENSION),
+            ExportFormat::Vtt => (subtitle::to_vtt(&subtitles()), VTT_EXTENSION)
+
+        };
+        //
+        if self.dry_run {
+
+            print!("{}", content);
+            return Ok(());
+
+        }
+        //
+        let output = match args.output {
+
+            Some(output) => output,
+
+            None => {
+
+                let dir = self.series_dir(&args.series).join(EXPORTS_DIR);
+
+                std::fs::create_dir_all(&dir)
+                    .into_report()
+                    .change_context(EShow::Command)
+                    .attach_printable_lazy(|| format!("unable to create '{}'", dir.display()))?;
+
+                dir.join(format!("{:03}", args.episode)).with_extension(extension)
+
+            }
+
+        };
+
+        std::fs::write(&output, content)
+            .into_report()
+            .change_context(EShow::Command)
+            .attach_printable_lazy(|| format!("unable to write '{}'", output.display()))?;
+
+        println!("episode {} exported to '{}'", args.episode, output.display());
+
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Speak every line of an episode with the voices of its characters
+    #[cfg(feature = "tts")]
+    fn render(&self, args: crate::cli::RenderArgs) -> Result<(), EShow> {
+        //
+        use producer::audio::mix::{MixOptions, SoundBank};
+        use producer::audio::tts::{render_episode, Espeak, FormantSynth, VoiceProfile};
+        use producer::audio::SampleFormat;
+        //
+        let series = self.load_series(&args.series)?;
+        let episode = Episode::load(&self.episode_path(&args.series, args.episode)).change_context(EShow::Command)?;
+        let voices = VoiceProfile::cast(&series);
+        //
+        // the sounds of the series are optional, an explicit directory is not
+        let sounds = match args.sounds {
+
+            Some(dir) => SoundBank::load(&dir).change_context(EShow::Command)?,
+
+            None => match self.series_dir(&args.series).join(SOUNDS_DIR) {
+
+                dir if dir.is_dir() => SoundBank::load(&dir).change_context(EShow::Command)?,
+                _ => SoundBank::default()
+
+            }
+
+        };
+
+        let options = MixOptions {
+            format: if args.float { SampleFormat::Float32 } else { SampleFormat::Pcm16 },
+            ..MixOptions::default()
+        };
+        //
+        if self.dry_run {
+
+            for (name, voice) in &voices {
+                println!("{:<20} {:>5.0} Hz  rate {:.2}  timbre {:.2}", name, voice.pitch, voice.rate, voice.timbre);
+            }
+
+            return Ok(());
+
+        }
+        //
+        let dir = args.output.unwrap_or_else(|| self.series_dir(&args.series).join(AUDIO_DIR).join(format!("{:03}", args.episode)));
+
+        let rendered = match args.espeak {
+
+            true => render_episode(&Espeak::default(), &episode, &voices, &sounds, &Pacing::default(), &options, &dir),
+            false => render_episode(&FormantSynth::default(), &episode, &voices, &sounds, &Pacing::default(), &options, &dir)
+
+        }
+        .change_context(EShow::Command)?;
+        //
+        if !rendered.missing.is_empty() {
+            producer::cwarn!("no sound for the cues {}", rendered.missing.join(", "));
+        }
+
+        let length = rendered.timeline.duration().as_secs();
+
+        println!(
+            "episode {} rendered to '{}', {} lines, {}:{:02}",
+            args.episode, rendered.track.display(), rendered.lines.len(), length / 60, length % 60
+        );
+
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Save a Fountain script as the next episode, it is then used like a generated one
+    fn import(&self, args: ImportArgs) -> Result<(), EShow> {
+        //
+        let series = self.load_series(&args.series)?;
+
+        let text = std::fs::read_to_string(&args.file)
+            .into_report()
+            .change_context(EShow::Command)
+            .attach_printable_lazy(|| format!("unable to read '{}'", args.file.display()))?;
+
+        let mut episode = fountain::import(&text, &series)
+            .change_context(EShow::Command)
+            .attach_printable_lazy(|| format!("in '{}'", args.file.display()))?;
+        //
+        // the number of the title page is kept when it is free
+        let path = match episode.number {
+
+            0 => None,
+            n => Some(self.episode_path(&args.series, n)).filter(|p| !p.exists())
+
+        };
+
+        let path = path.unwrap_or_else(|| {
+
+            episode.number = self.next_episode(&args.series);
+            self.episode_path(&args.series, episode.number)
+
+        });
+        //
+        let scenes = episode.scenes().count();
+
+        if self.dry_run {
+
+            println!("would import {} scenes as episode {} in '{}'", scenes, episode.number, path.display());
+            return Ok(());
+
+        }
+
+        let note = format!("imported from '{}'", args.file.display());
+        let version = self.project(&args.series)?.commit_episode(&episode, &note).change_context(EShow::Command)?;
+
+        println!("imported {} scenes as episode {} version {} in '{}'", scenes, episode.number, version, path.display());
+
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Print the versions of the episodes with their review status
+    fn versions(&self, args: VersionsArgs) -> Result<(), EShow> {
+        //
+        let project = self.project(&args.series)?;
+
+        let numbers: Vec<u32> = match args.episode {
+
+            Some(number) => vec![number],
+            None => project.manifest.episodes.keys().copied().collect()
+
+        };
+        //
+        for number in numbers {
+
+            let record = project.manifest.episodes.get(&number).ok_or_else(|| {
+
+                EShow::Command.as_report().attach_printable(format!("episode {} of '{}' has no version", number, args.series))
+
+            })?;
+
+            println!("episode {}", number);
+
+            for version in &record.versions {
+
+                let current = if version.version == record.current { "*" } else { " " };
+
+                println!(
+                    "{} v{:<3} {:<9} {}  {}",
+                    current, version.version, version.status.to_str(), version.created, version.note
+                );
+
+            }
+
+        }
+        //
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Print the lines that changed between two versions of an episode
+    fn diff(&self, args: DiffArgs) -> Result<(), EShow> {
+        //
+        let project = self.project(&args.series)?;
+
+        let to = match args.to {
+
+            Some(to) => to,
+            None => project.manifest.episodes.get(&args.episode).map_or(0, |r| r.current)
+
+        };
+
+        let old = project.load_version(args.episode, args.from).change_context(EShow::Command)?;
+        let new = project.load_version(args.episode, to).change_context(EShow::Command)?;
+        //
+        let diff = project::Diff { changes: project::diff(&old, &new) };
+
+        println!("--- episode {} v{}\n+++ episode {} v{}", args.episode, args.from, args.episode, to);
+        print!("{}", diff);
+
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Make an older version of an episode the current one
+    fn rollback(&self, args: RollbackArgs) -> Result<(), EShow> {
+        //
+        let mut project = self.project(&args.series)?;
+
+        if self.dry_run {
+
+            let episode = project.load_version(args.episode, args.version).change_context(EShow::Command)?;
+
+            println!("would restore version {} of episode {}: {}", args.version, args.episode, episode.logline);
+            return Ok(());
+
+        }
+        //
+        project.rollback(args.episode, args.version).change_context(EShow::Command)?;
+
+        println!("episode {} is back to version {}", args.episode, args.version);
+        println!("run 'show check {} {}' to record its facts again", args.series, args.episode);
+
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Print the estimated tokens and price of each file sent as a prompt
+    fn cost(&self, args: CostArgs) -> Result<(), EShow> {
+        //
+        let mut total = 0;
+        //
+        for path in &args.files {
+
+            let text = std::fs::read_to_string(path)
+                .into_report()
+                .change_context(EShow::Command)
+                .attach_printable_lazy(|| format!("unable to read '{}'", path.display()))?;
+
+            let tokens = estimate_tokens(&text);
+            total += tokens;
+
+            println!("{:>8} tokens  ${:>9.4}  {}", tokens, self.model.cost(tokens), path.display());
+
+        }
+        //
+        println!("{:>8} tokens  ${:>9.4}  total with '{}'", total, self.model.cost(total), self.model.to_str());
+        //
+        if let Some(name) = &args.series {
+
+            let generations = self.project(name)?.generations().change_context(EShow::Command)?;
+
+            let tokens: usize = generations.iter().map(|g| g.tokens).sum();
+            let cost: f64 = generations.iter().map(|g| g.cost).sum();
+
+            println!("{:>8} tokens  ${:>9.4}  spent on {} generation(s) of '{}'", tokens, cost, generations.len(), name);
+
+        }
+        //
+        Ok(())
+        //
+    }
+    //
+    //
+    /// Run the future of a generation until it is done, on the runtime of the session
+    fn block_on<T>(&self, future: impl Future<Output = Result<T, producer::EGeneral>>) -> Result<T, EShow> {
+
+        self.runtime.block_on(future).change_context(EShow::Command)
+
+    }
+    //
+}
@@ -0,0 +1,141 @@
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Configuration
+//
+// Settings read from a TOML file, the command line flags have the priority over them
+//
+use std::path::{Path, PathBuf};
+
+use error_stack::{IntoReport, Result, ResultExt};
+use serde::Deserialize;
+
+use producer::ModelType;
+
+use crate::EShow;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Test
+//
+#[cfg(test)]
+mod test {
+
+    use std::path::Path;
+
+    use producer::ModelType;
+    use super::Config;
+
+    #[test]
+    fn missing_keys_use_the_default() {
+
+        let config = Config::parse("model = \"accurate\"").unwrap();
+
+        assert_eq!(config.model(None).unwrap(), ModelType::Accurate);
+        assert_eq!(config.series_dir, Path::new("series"));
+        assert!(config.log.is_none());
+
+    }
+
+    #[test]
+    fn command_line_model_win() {
+
+        let config = Config::parse("model = \"accurate\"").unwrap();
+
+        assert_eq!(config.model(Some("fastest")).unwrap(), ModelType::Fastest);
+        assert!(config.model(Some("unknown")).is_err());
+        assert!(Config::parse("model = 3").is_err());
+
+    }
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Config
+//
+/// file read when no configuration is given on the command line
+pub const DEFAULT_CONFIG: &str = "show.toml";
+//
+//
+/// Settings of the show
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+
+    /// name or alias of the model used for the generation
+    pub model:      Option<String>,
+    /// directory containing one directory per series
+    pub series_dir: PathBuf,
+    /// log filter like `producer=debug,warn`, the verbosity flags have the priority
+    pub log:        Option<String>,
+
+}
+//
+impl Default for Config {
+
+    fn default() -> Self { Self { model: None, series_dir: PathBuf::from("series"), log: None } }
+
+}
+//
+impl Config {
+    //
+    /// Read the configuration file, the default one is optional
+    ///
+    /// # Arguments
+    ///
+    /// * 'path' - file given on the command line
+    ///
+    pub fn load(path: Option<&Path>) -> Result<Self, EShow> {
+        //
+        let path = match path {
+
+            Some(path) => path,
+
+            None if Path::new(DEFAULT_CONFIG).is_file() => Path::new(DEFAULT_CONFIG),
+
+            None => return Ok(Self::default())
+
+        };
+        //
+        let content = std::fs::read_to_string(path)
+            .into_report()
+            .change_context(EShow::Config)
+            .attach_printable_lazy(|| format!("unable to read '{}'", path.display()))?;
+
+        Self::parse(&content).attach_printable_lazy(|| format!("in '{}'", path.display()))
+        //
+    }
+    //
+    //
+    /// Parse the content of a configuration file
+    ///
+    /// # Arguments
+    ///
+    /// * 'content' - the TOML text
+    ///
+    pub fn parse(content: &str) -> Result<Self, EShow> {
+
+        toml::from_str(content).into_report().change_context(EShow::Config)
+
+    }
+    //
+    //
+    /// Model to use, the one given on the command line first
+    ///
+    /// # Arguments
+    ///
+    /// * 'cli' - the model given on the command line
+    ///
+    pub fn model(&self, cli: Option<&str>) -> Result<ModelType, EShow> {
+
+        match cli.or(self.model.as_deref()) {
+
+            Some(name) => name.parse::<ModelType>().change_context(EShow::Config),
+            None => Ok(ModelType::MostAccurate)
+
+        }
+
+    }
+    //
+}
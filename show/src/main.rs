@@ -1,3 +1,117 @@
+mod cli;
+mod command;
+mod config;
+mod player;
+
+use clap::Parser;
+
+use producer::logger::{self, Level};
+use producer::creport;
+
+use cli::{Cli, GlobalArgs};
+use command::Session;
+use config::Config;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Entry point
+//
 fn main() {
-    println!("Hello, world!");
+    //
+    let cli = Cli::parse();
+
+    // the panics are written by the logger instead of the default hook
+    std::panic::set_hook(Box::new(|_| ()));
+
+    logger::install_panic_hook();
+    //
+    let result = Config::load(cli.global.config.as_deref())
+        .and_then(|config| {
+
+            setup_logger(&config, &cli.global)?;
+
+            Session::new(config, &cli.global)
+
+        })
+        .and_then(|session| session.run(cli.command));
+    //
+    let code = match result {
+
+        Ok(()) => 0,
+
+        Err(report) => { creport!(Level::ERROR, report); 1 }
+
+    };
+
+    logger::shutdown();
+
+    std::process::exit(code);
+    //
+}
+//
+//
+/// Set the log filter, the verbosity flags first, then the configuration and the environment
+///
+/// Without any of them only the warnings and the errors are shown
+///
+/// # Arguments
+///
+/// * 'config' - the loaded configuration
+/// * 'global' - the flags shared by every subcommand
+///
+fn setup_logger(config: &Config, global: &GlobalArgs) -> error_stack::Result<(), EShow> {
+    //
+    match (global.level(), &config.log) {
+
+        (Some(level), _) => logger::set_max_level(level),
+
+        (None, Some(spec)) => logger::set_filter(spec).change_context(EShow::Config)?,
+
+        (None, None) if std::env::var(logger::LOG_FILTER_ENV).is_err() => logger::set_max_level(Level::WARN),
+
+        (None, None) => ()
+
+    }
+    //
+    logger::init();
+
+    Ok(())
+    //
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Error
+//
+use error_stack::{Context, Report, ResultExt};
+
+#[derive(Debug, Copy, Clone)]
+pub enum EShow {
+
+    Config,
+    Command
+
+}
+//
+impl std::fmt::Display for EShow {
+
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+
+        match self {
+
+            Self::Config =>     write!(f, "Invalid Configuration"),
+            Self::Command =>    write!(f, "Command Failed"),
+
+        }
+
+    }
+
+}
+//
+impl Context for EShow {}
+//
+impl EShow {
+
+    pub(crate) fn as_report(&self) -> Report<Self> { Report::new(*self) }
+
 }
@@ -0,0 +1,174 @@
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Player
+//
+// Play an episode in the terminal, the keys are read without waiting for enter while it plays.
+// The terminal is put in that mode with stty, without it the episode plays without controls.
+// Ctrl-c is then read as a key quitting the playback, so the terminal is always restored.
+//
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+use error_stack::{IntoReport, Result, ResultExt};
+
+use producer::cdebug;
+use producer::episode::Episode;
+use producer::playback::{controls, Control, Playback};
+
+use crate::EShow;
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Terminal
+//
+// time between two updates of the screen
+const TICK: Duration = Duration::from_millis(30);
+//
+//
+/// The terminal reading the keys one by one without showing them, restored when dropped
+struct RawMode { saved: String }
+//
+impl RawMode {
+    //
+    /// Change the mode of the terminal, none if stdin is not a terminal or stty is missing
+    fn enable() -> Option<Self> {
+        //
+        let stty = |args: &[&str]| Command::new("stty")
+            .args(args)
+            .stdin(Stdio::inherit())
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success());
+
+        let saved = String::from_utf8_lossy(&stty(&["-g"])?.stdout).trim().to_string();
+
+        stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+        //
+        Some(Self { saved })
+        //
+    }
+    //
+}
+//
+impl Drop for RawMode {
+
+    fn drop(&mut self) {
+
+        let _ = Command::new("stty").arg(&self.saved).stdin(Stdio::inherit()).status();
+
+    }
+
+}
+//
+//
+/// Read the keys of the viewer in a thread, it ends with stdin
+fn read_controls() -> Receiver<Control> {
+    //
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::spawn(move || {
+
+        let mut keys = [0u8; 16];
+
+        while let Ok(count @ 1..) = std::io::stdin().read(&mut keys) {
+
+            if controls(&keys[..count]).into_iter().any(|control| sender.send(control).is_err()) {
+                break;
+            }
+
+        }
+
+    });
+    //
+    receiver
+    //
+}
+//
+//
+/// Write the text of the playback right away
+fn show(text: &str) -> Result<(), EShow> {
+
+    let mut stdout = std::io::stdout();
+
+    stdout.write_all(text.as_bytes())
+        .and_then(|_| stdout.flush())
+        .into_report()
+        .change_context(EShow::Command)
+
+}
+//
+//
+// ------------------------------------------------------------------------------------------------
+// Playing
+//
+/// Play an episode until its end or until the viewer quits
+///
+/// # Arguments
+///
+/// * 'playback' - the episode to play
+/// * 'live'     - the versions of an episode being written, the playback ends with it
+/// * 'speed'    - how fast the episode is played, 2 is twice as fast
+///
+pub fn play(mut playback: Playback, mut live: Option<Receiver<Episode>>, speed: f64) -> Result<(), EShow> {
+    //
+    let raw = RawMode::enable();
+    let keys = raw.as_ref().map(|_| read_controls());
+
+    match raw {
+
+        Some(_) => println!("{}", "space: pause  n: skip  b: rewind  q: quit".dimmed()),
+        None => cdebug!("stdin is not a terminal, the episode plays without controls")
+
+    }
+    //
+    let mut last = Instant::now();
+
+    while !playback.is_done() {
+
+        for control in keys.iter().flat_map(|k| k.try_iter()) {
+
+            if control == Control::Quit {
+
+                if live.is_some() {
+                    println!("\nthe playback is stopped, the episode is still being written");
+                }
+
+                return show("\n");
+
+            }
+
+            show(&playback.control(control))?;
+
+        }
+
+        // every version of the episode written since the last tick
+        while let Some(updates) = &live {
+
+            match updates.try_recv() {
+
+                Ok(episode) => playback.update(&episode),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => { playback.finish(); live = None; }
+
+            }
+
+        }
+
+        let now = Instant::now();
+
+        show(&playback.advance((now - last).mul_f64(speed)))?;
+
+        last = now;
+
+        std::thread::sleep(TICK);
+
+    }
+    //
+    Ok(())
+    //
+}